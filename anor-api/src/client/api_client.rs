@@ -1,101 +1,269 @@
-use anor_storage::storage::storage_item::StorageItem;
-use anor_utils::config::Config;
-use std::io::prelude::*;
+use crate::protocol::{api_protocol::*, api_wire::*};
+use anor_storage::storage::storage_item::{ItemType, StorageItem};
+use anor_utils::config::{ApiConfig, Config};
+use std::io::{self, prelude::*};
 use std::net::TcpStream;
 use std::sync::Arc;
 
 pub trait SocketClient {
     fn with_config(config: Arc<Config>) -> Self;
-    fn connect(&mut self) -> std::io::Result<()>;
-    fn disconnect(&mut self) -> std::io::Result<()>;
-    fn insert(&self, storage_item: StorageItem);
-    fn update(&mut self, key: &str, storage_item: StorageItem) -> std::io::Result<()>;
-    fn get(&mut self, key: &str) -> std::io::Result<StorageItem>;
-    fn remove(&self, key: &str) -> bool;
-    fn keys(&self) -> Vec<String>;
-    fn clear(&self);
-    fn flush(&self);
+    fn connect(&mut self) -> io::Result<()>;
+    fn disconnect(&mut self) -> io::Result<()>;
+    fn insert(&mut self, storage_item: StorageItem) -> io::Result<()>;
+    fn update(&mut self, key: &str, storage_item: StorageItem) -> io::Result<()>;
+    fn get(&mut self, key: &str) -> io::Result<Option<StorageItem>>;
+    fn remove(&mut self, key: &str) -> io::Result<bool>;
+    fn keys(&mut self) -> io::Result<Vec<String>>;
+    fn clear(&mut self) -> io::Result<()>;
+    fn flush(&mut self) -> io::Result<()>;
 }
-pub struct Client {
+
+pub struct StorageApiClient {
     stream: Option<TcpStream>,
     config: Arc<Config>,
+    protocol_version: Option<ProtocolVersion>,
 }
 
-impl SocketClient for Client {
+impl StorageApiClient {
+    /// Returns the protocol version agreed with the connected node
+    pub fn protocol_version(&self) -> Option<ProtocolVersion> {
+        self.protocol_version
+    }
+
+    /// Returns the inner object of the remote item corresponding to the key
+    pub fn get_object<T: bincode::Decode>(&mut self, key: &str) -> io::Result<Option<T>> {
+        match self.get(key)? {
+            Some(item) => match item.get_object() {
+                Some(obj) => Ok(Some(obj)),
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Could not decode the object of item: `{}`", key),
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Sets the inner object of the remote item corresponding to the key
+    /// The item will be inserted if the remote storage does not have an item with the key present
+    pub fn set_object<T: bincode::Encode>(
+        &mut self,
+        key: &str,
+        item_type: ItemType,
+        obj: &T,
+    ) -> io::Result<()> {
+        let storage_item = match self.get(key)? {
+            Some(mut item) => {
+                item.item_type = item_type;
+                if !item.update_object(obj) {
+                    return Err(encode_error(key));
+                }
+                item
+            }
+            None => match StorageItem::with_type(key, item_type, obj) {
+                Some(item) => item,
+                None => return Err(encode_error(key)),
+            },
+        };
+        self.insert(storage_item)
+    }
+
+    /// Sends the request and waits for the response
+    /// Returns the payload of a succeeded request or `None` if the item was not found
+    fn request(&mut self, request: &ApiRequest) -> io::Result<Option<ApiPayload>> {
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "Client is not connected!",
+                ))
+            }
+        };
+
+        write_frame(stream, request).map_err(timed_out)?;
+        match read_frame(stream).map_err(timed_out)? {
+            ApiResponse::Ok(payload) => Ok(Some(payload)),
+            ApiResponse::NotFound => Ok(None),
+            ApiResponse::Error(err) => Err(io::Error::other(err)),
+            ApiResponse::Unauthorized => Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Unauthorized request: `{}`", request.name()),
+            )),
+        }
+    }
+
+    /// Sends the request and waits for the payload of the response
+    /// A `NotFound` response is an error for requests not looking up an item
+    fn request_payload(&mut self, request: &ApiRequest) -> io::Result<ApiPayload> {
+        match self.request(request)? {
+            Some(payload) => Ok(payload),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Not found: `{}`", request.name()),
+            )),
+        }
+    }
+}
+
+impl SocketClient for StorageApiClient {
     fn with_config(config: Arc<Config>) -> Self {
-        Client {
+        StorageApiClient {
             stream: None,
             config,
+            protocol_version: None,
         }
     }
 
-    fn connect(&mut self) -> std::io::Result<()> {
+    /// Connects to the first available remote node
+    /// Nodes are attempted in priority order, falling back to lower priorities on failure
+    fn connect(&mut self) -> io::Result<()> {
         assert!(self.config.remote.is_some());
         let config_remote = self.config.remote.as_ref().unwrap();
         assert!(!config_remote.nodes.is_empty());
-        let remote_address = config_remote.nodes[0];
 
-        let stream = TcpStream::connect(remote_address)?;
+        let mut last_err = None;
+        for node in config_remote.nodes_by_priority() {
+            let remote_address = &node.address;
+            let mut stream = match TcpStream::connect(remote_address) {
+                Ok(stream) => stream,
+                Err(err) => {
+                    tracing::warn!("could not connect to {}: {}", remote_address, err);
+                    last_err = Some(err);
+                    continue;
+                }
+            };
 
-        let local_addr = stream.local_addr().unwrap();
-        tracing::info!("connected to {} as {}", remote_address, local_addr);
+            let local_addr = stream.local_addr().unwrap();
+            tracing::info!("connected to {} as {}", remote_address, local_addr);
 
-        stream.set_nodelay(true).expect("set_nodelay call failed");
+            stream.set_nodelay(true).expect("set_nodelay call failed");
 
-        self.stream = Some(stream);
-        Ok(())
+            let default_config_api = ApiConfig::default();
+            let config_api = self.config.api.as_ref().unwrap_or(&default_config_api);
+            let io_timeout = config_api.socket_timeout();
+            stream.set_read_timeout(io_timeout)?;
+            stream.set_write_timeout(io_timeout)?;
+            set_keepalive(&stream, config_api.keepalive.as_ref())?;
+
+            let protocol_version = match handshake(&mut stream, PROTOCOL_VERSION) {
+                Ok(protocol_version) => protocol_version,
+                Err(err) => {
+                    tracing::warn!("handshake with {} failed: {}", remote_address, err);
+                    last_err = Some(err);
+                    continue;
+                }
+            };
+            tracing::debug!(
+                "agreed protocol version {} with {}",
+                protocol_version,
+                remote_address
+            );
+
+            self.stream = Some(stream);
+            self.protocol_version = Some(protocol_version);
+            return Ok(());
+        }
+
+        Err(last_err.unwrap())
     }
 
-    fn disconnect(&mut self) -> std::io::Result<()> {
+    fn disconnect(&mut self) -> io::Result<()> {
         let stream = self.stream.as_mut().unwrap();
         stream.flush()?;
         self.stream = None;
+        self.protocol_version = None;
         Ok(())
     }
 
-/*
-    fn set_item(&mut self, key: String) -> std::io::Result<()> {
-        let stream = self.stream.as_mut().unwrap();
-        stream.write_all(key.as_bytes())?;
-        // stream.shutdown(std::net::Shutdown::Write).unwrap();
-        stream.flush().unwrap();
-        Ok(())
+    fn insert(&mut self, storage_item: StorageItem) -> io::Result<()> {
+        match self.request_payload(&ApiRequest::Insert(storage_item))? {
+            ApiPayload::Done => Ok(()),
+            payload => Err(unexpected_response(payload)),
+        }
     }
 
-    fn get_item(&mut self, _key: String) -> std::io::Result<()> {
-        let stream = self.stream.as_mut().unwrap();
-        let mut buf = [0; 128];
-        stream.write_all(&buf)?;
-        stream.read_exact(&mut buf)?;
-        Ok(())
+    fn update(&mut self, key: &str, mut storage_item: StorageItem) -> io::Result<()> {
+        storage_item.key = key.to_owned();
+        self.insert(storage_item)
     }
-*/
 
-    fn insert(&self, _storage_item: StorageItem) {
-        todo!()
+    fn get(&mut self, key: &str) -> io::Result<Option<StorageItem>> {
+        match self.request(&ApiRequest::Get(key.to_owned()))? {
+            Some(ApiPayload::Item(item)) => Ok(Some(item)),
+            None => Ok(None),
+            Some(payload) => Err(unexpected_response(payload)),
+        }
     }
 
-    fn update(&mut self, _key: &str, _storage_item: StorageItem) -> std::io::Result<()> {
-        todo!()
+    fn remove(&mut self, key: &str) -> io::Result<bool> {
+        match self.request_payload(&ApiRequest::Remove(key.to_owned()))? {
+            ApiPayload::Removed(removed) => Ok(removed),
+            payload => Err(unexpected_response(payload)),
+        }
     }
 
-    fn get(&mut self, _key: &str) -> std::io::Result<StorageItem> {
-        todo!()
+    fn keys(&mut self) -> io::Result<Vec<String>> {
+        match self.request_payload(&ApiRequest::Keys)? {
+            ApiPayload::Keys(keys) => Ok(keys),
+            payload => Err(unexpected_response(payload)),
+        }
     }
 
-    fn remove(&self, _key: &str) -> bool {
-        todo!()
+    fn clear(&mut self) -> io::Result<()> {
+        match self.request_payload(&ApiRequest::Clear)? {
+            ApiPayload::Done => Ok(()),
+            payload => Err(unexpected_response(payload)),
+        }
     }
 
-    fn keys(&self) -> Vec<String> {
-        vec![]
+    fn flush(&mut self) -> io::Result<()> {
+        match self.request_payload(&ApiRequest::Flush)? {
+            ApiPayload::Done => Ok(()),
+            payload => Err(unexpected_response(payload)),
+        }
     }
+}
 
-    fn clear(&self) {
-        todo!()
+/// Performs the handshake opening a connection, offering the protocol version
+/// Returns the version agreed with the service
+/// A rejected or unsupported version is reported as an `Unsupported` error
+pub fn handshake<S: Read + Write>(
+    stream: &mut S,
+    version: ProtocolVersion,
+) -> io::Result<ProtocolVersion> {
+    write_frame(stream, &ApiHandshake { version }).map_err(timed_out)?;
+    match read_frame(stream).map_err(timed_out)? {
+        ApiHandshakeResponse::Accepted(agreed) if agreed >= MIN_PROTOCOL_VERSION => Ok(agreed),
+        ApiHandshakeResponse::Accepted(agreed) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Unsupported protocol version of the service: {}", agreed),
+        )),
+        ApiHandshakeResponse::Rejected(reason) => {
+            Err(io::Error::new(io::ErrorKind::Unsupported, reason))
+        }
     }
+}
 
-    fn flush(&self) {
-        todo!()
+fn encode_error(key: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Could not encode the object of item: `{}`", key),
+    )
+}
+
+/// Reports an expired socket timeout as `TimedOut`, some platforms report it as `WouldBlock`
+fn timed_out(err: io::Error) -> io::Error {
+    if err.kind() == io::ErrorKind::WouldBlock {
+        io::Error::new(io::ErrorKind::TimedOut, err)
+    } else {
+        err
     }
 }
+
+fn unexpected_response(payload: ApiPayload) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Unexpected response: {:?}", payload),
+    )
+}
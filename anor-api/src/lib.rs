@@ -6,6 +6,9 @@
 
 pub mod service;
 pub mod client;
+pub mod protocol;
 
 pub use service::api_service::*;
-pub use client::api_client::*;
\ No newline at end of file
+pub use client::api_client::*;
+pub use protocol::api_protocol::*;
+pub use protocol::api_wire::*;
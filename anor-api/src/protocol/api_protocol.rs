@@ -0,0 +1,156 @@
+use anor_storage::StorageItem;
+use anor_utils::config::KeepaliveConfig;
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::net::TcpStream;
+
+/// Version of the API protocol, agreed by the handshake opening each connection
+pub type ProtocolVersion = u16;
+
+/// Protocol version spoken by this build, bumped on incompatible changes of the messages
+pub const PROTOCOL_VERSION: ProtocolVersion = 1;
+
+/// Oldest protocol version still supported
+pub const MIN_PROTOCOL_VERSION: ProtocolVersion = 1;
+
+/// Handshake sent by the client as the first message of a connection
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub struct ApiHandshake {
+    /// the latest protocol version supported by the client
+    pub version: ProtocolVersion,
+}
+
+/// Handshake response of the service
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub enum ApiHandshakeResponse {
+    /// the version agreed for the connection
+    Accepted(ProtocolVersion),
+    /// the client version is not supported, the service closes the connection
+    Rejected(String),
+}
+
+impl ApiHandshakeResponse {
+    /// Returns the response of a peer supporting the protocol versions from `MIN_PROTOCOL_VERSION` to `PROTOCOL_VERSION`
+    /// The agreed version is the latest one supported by both peers
+    pub fn negotiate(version: ProtocolVersion) -> Self {
+        if version < MIN_PROTOCOL_VERSION {
+            ApiHandshakeResponse::Rejected(format!(
+                "Unsupported protocol version: {}, supported versions: {}..={}",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ))
+        } else {
+            ApiHandshakeResponse::Accepted(version.min(PROTOCOL_VERSION))
+        }
+    }
+}
+
+/// API request sent by the client to the service
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub enum ApiRequest {
+    Keys,
+    Get(String),
+    Insert(StorageItem),
+    Remove(String),
+    Clear,
+    Flush,
+}
+
+impl ApiRequest {
+    /// Returns the command name of the request
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiRequest::Keys => "keys",
+            ApiRequest::Get(_) => "get",
+            ApiRequest::Insert(_) => "insert",
+            ApiRequest::Remove(_) => "remove",
+            ApiRequest::Clear => "clear",
+            ApiRequest::Flush => "flush",
+        }
+    }
+
+    /// Returns whether the request modifies the storage
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            ApiRequest::Insert(_) | ApiRequest::Remove(_) | ApiRequest::Clear | ApiRequest::Flush
+        )
+    }
+}
+
+/// API response sent by the service to the client
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub enum ApiResponse {
+    /// the request succeeded
+    Ok(ApiPayload),
+    /// the requested item does not exist
+    NotFound,
+    /// the request failed or could not be decoded
+    Error(String),
+    /// the client is not allowed to perform the request
+    Unauthorized,
+}
+
+impl ApiResponse {
+    /// Returns the status name of the response
+    pub fn status(&self) -> &'static str {
+        match self {
+            ApiResponse::Ok(_) => "ok",
+            ApiResponse::NotFound => "not_found",
+            ApiResponse::Error(_) => "error",
+            ApiResponse::Unauthorized => "unauthorized",
+        }
+    }
+}
+
+/// Payload of a succeeded request
+#[derive(Debug, bincode::Encode, bincode::Decode)]
+pub enum ApiPayload {
+    Done,
+    Keys(Vec<String>),
+    Item(StorageItem),
+    Removed(bool),
+}
+
+/// Sets the TCP keepalive of the stream, so connections to dead peers are detected by the OS
+/// Keepalive is disabled if no parameters are given
+pub fn set_keepalive(stream: &TcpStream, keepalive: Option<&KeepaliveConfig>) -> io::Result<()> {
+    let socket = SockRef::from(stream);
+    match keepalive {
+        Some(keepalive) => {
+            let params = TcpKeepalive::new().with_time(keepalive.idle);
+            #[cfg(any(
+                target_os = "android",
+                target_os = "freebsd",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "windows",
+            ))]
+            let params = params.with_interval(keepalive.interval);
+            socket.set_tcp_keepalive(&params)
+        }
+        None => socket.set_keepalive(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_test() {
+        assert!(matches!(
+            ApiHandshakeResponse::negotiate(PROTOCOL_VERSION),
+            ApiHandshakeResponse::Accepted(PROTOCOL_VERSION)
+        ));
+        // a newer client falls back to the version of the service
+        assert!(matches!(
+            ApiHandshakeResponse::negotiate(PROTOCOL_VERSION + 1),
+            ApiHandshakeResponse::Accepted(PROTOCOL_VERSION)
+        ));
+        assert!(matches!(
+            ApiHandshakeResponse::negotiate(MIN_PROTOCOL_VERSION - 1),
+            ApiHandshakeResponse::Rejected(_)
+        ));
+    }
+}
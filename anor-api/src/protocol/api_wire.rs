@@ -0,0 +1,193 @@
+//! Wire format of the API protocol
+//! Messages are encoded with the bincode configuration of the protocol and sent as length-prefixed frames,
+//! independently of the codec of the persisted storage data
+
+use super::api_protocol::{ApiRequest, ApiResponse};
+use std::io::{self, Read, Write};
+
+/// Size of the frame length prefix in bytes
+const API_FRAME_LENGTH_SIZE: usize = 8;
+
+/// Returns the bincode configuration of the messages on the wire
+fn wire_config() -> bincode::config::Configuration {
+    bincode::config::standard()
+}
+
+/// Encodes the request into the data of a frame
+pub fn encode_request(request: &ApiRequest) -> io::Result<Vec<u8>> {
+    encode_message(request)
+}
+
+/// Decodes the request from the data of a frame
+pub fn decode_request(data: &[u8]) -> io::Result<ApiRequest> {
+    decode_message(data)
+}
+
+/// Encodes the response into the data of a frame
+pub fn encode_response(response: &ApiResponse) -> io::Result<Vec<u8>> {
+    encode_message(response)
+}
+
+/// Decodes the response from the data of a frame
+pub fn decode_response(data: &[u8]) -> io::Result<ApiResponse> {
+    decode_message(data)
+}
+
+fn encode_message<T: bincode::Encode>(message: &T) -> io::Result<Vec<u8>> {
+    bincode::encode_to_vec(message, wire_config()).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Could not encode message: {}", err),
+        )
+    })
+}
+
+fn decode_message<T: bincode::Decode>(data: &[u8]) -> io::Result<T> {
+    match bincode::decode_from_slice(data, wire_config()) {
+        Ok((message, _len)) => Ok(message),
+        Err(err) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Could not decode message: {}", err),
+        )),
+    }
+}
+
+/// Encodes the message and writes it as a length-prefixed frame
+/// Returns the number of bytes written
+pub fn write_frame<T: bincode::Encode>(stream: &mut impl Write, message: &T) -> io::Result<usize> {
+    let data = encode_message(message)?;
+
+    stream.write_all(&(data.len() as u64).to_be_bytes())?;
+    stream.write_all(&data)?;
+    stream.flush()?;
+    Ok(API_FRAME_LENGTH_SIZE + data.len())
+}
+
+/// Reads a length-prefixed frame and decodes the message
+pub fn read_frame<T: bincode::Decode>(stream: &mut impl Read) -> io::Result<T> {
+    let data = read_frame_data(stream)?;
+    decode_frame_data(&data)
+}
+
+/// Reads the data of a length-prefixed frame without decoding it
+pub fn read_frame_data(stream: &mut impl Read) -> io::Result<Vec<u8>> {
+    read_frame_data_limited(stream, u64::MAX)
+}
+
+/// Reads the data of a length-prefixed frame without decoding it
+/// Returns an `InvalidData` error if the frame is longer than `max_length`, before any of its data is read
+pub fn read_frame_data_limited(stream: &mut impl Read, max_length: u64) -> io::Result<Vec<u8>> {
+    let mut length_arr = [0_u8; API_FRAME_LENGTH_SIZE];
+    stream.read_exact(&mut length_arr)?;
+    let length = u64::from_be_bytes(length_arr);
+    if length > max_length {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Frame length {} exceeds the maximum of {}",
+                length, max_length
+            ),
+        ));
+    }
+
+    // the data is read without trusting the length for an allocation up front
+    let mut data = vec![];
+    stream.take(length).read_to_end(&mut data)?;
+    if (data.len() as u64) < length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Frame is truncated",
+        ));
+    }
+    Ok(data)
+}
+
+/// Decodes the message from the data of a frame
+pub fn decode_frame_data<T: bincode::Decode>(data: &[u8]) -> io::Result<T> {
+    decode_message(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::api_protocol::ApiPayload;
+    use anor_storage::StorageItem;
+
+    #[test]
+    fn request_roundtrip_test() {
+        let requests = [
+            ApiRequest::Keys,
+            ApiRequest::Get("my_key".into()),
+            ApiRequest::Insert(StorageItem::new("my_key", &String::from("abc")).unwrap()),
+            ApiRequest::Remove("my_key".into()),
+            ApiRequest::Clear,
+            ApiRequest::Flush,
+        ];
+        for request in requests {
+            let decoded = decode_request(&encode_request(&request).unwrap()).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", request));
+        }
+    }
+
+    #[test]
+    fn response_roundtrip_test() {
+        let responses = [
+            ApiResponse::Ok(ApiPayload::Done),
+            ApiResponse::Ok(ApiPayload::Keys(vec!["first".into(), "second".into()])),
+            ApiResponse::Ok(ApiPayload::Item(
+                StorageItem::new("my_key", &String::from("abc")).unwrap(),
+            )),
+            ApiResponse::Ok(ApiPayload::Removed(true)),
+            ApiResponse::NotFound,
+            ApiResponse::Error("failed".into()),
+            ApiResponse::Unauthorized,
+        ];
+        for response in responses {
+            let decoded = decode_response(&encode_response(&response).unwrap()).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", response));
+        }
+    }
+
+    #[test]
+    fn frame_roundtrip_test() {
+        let mut buf = vec![];
+        write_frame(&mut buf, &ApiRequest::Get("my_key".into())).unwrap();
+        write_frame(&mut buf, &ApiResponse::Error("failed".into())).unwrap();
+
+        let mut reader = buf.as_slice();
+        let request: ApiRequest = read_frame(&mut reader).unwrap();
+        assert!(matches!(request, ApiRequest::Get(key) if key == "my_key"));
+        let data = read_frame_data(&mut reader).unwrap();
+        let response = decode_response(&data).unwrap();
+        assert!(matches!(response, ApiResponse::Error(err) if err == "failed"));
+    }
+
+    #[test]
+    fn malformed_frame_test() {
+        let mut buf = vec![];
+        buf.extend_from_slice(&3_u64.to_be_bytes());
+        buf.extend_from_slice(&[0xff, 0xff, 0xff]);
+
+        let data = read_frame_data(&mut buf.as_slice()).unwrap();
+        let result = decode_request(&data);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn frame_length_limit_test() {
+        let mut buf = vec![];
+        write_frame(&mut buf, &ApiRequest::Get("my_key".into())).unwrap();
+        let length = (buf.len() - API_FRAME_LENGTH_SIZE) as u64;
+        assert!(read_frame_data_limited(&mut buf.as_slice(), length).is_ok());
+        let err = read_frame_data_limited(&mut buf.as_slice(), length - 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // a huge length prefix is neither trusted for an allocation nor read past the data
+        let mut buf = u64::MAX.to_be_bytes().to_vec();
+        buf.extend_from_slice(b"abc");
+        let err = read_frame_data_limited(&mut buf.as_slice(), 1024).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read_frame_data(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
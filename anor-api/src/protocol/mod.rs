@@ -0,0 +1,2 @@
+pub mod api_protocol;
+pub mod api_wire;
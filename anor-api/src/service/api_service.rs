@@ -1,17 +1,27 @@
-use std::io::prelude::*;
-use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, TcpStream};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anor_storage::{Storage, StorageItem};
-use anor_utils::{Config, ThreadPool};
+use anor_utils::config::ApiConfig;
+use anor_utils::net::bind_with_retries;
+use anor_utils::{Config, Metrics, ShutdownToken, ThreadPool};
+
+use crate::protocol::{api_protocol::*, api_wire::*};
+
+/// interval to check the shutdown state while waiting for connections and requests
+const SHUTDOWN_POLL_INTERVAL_MILLISECONDS: u64 = 10;
 
 pub trait ApiService {
     fn with_config(storage: Arc<Storage>, config: Arc<Config>) -> Self;
     fn start(
         &self,
-        server_shutdown: Arc<AtomicBool>,
+        server_shutdown: Arc<ShutdownToken>,
         signal_server_ready: Sender<()>,
     ) -> Result<(), String>;
     fn stop(&self);
@@ -24,26 +34,58 @@ pub trait ApiService {
 pub struct Service {
     storage: Arc<Storage>,
     config: Arc<Config>,
+    shutdown: Arc<AtomicBool>,
+    metrics: Arc<Metrics>,
+    local_addr: OnceLock<SocketAddr>,
 }
 
 pub type ApiMutex<'a> = Arc<Mutex<Service>>;
 
+impl Service {
+    /// Returns the request metrics of the service
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Returns the bound address of the service, once the ready signal is sent
+    /// Resolves the actual port if the service listens on port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr.get().copied()
+    }
+}
+
 impl ApiService for Service {
     fn with_config(storage: Arc<Storage>, config: Arc<Config>) -> Self {
-        Service { storage, config }
+        Service {
+            storage,
+            config,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(Metrics::new()),
+            local_addr: OnceLock::new(),
+        }
     }
 
     fn start(
         &self,
-        server_shutdown: Arc<AtomicBool>,
+        server_shutdown: Arc<ShutdownToken>,
         signal_ready_sender: Sender<()>,
     ) -> Result<(), String> {
         assert!(self.config.api.is_some());
         let config_server = self.config.api.as_ref().unwrap();
         assert!(!config_server.listen_on.is_empty());
-        let listen_on = config_server.listen_on[0];
+        let listen_on = &config_server.listen_on[0];
+
+        let listener = match bind_with_retries(listen_on, config_server.bind_retries) {
+            Ok(listener) => listener,
+            Err(err) => {
+                tracing::error!("API service could not listen on {}: {}", listen_on, err);
+                return Err(err.to_string());
+            }
+        };
 
-        let listener = TcpListener::bind(listen_on).unwrap();
+        if let Ok(local_addr) = listener.local_addr() {
+            _ = self.local_addr.set(local_addr);
+        }
 
         // send the ready signal
         if let Err(err) = signal_ready_sender.send(()) {
@@ -51,68 +93,416 @@ impl ApiService for Service {
         }
 
         tracing::info!("API service listening on {} ...", listen_on);
-        // listener.set_nonblocking(true).unwrap();
+
+        // non-blocking accept allows to observe the shutdown state without pending connections
+        if let Err(err) = listener.set_nonblocking(true) {
+            return Err(err.to_string());
+        }
 
         let pool = ThreadPool::new(2);
+        let limiter = Arc::new(ConnectionLimiter::new(config_server.max_conns_per_ip));
 
-        while !server_shutdown.load(Ordering::SeqCst) {
+        while !server_shutdown.is_triggered() && !self.shutdown.load(Ordering::SeqCst) {
             match listener.accept() {
                 Ok((stream, addr)) => {
-                    let shutdown_clone = server_shutdown.clone();
+                    if !limiter.try_acquire(addr.ip()) {
+                        tracing::warn!(
+                            "Refusing connection of {}, the connection limit per IP is reached",
+                            addr
+                        );
+                        drop(stream);
+                        continue;
+                    }
+                    if let Err(err) = prepare_stream(&stream, config_server) {
+                        limiter.release(addr.ip());
+                        tracing::error!("couldn't prepare the connection of {}: {}", addr, err);
+                        continue;
+                    }
+                    let storage_clone = self.storage.clone();
+                    let shutdown_clone = self.shutdown.clone();
+                    let metrics_clone = self.metrics.clone();
+                    let settings = ConnectionSettings::new(config_server);
+                    let limiter_clone = limiter.clone();
+                    tracing::trace!(
+                        "API connection pool: {} active, {} queued",
+                        pool.active_count(),
+                        pool.queued_count()
+                    );
                     pool.execute(move || {
-                        handle_connection(stream, addr, shutdown_clone);
+                        metrics_clone.connection_opened();
+                        handle_connection(
+                            stream,
+                            addr,
+                            storage_clone,
+                            shutdown_clone,
+                            &metrics_clone,
+                            settings,
+                        );
+                        metrics_clone.connection_closed();
+                        limiter_clone.release(addr.ip());
                     });
                 }
-                /*
-                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                    // wait until network socket is ready, typically implemented
-                    // via platform-specific APIs such as epoll or IOCP
-                    thread::sleep(time::Duration::from_millis(1));
-                    continue;
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(SHUTDOWN_POLL_INTERVAL_MILLISECONDS));
                 }
-                */
                 Err(e) => {
                     tracing::error!("couldn't get client: {e:?}")
                 }
             }
         }
 
+        // signal the connection handlers to finish
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        tracing::info!("API service on {} stopped", listen_on);
         Ok(())
     }
 
-    fn stop(&self) {}
+    /// Stops the service, `start` returns once the active connection handlers finish
+    /// A stopped service cannot be started again
+    fn stop(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
 
     fn keys(&self) -> Vec<String> {
         self.storage.keys()
     }
 
-    fn set_item(&self, _key: &str, _item: StorageItem) -> bool {
-        false
+    fn set_item(&self, key: &str, mut item: StorageItem) -> bool {
+        item.key = key.to_owned();
+        if let Err(err) = item.validate() {
+            tracing::error!("Rejected item `{}`: {}", key, err);
+            return false;
+        }
+        self.storage.insert(item).is_ok()
     }
 
-    fn get_item(&self, _key: &str) -> Option<StorageItem> {
-        None
+    fn get_item(&self, key: &str) -> Option<StorageItem> {
+        self.storage.get(key)
     }
 
     fn remove_item(&self, key: &str) -> bool {
-        self.storage.remove(key);
+        self.storage.remove(key)
+    }
+}
+
+/// Timeouts and limits of a connection, taken from the api configuration
+#[derive(Clone, Copy)]
+struct ConnectionSettings {
+    io_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    max_frame_bytes: u64,
+}
+
+impl ConnectionSettings {
+    fn new(config_api: &ApiConfig) -> Self {
+        ConnectionSettings {
+            io_timeout: config_api.socket_timeout(),
+            idle_timeout: config_api.connection_idle_timeout(),
+            max_frame_bytes: config_api.max_frame_bytes,
+        }
+    }
+}
+
+/// Active connection counts per client IP
+struct ConnectionLimiter {
+    max_per_ip: Option<usize>,
+    counts: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl ConnectionLimiter {
+    fn new(max_per_ip: Option<usize>) -> Self {
+        ConnectionLimiter {
+            max_per_ip,
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts a new connection of the IP, returns `false` if the IP is at its limit already
+    fn try_acquire(&self, ip: IpAddr) -> bool {
+        let max_per_ip = match self.max_per_ip {
+            Some(max_per_ip) => max_per_ip,
+            None => return true,
+        };
+        let mut counts = self.counts.lock().unwrap_or_else(|err| err.into_inner());
+        let count = counts.entry(ip).or_default();
+        if *count >= max_per_ip {
+            return false;
+        }
+        *count += 1;
         true
     }
+
+    /// Releases a connection of the IP counted by `try_acquire`
+    fn release(&self, ip: IpAddr) {
+        if self.max_per_ip.is_none() {
+            return;
+        }
+        let mut counts = self.counts.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(count) = counts.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&ip);
+            }
+        }
+    }
+}
+
+/// Sets the accepted stream to blocking mode and applies the socket options of the configuration
+fn prepare_stream(stream: &TcpStream, config_api: &ApiConfig) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    set_keepalive(stream, config_api.keepalive.as_ref())
 }
 
-fn handle_connection(mut stream: TcpStream, addr: SocketAddr, shutdown: Arc<AtomicBool>) {
+fn handle_connection(
+    mut stream: TcpStream,
+    addr: SocketAddr,
+    storage: Arc<Storage>,
+    shutdown: Arc<AtomicBool>,
+    metrics: &Metrics,
+    settings: ConnectionSettings,
+) {
     tracing::debug!("Client connected: {}", addr);
+    let ConnectionSettings {
+        io_timeout,
+        idle_timeout,
+        max_frame_bytes,
+    } = settings;
+
+    if let Err(err) = stream.set_write_timeout(io_timeout) {
+        tracing::error!("Could not set the write timeout for {}: {}", addr, err);
+        return;
+    }
 
-    let mut buf = [0; 1024];
-    let addr = stream.peer_addr().unwrap();
-    while !shutdown.load(Ordering::SeqCst) {
-        let count = stream.read(&mut buf).unwrap();
-        tracing::trace!("Received bytes count from {} : {}", addr, count);
+    let mut protocol_version = None;
+    loop {
+        match wait_for_request(&stream, &shutdown, idle_timeout, io_timeout) {
+            Ok(true) => {}
+            Ok(false) => {
+                tracing::debug!("Closing connection: {}", addr);
+                break;
+            }
+            Err(err) if err.kind() == ErrorKind::TimedOut => {
+                tracing::warn!("Closing idle connection {}: {}", addr, err);
+                break;
+            }
+            Err(err) => {
+                tracing::error!("Could not read request from {}: {}", addr, err);
+                break;
+            }
+        }
 
-        let mut vec = buf.to_vec();
-        vec.truncate(count);
-        let msg = String::from_utf8(vec).unwrap();
+        // the frame length is checked before reading, so an oversized prefix does not exhaust the memory
+        let data = match read_frame_data_limited(&mut stream, max_frame_bytes) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => {
+                tracing::debug!("Client disconnected: {}", addr);
+                break;
+            }
+            Err(err) => {
+                tracing::error!("Could not read request from {}: {}", addr, err);
+                break;
+            }
+        };
+
+        // the first message of a connection is the handshake agreeing the protocol version
+        if protocol_version.is_none() {
+            match handle_handshake(&mut stream, &data) {
+                Ok(Some(version)) => {
+                    tracing::debug!("Agreed protocol version {} with {}", version, addr);
+                    protocol_version = Some(version);
+                    continue;
+                }
+                Ok(None) => {
+                    tracing::warn!("Rejected the handshake of {}", addr);
+                    break;
+                }
+                Err(err) => {
+                    tracing::error!("Could not send handshake response to {}: {}", addr, err);
+                    break;
+                }
+            }
+        }
+
+        // the frame boundary is known, so a malformed request does not break the connection
+        let (command, response) = match decode_request(&data) {
+            Ok(request) => {
+                tracing::trace!("Received request from {} : {:?}", addr, request);
+                (request.name(), handle_request(&storage, request))
+            }
+            Err(err) => {
+                tracing::warn!("Malformed request from {}: {}", addr, err);
+                (
+                    "malformed",
+                    ApiResponse::Error(format!("Malformed request: {}", err)),
+                )
+            }
+        };
+
+        match write_frame(&mut stream, &response) {
+            Ok(bytes_sent) => metrics.record_request(command, response.status(), bytes_sent as u64),
+            Err(err) => {
+                tracing::error!("Could not send response to {}: {}", addr, err);
+                break;
+            }
+        }
+    }
+}
+
+/// Answers the handshake of the client
+/// Returns the agreed protocol version, or `None` if the handshake was rejected
+fn handle_handshake(
+    stream: &mut TcpStream,
+    data: &[u8],
+) -> std::io::Result<Option<ProtocolVersion>> {
+    let response = match decode_frame_data::<ApiHandshake>(data) {
+        Ok(handshake) => ApiHandshakeResponse::negotiate(handshake.version),
+        Err(err) => ApiHandshakeResponse::Rejected(format!("Malformed handshake: {}", err)),
+    };
+    write_frame(stream, &response)?;
+    match response {
+        ApiHandshakeResponse::Accepted(version) => Ok(Some(version)),
+        ApiHandshakeResponse::Rejected(_) => Ok(None),
+    }
+}
+
+/// Waits until the next request is available
+/// Returns `false` if the service is shutting down or the client disconnected
+/// Returns a `TimedOut` error if no request is available within the idle timeout
+/// The read timeout is set to the io timeout afterwards, so a partially sent request does not block the handler
+fn wait_for_request(
+    stream: &TcpStream,
+    shutdown: &AtomicBool,
+    idle_timeout: Option<Duration>,
+    io_timeout: Option<Duration>,
+) -> std::io::Result<bool> {
+    let poll_interval = Duration::from_millis(SHUTDOWN_POLL_INTERVAL_MILLISECONDS);
+    stream.set_read_timeout(Some(poll_interval))?;
+
+    let started_on = Instant::now();
+    let mut buf = [0_u8; 1];
+    let available = loop {
+        if shutdown.load(Ordering::SeqCst) {
+            break false;
+        }
+        if let Some(idle_timeout) = idle_timeout {
+            if started_on.elapsed() >= idle_timeout {
+                return Err(std::io::Error::new(
+                    ErrorKind::TimedOut,
+                    format!("no request within {} ms", idle_timeout.as_millis()),
+                ));
+            }
+        }
+        match stream.peek(&mut buf) {
+            Ok(0) => break false,
+            Ok(_) => break true,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(err) => return Err(err),
+        }
+    };
+
+    stream.set_read_timeout(io_timeout)?;
+    Ok(available)
+}
+
+fn handle_request(storage: &Storage, request: ApiRequest) -> ApiResponse {
+    if request.is_mutating() && storage.is_read_only() {
+        return ApiResponse::Error(format!(
+            "Could not {}, the storage is opened read-only!",
+            request.name()
+        ));
+    }
+
+    match request {
+        ApiRequest::Keys => ApiResponse::Ok(ApiPayload::Keys(storage.keys())),
+        ApiRequest::Get(key) => match storage.get(&key) {
+            Some(item) => ApiResponse::Ok(ApiPayload::Item(item)),
+            None => ApiResponse::NotFound,
+        },
+        ApiRequest::Insert(item) => {
+            // items decoded from the wire are checked before reaching the storage
+            if let Err(err) = item.validate() {
+                return ApiResponse::Error(err.to_string());
+            }
+            match storage.insert(item) {
+                Ok(()) => ApiResponse::Ok(ApiPayload::Done),
+                Err(err) => ApiResponse::Error(err),
+            }
+        }
+        ApiRequest::Remove(key) => ApiResponse::Ok(ApiPayload::Removed(storage.remove(&key))),
+        ApiRequest::Clear => {
+            storage.clear();
+            ApiResponse::Ok(ApiPayload::Done)
+        }
+        ApiRequest::Flush => match storage.flush() {
+            Ok(_) => ApiResponse::Ok(ApiPayload::Done),
+            Err(err) => ApiResponse::Error(err.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anor_storage::{BasicType, ItemType, MemBackend};
+    use anor_utils::config::KeepaliveConfig;
+    use std::net::TcpListener;
+
+    #[test]
+    fn handle_request_test() {
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+
+        let response = handle_request(&storage, ApiRequest::Get("missing".into()));
+        assert!(matches!(response, ApiResponse::NotFound));
+
+        let item = StorageItem::new("my_key", &String::from("abc")).unwrap();
+        let response = handle_request(&storage, ApiRequest::Insert(item));
+        assert!(matches!(response, ApiResponse::Ok(ApiPayload::Done)));
+
+        let response = handle_request(&storage, ApiRequest::Get("my_key".into()));
+        assert!(
+            matches!(response, ApiResponse::Ok(ApiPayload::Item(item)) if item.key == "my_key")
+        );
+
+        // invalid items are rejected
+        let item = StorageItem::new("", &String::from("abc")).unwrap();
+        let response = handle_request(&storage, ApiRequest::Insert(item));
+        assert!(matches!(response, ApiResponse::Error(err) if err.contains("key is empty")));
+        let item = StorageItem::with_type("flag", ItemType::Basic(BasicType::Bool), &42_u32);
+        let response = handle_request(&storage, ApiRequest::Insert(item.unwrap()));
+        assert!(matches!(response, ApiResponse::Error(_)));
+        assert!(storage.get("flag").is_none());
+    }
+
+    #[test]
+    fn keepalive_test() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        let keepalive = KeepaliveConfig {
+            idle: Duration::from_secs(42),
+            interval: Duration::from_secs(7),
+        };
+        let config_api = ApiConfig {
+            keepalive: Some(keepalive),
+            ..Default::default()
+        };
+        prepare_stream(&stream, &config_api).unwrap();
+
+        let socket = socket2::SockRef::from(&stream);
+        assert!(socket.keepalive().unwrap());
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(socket.tcp_keepalive_time().unwrap(), keepalive.idle);
+            assert_eq!(socket.tcp_keepalive_interval().unwrap(), keepalive.interval);
+        }
 
-        tracing::trace!("Received message from {} : {}", addr, msg);
+        let config_api = ApiConfig {
+            keepalive: None,
+            ..Default::default()
+        };
+        prepare_stream(&stream, &config_api).unwrap();
+        assert!(!socket.keepalive().unwrap());
     }
 }
@@ -0,0 +1,55 @@
+mod common;
+
+#[test]
+fn api_bind_retry() {
+    use anor_api::{ApiService, Service, SocketClient};
+    use anor_storage::Storage;
+    use anor_utils::config::{ApiConfig, Config, NetworkAddress};
+    use anor_utils::ShutdownToken;
+    use common::{api_config, TestService};
+    use std::net::TcpListener;
+    use std::sync::{mpsc::channel, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // the port is held by another listener
+    let holder = TcpListener::bind("127.0.0.1:0").unwrap();
+    let listen_on: NetworkAddress = holder.local_addr().unwrap().to_string().parse().unwrap();
+
+    // without retries the service fails to start
+    let config = Arc::new(Config {
+        api: Some(ApiConfig {
+            listen_on: vec![listen_on.clone()],
+            ..api_config()
+        }),
+        ..Default::default()
+    });
+    let (ready_sender, _ready_receiver) = channel();
+    let service = Service::with_config(Arc::new(Storage::open_in_memory()), config);
+    let err = service
+        .start(Arc::new(ShutdownToken::new()), ready_sender)
+        .unwrap_err();
+    assert!(err.contains("after 0 retries"));
+    drop(service);
+
+    // with retries the service binds once the port is released
+    let started_on = Instant::now();
+    let release = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        drop(holder);
+    });
+    let service = TestService::start(ApiConfig {
+        listen_on: vec![listen_on],
+        bind_retries: 5,
+        ..api_config()
+    });
+    release.join().unwrap();
+    assert!(started_on.elapsed() >= Duration::from_millis(200));
+
+    let mut client = service.client();
+    client.connect().unwrap();
+    client.keys().unwrap();
+    client.disconnect().unwrap();
+
+    service.stop();
+}
@@ -0,0 +1,50 @@
+mod common;
+
+#[test]
+fn api_conn_limit() {
+    use anor_utils::config::ApiConfig;
+    use common::{api_config, TestService};
+    use std::io::{ErrorKind, Read};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    let max_conns_per_ip = 2;
+    let service = TestService::start(ApiConfig {
+        max_conns_per_ip: Some(max_conns_per_ip),
+        ..api_config()
+    });
+
+    // an open connection times out reading, a refused connection is closed by the service
+    let is_open = |stream: &mut TcpStream| {
+        stream
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let mut buf = [0_u8; 1];
+        match stream.read(&mut buf) {
+            Ok(0) => false,
+            Ok(_) => panic!("unexpected data"),
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => true,
+            Err(_) => false,
+        }
+    };
+
+    let mut streams: Vec<TcpStream> = (0..max_conns_per_ip).map(|_| service.connect()).collect();
+    let mut refused = service.connect();
+    assert!(!is_open(&mut refused));
+    for stream in streams.iter_mut() {
+        assert!(is_open(stream));
+    }
+
+    // closing a connection frees a slot once the service notices the disconnect
+    drop(streams.pop());
+    let mut accepted = false;
+    for _ in 0..20 {
+        if is_open(&mut service.connect()) {
+            accepted = true;
+            break;
+        }
+    }
+    assert!(accepted);
+
+    service.stop();
+}
@@ -0,0 +1,50 @@
+mod common;
+
+#[test]
+fn api_errors() {
+    use anor_api::{
+        handshake, read_frame, write_frame, ApiPayload, ApiRequest, ApiResponse, SocketClient,
+        PROTOCOL_VERSION,
+    };
+    use common::{api_config, TestService};
+    use std::io::{Read, Write};
+
+    let service = TestService::start(api_config());
+
+    // a missing item is reported as `NotFound` and mapped to `None` by the client
+    let mut client = service.client();
+    client.connect().unwrap();
+    assert!(client.get("missing").unwrap().is_none());
+    client.disconnect().unwrap();
+
+    let mut stream = service.connect();
+    handshake(&mut stream, PROTOCOL_VERSION).unwrap();
+    write_frame(&mut stream, &ApiRequest::Get("missing".into())).unwrap();
+    let response: ApiResponse = read_frame(&mut stream).unwrap();
+    assert!(matches!(response, ApiResponse::NotFound));
+
+    // a malformed request is answered with an `Error`, the connection stays usable
+    stream.write_all(&3_u64.to_be_bytes()).unwrap();
+    stream.write_all(&[0xff, 0xff, 0xff]).unwrap();
+    let response: ApiResponse = read_frame(&mut stream).unwrap();
+    assert!(matches!(response, ApiResponse::Error(err) if err.contains("Malformed request")));
+
+    write_frame(&mut stream, &ApiRequest::Keys).unwrap();
+    let response: ApiResponse = read_frame(&mut stream).unwrap();
+    assert!(matches!(response, ApiResponse::Ok(ApiPayload::Keys(_))));
+    drop(stream);
+
+    // an oversized length prefix closes the connection before the handshake, nothing is allocated for it
+    let mut stream = service.connect();
+    stream.write_all(&u64::MAX.to_be_bytes()).unwrap();
+    let mut buf = vec![];
+    assert!(matches!(stream.read_to_end(&mut buf), Ok(0) | Err(_)));
+    drop(stream);
+
+    // the service keeps serving
+    client.connect().unwrap();
+    assert!(client.get("missing").unwrap().is_none());
+    client.disconnect().unwrap();
+
+    service.stop();
+}
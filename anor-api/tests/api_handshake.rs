@@ -0,0 +1,39 @@
+mod common;
+
+#[test]
+fn api_handshake() {
+    use anor_api::{handshake, SocketClient, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+    use common::{api_config, TestService};
+    use std::io::{ErrorKind, Read};
+    use std::time::Duration;
+
+    let service = TestService::start(api_config());
+
+    // a client with a supported version agrees on it
+    let mut client = service.client();
+    client.connect().unwrap();
+    assert_eq!(client.protocol_version(), Some(PROTOCOL_VERSION));
+    client.keys().unwrap();
+    client.disconnect().unwrap();
+    assert_eq!(client.protocol_version(), None);
+
+    // a newer client falls back to the version of the service
+    let mut stream = service.connect();
+    assert_eq!(
+        handshake(&mut stream, PROTOCOL_VERSION + 1).unwrap(),
+        PROTOCOL_VERSION
+    );
+    drop(stream);
+
+    // a client with an unsupported version is rejected and disconnected
+    let mut stream = service.connect();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let err = handshake(&mut stream, MIN_PROTOCOL_VERSION - 1).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+    let mut buf = [0_u8; 1];
+    assert_eq!(stream.read(&mut buf).unwrap(), 0);
+
+    service.stop();
+}
@@ -0,0 +1,39 @@
+mod common;
+
+#[test]
+fn api_idle() {
+    use anor_api::SocketClient;
+    use anor_utils::config::ApiConfig;
+    use common::{api_config, TestService};
+    use std::io::Read;
+    use std::net::TcpStream;
+    use std::time::{Duration, Instant};
+
+    let idle_timeout = Duration::from_millis(200);
+    let service = TestService::start(ApiConfig {
+        idle_timeout: Some(idle_timeout),
+        ..api_config()
+    });
+
+    // idle connections occupying all the workers are closed after the idle timeout
+    let started_on = Instant::now();
+    let idle_streams: Vec<TcpStream> = (0..2).map(|_| service.connect()).collect();
+    for mut stream in idle_streams {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let mut buf = [0_u8; 1];
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+    }
+    let elapsed = started_on.elapsed();
+    assert!(elapsed >= idle_timeout / 2);
+    assert!(elapsed < Duration::from_secs(5));
+
+    // the freed workers serve new connections
+    let mut client = service.client();
+    client.connect().unwrap();
+    client.keys().unwrap();
+    client.disconnect().unwrap();
+
+    service.stop();
+}
@@ -0,0 +1,62 @@
+mod common;
+
+#[test]
+fn api_read_only() {
+    use anor_api::SocketClient;
+    use anor_storage::{Storage, StorageItem};
+    use anor_utils::config::{Config, StorageConfig};
+    use common::{api_config, TestService};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    let data_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("tmp")
+        .join("api_read_only");
+    _ = std::fs::remove_dir_all(&data_path);
+
+    let config = |read_only: bool| {
+        Arc::new(Config {
+            storage: Some(StorageConfig {
+                data_path: data_path.clone(),
+                read_only,
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    };
+
+    // persist an item to be served read-only
+    let storage = Storage::open_with_config(config(false));
+    storage
+        .insert(StorageItem::new("my_key", &String::from("abc")).unwrap())
+        .unwrap();
+    drop(storage);
+
+    // start the api service
+    let storage = Arc::new(Storage::open_with_config(config(true)));
+    assert!(storage.is_read_only());
+    let service = TestService::start_with_storage(api_config(), storage);
+
+    let mut client = service.client();
+    client.connect().unwrap();
+
+    // reads are served
+    assert_eq!(client.keys().unwrap(), vec!["my_key".to_string()]);
+    assert_eq!(
+        client.get_object::<String>("my_key").unwrap(),
+        Some("abc".to_string())
+    );
+
+    // mutations are rejected
+    let item = StorageItem::new("other_key", &String::from("def")).unwrap();
+    let err = client.insert(item).unwrap_err();
+    assert!(err.to_string().contains("read-only"));
+    assert!(client.remove("my_key").is_err());
+    assert!(client.clear().is_err());
+    assert!(client.flush().is_err());
+    assert_eq!(client.keys().unwrap(), vec!["my_key".to_string()]);
+    client.disconnect().unwrap();
+
+    service.stop();
+}
@@ -0,0 +1,47 @@
+mod common;
+
+#[test]
+fn api_timeout() {
+    use anor_api::{SocketClient, StorageApiClient};
+    use anor_utils::config::{ApiConfig, Config};
+    use common::{api_config, remote_config, TestService};
+    use std::io::{ErrorKind, Read};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    let io_timeout = Duration::from_millis(200);
+    let service = TestService::start(ApiConfig {
+        io_timeout,
+        ..api_config()
+    });
+
+    // the handler closes a connection without requests after the timeout
+    let mut stream = service.connect();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let started_on = Instant::now();
+    let mut buf = [0_u8; 1];
+    assert_eq!(stream.read(&mut buf).unwrap(), 0);
+    let elapsed = started_on.elapsed();
+    assert!(elapsed >= io_timeout / 2);
+    assert!(elapsed < Duration::from_secs(5));
+
+    service.stop();
+
+    // the client gives up on a node accepting the connection but never responding
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client_config = Arc::new(Config {
+        api: Some(ApiConfig {
+            io_timeout,
+            ..Default::default()
+        }),
+        remote: Some(remote_config(listener.local_addr().unwrap())),
+        ..Default::default()
+    });
+    let mut client = StorageApiClient::with_config(client_config);
+    let err = client.connect().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    let (_silent_stream, _) = listener.accept().unwrap();
+}
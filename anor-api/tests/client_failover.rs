@@ -0,0 +1,60 @@
+#[test]
+fn client_failover() {
+    use anor_api::{
+        read_frame, write_frame, ApiHandshake, ApiHandshakeResponse, SocketClient, StorageApiClient,
+    };
+    use anor_utils::config::{Config, RemoteConfig, RemoteNode};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+
+    let low_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let high_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let low_port = low_listener.local_addr().unwrap().port();
+    let high_port = high_listener.local_addr().unwrap().port();
+
+    let remote_config = |nodes: &str| {
+        let nodes = nodes
+            .split(',')
+            .map(|node| node.parse::<RemoteNode>().unwrap())
+            .collect();
+        Arc::new(Config {
+            storage: None,
+            api: None,
+            http: None,
+            remote: Some(RemoteConfig { nodes }),
+        })
+    };
+
+    // answers the handshake of one connection, the listener is closed afterwards
+    let serve_handshake = |listener: TcpListener| {
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let handshake: ApiHandshake = read_frame(&mut stream).unwrap();
+            let response = ApiHandshakeResponse::negotiate(handshake.version);
+            write_frame(&mut stream, &response).unwrap();
+        })
+    };
+
+    // the higher priority node is preferred regardless of the configured order
+    let config = remote_config(&format!(
+        "127.0.0.1:{},127.0.0.1:{}#10",
+        low_port, high_port
+    ));
+    let high_handle = serve_handshake(high_listener);
+    let mut client = StorageApiClient::with_config(config.clone());
+    client.connect().unwrap();
+    high_handle.join().unwrap();
+    low_listener.set_nonblocking(true).unwrap();
+    assert!(low_listener.accept().is_err());
+    low_listener.set_nonblocking(false).unwrap();
+    client.disconnect().unwrap();
+
+    // falls back to the lower priority node if the higher one is not available
+    let low_handle = serve_handshake(low_listener);
+    let mut client = StorageApiClient::with_config(config);
+    client.connect().unwrap();
+    low_handle.join().unwrap();
+    client.disconnect().unwrap();
+}
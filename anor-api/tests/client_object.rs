@@ -0,0 +1,45 @@
+mod common;
+
+#[test]
+fn client_object() {
+    use anor_api::SocketClient;
+    use anor_storage::storage::storage_item::*;
+    use common::{api_config, TestService};
+    use std::collections::HashMap;
+
+    let service = TestService::start(api_config());
+
+    let key = "my_map";
+    let mut sample_map = HashMap::<u8, String>::new();
+    sample_map.insert(1, "One".into());
+    sample_map.insert(2, "Two".into());
+
+    let mut client = service.client();
+    client.connect().unwrap();
+    client.clear().unwrap();
+
+    // set the map through the client
+    let item_type = ItemType::Complex(ComplexType::Map(BasicType::U8, BasicType::String));
+    client
+        .set_object(key, item_type.clone(), &sample_map)
+        .unwrap();
+
+    // read the map back typed
+    let map: HashMap<u8, String> = client.get_object(key).unwrap().unwrap();
+    assert_eq!(map, sample_map);
+
+    // update the map through the client
+    sample_map.insert(3, "Three".into());
+    client.set_object(key, item_type, &sample_map).unwrap();
+    let map: HashMap<u8, String> = client.get_object(key).unwrap().unwrap();
+    assert_eq!(map, sample_map);
+    assert_eq!(client.keys().unwrap(), vec![key.to_string()]);
+
+    // missing keys are reported as `None`
+    assert!(client.get_object::<String>("missing").unwrap().is_none());
+
+    client.clear().unwrap();
+    client.disconnect().unwrap();
+
+    service.stop();
+}
@@ -0,0 +1,100 @@
+//! Harness shared by the api service tests
+#![allow(dead_code)]
+
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{mpsc::channel, Arc};
+use std::thread::{self, JoinHandle};
+
+use anor_api::{ApiService, Service, SocketClient, StorageApiClient};
+use anor_storage::Storage;
+use anor_utils::config::{ApiConfig, Config, NetworkAddress, RemoteConfig};
+use anor_utils::ShutdownToken;
+
+/// Returns the configuration of a service listening on a free port of the loopback interface
+pub fn api_config() -> ApiConfig {
+    ApiConfig {
+        listen_on: vec!["127.0.0.1:0".parse().unwrap()],
+        enabled: true,
+        ..Default::default()
+    }
+}
+
+/// Returns the remote configuration of a client connecting to the address
+pub fn remote_config(addr: SocketAddr) -> RemoteConfig {
+    let address: NetworkAddress = addr.to_string().parse().unwrap();
+    RemoteConfig {
+        nodes: vec![address.into()],
+    }
+}
+
+/// Returns the configuration of a client connecting to the address
+pub fn client_config(addr: SocketAddr) -> Arc<Config> {
+    Arc::new(Config {
+        remote: Some(remote_config(addr)),
+        ..Default::default()
+    })
+}
+
+/// Running api service, shut down when dropped
+pub struct TestService {
+    pub addr: SocketAddr,
+    pub service: Arc<Service>,
+    shutdown: Arc<ShutdownToken>,
+    handle: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl TestService {
+    /// Starts the service on an empty in-memory storage
+    pub fn start(api: ApiConfig) -> Self {
+        Self::start_with_storage(api, Arc::new(Storage::open_in_memory()))
+    }
+
+    /// Starts the service on the storage, returns once the service accepts connections
+    pub fn start_with_storage(api: ApiConfig, storage: Arc<Storage>) -> Self {
+        let config = Arc::new(Config {
+            api: Some(api),
+            ..Default::default()
+        });
+        let service = Arc::new(Service::with_config(storage, config));
+        let shutdown = Arc::new(ShutdownToken::new());
+        let (ready_sender, ready_receiver) = channel();
+        let service_clone = service.clone();
+        let service_shutdown = shutdown.clone();
+        let handle = thread::spawn(move || service_clone.start(service_shutdown, ready_sender));
+        ready_receiver.recv().unwrap();
+
+        TestService {
+            addr: service.local_addr().unwrap(),
+            service,
+            shutdown,
+            handle: Some(handle),
+        }
+    }
+
+    /// Returns a client of the service, not connected yet
+    pub fn client(&self) -> StorageApiClient {
+        StorageApiClient::with_config(client_config(self.addr))
+    }
+
+    /// Opens a raw connection to the service
+    pub fn connect(&self) -> TcpStream {
+        TcpStream::connect(self.addr).unwrap()
+    }
+
+    /// Shuts the service down and waits until it stops accepting connections
+    pub fn stop(mut self) {
+        self.shutdown.trigger();
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap().unwrap();
+        }
+    }
+}
+
+impl Drop for TestService {
+    fn drop(&mut self) {
+        self.shutdown.trigger();
+        if let Some(handle) = self.handle.take() {
+            _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,43 @@
+mod common;
+
+#[test]
+fn service_stop() {
+    use anor_api::{ApiService, Service, SocketClient, StorageApiClient};
+    use anor_storage::Storage;
+    use anor_utils::config::Config;
+    use anor_utils::ShutdownToken;
+    use common::{api_config, client_config};
+    use std::sync::{mpsc::channel, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    let config = Arc::new(Config {
+        api: Some(api_config()),
+        ..Default::default()
+    });
+    let storage = Arc::new(Storage::open_in_memory());
+    let service = Arc::new(Service::with_config(storage, config));
+
+    // start the api service
+    let (ready_sender, ready_receiver) = channel();
+    let (stopped_sender, stopped_receiver) = channel();
+    let service_clone = service.clone();
+    thread::spawn(move || {
+        let result = service_clone.start(Arc::new(ShutdownToken::new()), ready_sender);
+        stopped_sender.send(result).unwrap();
+    });
+    ready_receiver.recv().unwrap();
+
+    // keep an idle client connected
+    let mut client = StorageApiClient::with_config(client_config(service.local_addr().unwrap()));
+    client.connect().unwrap();
+    assert!(client.keys().is_ok());
+
+    // stop the service from another thread
+    let service_clone = service.clone();
+    thread::spawn(move || service_clone.stop()).join().unwrap();
+
+    // `start` returns promptly
+    let result = stopped_receiver.recv_timeout(Duration::from_secs(2));
+    assert_eq!(result, Ok(Ok(())));
+}
@@ -1,6 +1,6 @@
 use bytes::Bytes;
 use http_body_util::{BodyExt, Empty};
-use http_common::http_range::{CompleteLength, HttpRange};
+use http_common::http_range::RANGE_UNIT;
 use hyper::Request;
 use hyper_util::rt::TokioIo;
 use std::ops::Range;
@@ -75,14 +75,9 @@ pub async fn request_url(
         .body(Empty::<Bytes>::new())?;
 
     if let Some(range_v) = range {
-        let http_range = HttpRange {
-            ranges: vec![range_v],
-            complete_length: Some(CompleteLength::Unknown),
-        };
-        req.headers_mut().append(
-            hyper::header::CONTENT_RANGE,
-            http_range.to_header().parse().unwrap(),
-        );
+        let range_header = format!("{}={}-{}", RANGE_UNIT, range_v.start, range_v.end);
+        req.headers_mut()
+            .append(hyper::header::RANGE, range_header.parse().unwrap());
     }
 
     tracing::trace!("Request:\n{:#?}", req);
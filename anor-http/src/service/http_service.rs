@@ -1,55 +1,185 @@
+use std::convert::Infallible;
+use std::future::Future;
 use std::io::SeekFrom;
 use std::net::SocketAddr;
-use std::path::Path;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Component, Path, PathBuf};
 use std::sync::mpsc::Sender;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncSeekExt;
 use tokio::net::TcpListener;
 use tokio::runtime::Runtime;
+use tokio::sync::broadcast::error::RecvError;
 
 use bytes::Bytes;
-use http_body_util::Full;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited, StreamBody};
+use httpdate::HttpDate;
+use hyper::body::{Body, Frame};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper::{Method, Request, Response, Result, StatusCode};
 use hyper_util::rt::TokioIo;
 
+use anor_storage::storage::storage_item::{ComplexType, ItemType};
 use anor_storage::storage::Storage;
-use anor_utils::config::Config;
+use anor_storage::{ChangeEvent, ChangeKind};
+use anor_utils::config::{Config, CorsConfig, NetworkAddress};
+use anor_utils::net::bind_with_retries;
+use anor_utils::{Metrics, ShutdownToken};
 use http_common::http_range::{self, HttpRange};
 
 // A simple type alias so as to DRY.
 type ServiceResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
+/// Body of the responses, complete for the files and items but streamed for the change events
+type ResponseBody = BoxBody<Bytes, Infallible>;
+
+/// request path of the Server-Sent Events stream of the storage changes
+const EVENTS_PATH: &str = "/_events";
+
+/// request path prefix to access the storage items
+const STORAGE_PATH_PREFIX: &str = "/storage/";
+
+/// query parameter requesting a download with the given filename
+const DOWNLOAD_QUERY_PARAM: &str = "download";
+
+/// item metafield with the filename to download the item with
+const FILENAME_METAFIELD: &str = "filename";
+
+/// item metafield protecting the item from being overwritten or removed over http, if `true`
+const READ_ONLY_METAFIELD: &str = "read_only";
+
+/// scheme of the `Authorization` header carrying the access token
+const BEARER_AUTH_SCHEME: &str = "Bearer";
+
+/// methods supported by the service, listed in the `Allow` header of a 405 or `OPTIONS` response
+const ALLOWED_METHODS: [Method; 5] = [
+    Method::GET,
+    Method::HEAD,
+    Method::PUT,
+    Method::DELETE,
+    Method::OPTIONS,
+];
+
+/// validators of the file content, an `If-Range` request is answered partially only if one matches
+struct FileValidators {
+    etag: String,
+    last_modified: HttpDate,
+}
+
+impl FileValidators {
+    fn new(content_length: u64, modified: SystemTime) -> Self {
+        let mtime = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        FileValidators {
+            etag: format!("\"{:x}-{:x}\"", content_length, mtime.as_nanos()),
+            last_modified: modified.into(),
+        }
+    }
+
+    /// Returns whether the `If-Range` header value matches the entity tag or the modification date
+    /// A weak entity tag never matches
+    fn if_range_matches(&self, if_range: &str) -> bool {
+        let if_range = if_range.trim();
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            if_range == self.etag
+        } else {
+            if_range
+                .parse::<HttpDate>()
+                .is_ok_and(|date| date == self.last_modified)
+        }
+    }
+
+    /// Returns whether the `If-None-Match` header value lists the entity tag, `*` matches any
+    /// Entity tags are compared weakly, ignoring a `W/` prefix
+    fn if_none_match_matches(&self, if_none_match: &str) -> bool {
+        let etag = self.etag.trim_start_matches("W/");
+        if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+    }
+
+    /// Adds the `ETag` and `Last-Modified` headers to the response
+    fn add_headers(&self, builder: http::response::Builder) -> http::response::Builder {
+        builder
+            .header(hyper::header::ETAG, &self.etag)
+            .header(hyper::header::LAST_MODIFIED, self.last_modified.to_string())
+    }
+}
+
+/// state shared by the connection handlers
+struct ServiceContext {
+    storage: Arc<Storage>,
+    metrics: Arc<Metrics>,
+    max_body_bytes: u64,
+    access_log: bool,
+    request_timeout: Option<Duration>,
+    cors: Option<CorsConfig>,
+    access_token: Option<String>,
+    shutdown: Arc<ShutdownToken>,
+}
+
 pub struct Service {
     storage: Arc<Storage>,
     config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    local_addr: Arc<OnceLock<SocketAddr>>,
 }
 
 impl Service {
     pub fn with_config(storage: Arc<Storage>, config: Arc<Config>) -> Self {
         assert!(config.http.is_some());
         assert!(!config.http.as_ref().unwrap().listen_on.is_empty());
-        Service { storage, config }
+        Service {
+            storage,
+            config,
+            metrics: Arc::new(Metrics::new()),
+            local_addr: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Returns the bound address of the service, once the ready signal is sent
+    /// Resolves the actual port if the service listens on port 0
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr.get().copied()
+    }
+
+    /// Returns the request metrics of the service
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
     }
 
     pub fn start(
         &self,
         http_service_ready_sender: Sender<()>,
-        server_shutdown: Arc<AtomicBool>,
+        server_shutdown: Arc<ShutdownToken>,
     ) -> JoinHandle<()> {
-        let listen_on = self.config.http.as_ref().unwrap().listen_on[0];
-        let storage = self.storage.clone();
+        let config_http = self.config.http.as_ref().unwrap();
+        let listen_on = config_http.listen_on[0].clone();
+        let bind_retries = config_http.bind_retries;
+        let context = Arc::new(ServiceContext {
+            storage: self.storage.clone(),
+            metrics: self.metrics.clone(),
+            max_body_bytes: config_http.max_body_bytes,
+            access_log: config_http.access_log,
+            request_timeout: config_http.request_timeout,
+            cors: config_http.cors.clone(),
+            access_token: config_http.access_token.clone(),
+            shutdown: server_shutdown.clone(),
+        });
+        let local_addr = self.local_addr.clone();
         tracing::info!("Starting HTTP service...");
         std::thread::spawn(move || {
             let async_runtime = Runtime::new().unwrap();
             async_runtime.block_on(async {
                 if let Err(err) = start(
-                    storage,
+                    context,
                     listen_on,
+                    bind_retries,
+                    local_addr,
                     http_service_ready_sender,
                     server_shutdown,
                 )
@@ -63,12 +193,21 @@ impl Service {
 }
 
 async fn start(
-    _storage: Arc<Storage>,
-    listen_on: SocketAddr,
+    context: Arc<ServiceContext>,
+    listen_on: NetworkAddress,
+    bind_retries: u32,
+    local_addr: Arc<OnceLock<SocketAddr>>,
     http_service_ready_sender: Sender<()>,
-    http_service_shutdown: Arc<AtomicBool>,
+    http_service_shutdown: Arc<ShutdownToken>,
 ) -> std::result::Result<(), Box<dyn std::error::Error>> {
-    let listener = TcpListener::bind(listen_on).await?;
+    // the retries back off by sleeping, so the bind is kept off the async workers
+    let bind_address = listen_on.clone();
+    let listener =
+        tokio::task::spawn_blocking(move || bind_with_retries(&bind_address, bind_retries))
+            .await??;
+    listener.set_nonblocking(true)?;
+    _ = local_addr.set(listener.local_addr()?);
+    let listener = TcpListener::from_std(listener)?;
 
     // send the ready signal
     if let Err(err) = http_service_ready_sender.send(()) {
@@ -78,16 +217,23 @@ async fn start(
     tracing::info!("HTTP service running on http://{}", listen_on);
 
     let mut tasks: Vec<tokio::task::JoinHandle<()>> = vec![];
-    while !http_service_shutdown.load(Ordering::SeqCst) {
-        let (stream, _) = listener.accept().await?;
+    while !http_service_shutdown.is_triggered() {
+        // the shutdown signal wakes the pending accept
+        let stream = tokio::select! {
+            accepted = listener.accept() => accepted?.0,
+            _ = http_service_shutdown.triggered() => break,
+        };
+        let context = context.clone();
         let task = tokio::task::spawn(async move {
             let io = TokioIo::new(stream);
+            context.metrics.connection_opened();
             if let Err(err) = http1::Builder::new()
-                .serve_connection(io, service_fn(file_service))
+                .serve_connection(io, service_fn(|req| serve(req, context.clone())))
                 .await
             {
                 tracing::error!("Failed to serve connection: {:?}", err);
             }
+            context.metrics.connection_closed();
         });
 
         // clean-up, remove finished tasks
@@ -114,14 +260,452 @@ async fn start(
     Ok(())
 }
 
-async fn file_service(req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>> {
+/// Streams the change events, other requests are answered by the file service
+/// Every response allows the configured origin, if cross-origin access is configured
+async fn serve(
+    req: Request<hyper::body::Incoming>,
+    context: Arc<ServiceContext>,
+) -> Result<Response<ResponseBody>> {
+    let mut response = if req.method() == Method::GET && req.uri().path() == EVENTS_PATH {
+        // the stream is long-lived, so it is not bound by the request timeout
+        context
+            .metrics
+            .record_request(req.method().as_str(), "200", 0);
+        events_stream(&context)
+    } else {
+        file_service(req, context.clone())
+            .await?
+            .map(BodyExt::boxed)
+    };
+    if let Some(cors) = &context.cors {
+        if let Ok(value) = hyper::header::HeaderValue::from_str(&cors.allow_origin) {
+            response
+                .headers_mut()
+                .insert(hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN, value);
+        }
+    }
+    Ok(response)
+}
+
+/// Streams the changes of the storage as Server-Sent Events until the client disconnects or the service shuts down
+/// A subscriber falling behind gets a `resync` event with the number of missed changes instead of the changes
+fn events_stream(context: &ServiceContext) -> Response<ResponseBody> {
+    let receiver = context.storage.subscribe();
+    let shutdown = context.shutdown.clone();
+    let events = futures_util::stream::unfold(
+        (receiver, shutdown),
+        |(mut receiver, shutdown)| async move {
+            let event = tokio::select! {
+                event = receiver.recv() => event,
+                _ = shutdown.triggered() => return None,
+            };
+            let frame = sse_frame(event)?;
+            Some((Ok(Frame::data(frame)), (receiver, shutdown)))
+        },
+    );
+
+    match Response::builder()
+        .status(StatusCode::OK)
+        .header(hyper::header::CONTENT_TYPE, "text/event-stream")
+        .header(hyper::header::CACHE_CONTROL, "no-cache")
+        // the stream ends only on shutdown, the connection is not reused then
+        .header(hyper::header::CONNECTION, "close")
+        .body(StreamBody::new(events).boxed())
+    {
+        Ok(response) => response,
+        Err(err) => {
+            tracing::error!("unable to build response: {}", err);
+            send_error_500().map(BodyExt::boxed)
+        }
+    }
+}
+
+/// Encodes the change, or the number of changes missed by a lagging subscriber, as a Server-Sent Event
+/// Returns `None` once the subscription is closed
+fn sse_frame(event: std::result::Result<ChangeEvent, RecvError>) -> Option<Bytes> {
+    let (name, data) = match event {
+        Ok(ChangeEvent {
+            key,
+            kind: ChangeKind::Set,
+        }) => ("set", key),
+        Ok(ChangeEvent {
+            key,
+            kind: ChangeKind::Removed,
+        }) => ("removed", key),
+        Err(RecvError::Lagged(missed)) => ("resync", missed.to_string()),
+        Err(RecvError::Closed) => return None,
+    };
+    let mut frame = format!("event: {}\n", name);
+    // a line break in the data is sent as a continued data field
+    for line in data.replace("\r\n", "\n").split(['\r', '\n']) {
+        frame.push_str(&format!("data: {}\n", line));
+    }
+    frame.push('\n');
+    Some(Bytes::from(frame))
+}
+
+async fn file_service(
+    req: Request<hyper::body::Incoming>,
+    context: Arc<ServiceContext>,
+) -> Result<Response<Full<Bytes>>> {
     tracing::trace!("recevied request:{:#?}", req);
 
-    match *req.method() {
-        Method::HEAD => file_info(&req).await,
-        Method::GET => file_send(&req).await,
-        _ => Ok(send_error_404()),
+    let started_on = Instant::now();
+    let method = req.method().clone();
+    let path = if context.access_log {
+        req.uri().path().to_string()
+    } else {
+        String::new()
+    };
+    let handler = async {
+        match method {
+            Method::HEAD if req.uri().path().starts_with(STORAGE_PATH_PREFIX) => {
+                storage_get(&req, &context).map(head_response)
+            }
+            Method::HEAD => file_info(&req).await,
+            Method::GET if req.uri().path().starts_with(STORAGE_PATH_PREFIX) => {
+                storage_get(&req, &context)
+            }
+            Method::GET => file_send(&req).await.map(|mut response| {
+                if let Some(filename) = download_filename(&req) {
+                    set_content_disposition(&mut response, &filename);
+                }
+                response
+            }),
+            // only items are written or removed, other paths are not found for PUT and DELETE alike
+            Method::PUT => storage_put(req, &context).await,
+            Method::DELETE => storage_delete(&req, &context),
+            Method::OPTIONS => Ok(options_response(context.cors.as_ref())),
+            _ => Ok(send_error_405()),
+        }
+    };
+    let response = with_request_timeout(context.request_timeout, handler).await;
+
+    if let Ok(response) = &response {
+        let bytes_sent = response.body().size_hint().exact().unwrap_or_default();
+        context
+            .metrics
+            .record_request(method.as_str(), response.status().as_str(), bytes_sent);
+        if context.access_log {
+            tracing::info!(
+                method = method.as_str(),
+                path,
+                status = response.status().as_u16(),
+                bytes = bytes_sent,
+                duration_us = started_on.elapsed().as_micros() as u64,
+                "request handled"
+            );
+        }
     }
+
+    response
+}
+
+/// Awaits the handler of a request within the timeout, if any
+/// A request exceeding the timeout is answered with `503`, the handler is dropped
+async fn with_request_timeout<F>(
+    request_timeout: Option<Duration>,
+    handler: F,
+) -> Result<Response<Full<Bytes>>>
+where
+    F: Future<Output = Result<Response<Full<Bytes>>>>,
+{
+    match request_timeout {
+        Some(request_timeout) => match tokio::time::timeout(request_timeout, handler).await {
+            Ok(response) => response,
+            Err(_) => {
+                tracing::error!(
+                    "request not handled within {} ms",
+                    request_timeout.as_millis()
+                );
+                Ok(send_error_503())
+            }
+        },
+        None => handler.await,
+    }
+}
+
+/// Sends the item with the key given by the request path
+/// Blob items are sent as their content, other items as their encoded data
+/// Ranges are not supported for items, the whole item is always sent
+/// The item is sent as an attachment if a download filename is requested or set in the item metafields
+fn storage_get(
+    req: &Request<hyper::body::Incoming>,
+    context: &ServiceContext,
+) -> Result<Response<Full<Bytes>>> {
+    let key = match req
+        .uri()
+        .path()
+        .strip_prefix(STORAGE_PATH_PREFIX)
+        .and_then(percent_decode)
+    {
+        Some(key) if !key.is_empty() => key,
+        _ => return Ok(send_error_404()),
+    };
+
+    let item = match context.storage.get(&key) {
+        Some(item) => item,
+        None => return Ok(send_error_404()),
+    };
+
+    let body = match item.item_type {
+        ItemType::Complex(ComplexType::Blob) => match item.get_object::<Vec<u8>>() {
+            Some(blob) => blob,
+            None => {
+                tracing::error!("could not decode item: {}", key);
+                return Ok(send_error_500());
+            }
+        },
+        _ => item.data.clone(),
+    };
+
+    let filename = download_filename(req).or_else(|| {
+        item.metafields
+            .as_ref()
+            .and_then(|metafields| metafields.get(FILENAME_METAFIELD).cloned())
+    });
+
+    match Response::builder()
+        .status(StatusCode::OK)
+        .header(hyper::header::CONTENT_TYPE, "application/octet-stream")
+        .header(hyper::header::ACCEPT_RANGES, "none")
+        .body(Full::new(Bytes::from(body)))
+    {
+        Ok(mut response) => {
+            if let Some(filename) = filename {
+                set_content_disposition(&mut response, &filename);
+            }
+            Ok(response)
+        }
+        Err(err) => {
+            tracing::error!("unable to build response: {}", err);
+            Ok(send_error_500())
+        }
+    }
+}
+
+/// Returns the filename of the `download` query parameter
+fn download_filename(req: &Request<hyper::body::Incoming>) -> Option<String> {
+    req.uri().query()?.split('&').find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if name != DOWNLOAD_QUERY_PARAM || value.is_empty() {
+            return None;
+        }
+        percent_decode(&value.replace('+', " "))
+    })
+}
+
+/// Sets the response to be downloaded as an attachment with the filename
+fn set_content_disposition(response: &mut Response<Full<Bytes>>, filename: &str) {
+    match hyper::header::HeaderValue::from_str(&content_disposition(filename)) {
+        Ok(value) => {
+            response
+                .headers_mut()
+                .insert(hyper::header::CONTENT_DISPOSITION, value);
+        }
+        Err(err) => tracing::error!("invalid content disposition for {}: {}", filename, err),
+    }
+}
+
+/// Formats the `Content-Disposition` value of an attachment
+/// The quoted filename escapes `"` and `\`, non-ASCII filenames are added percent-encoded as `filename*`
+fn content_disposition(filename: &str) -> String {
+    let mut quoted = String::with_capacity(filename.len());
+    for c in filename.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_ascii() && !c.is_ascii_control() => quoted.push(c),
+            _ => quoted.push('_'),
+        }
+    }
+
+    let mut value = format!("attachment; filename=\"{}\"", quoted);
+    if !filename.is_ascii() {
+        let encoded: String = filename
+            .bytes()
+            .map(|b| {
+                if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                    (b as char).to_string()
+                } else {
+                    format!("%{:02X}", b)
+                }
+            })
+            .collect();
+        value.push_str(&format!("; filename*=UTF-8''{}", encoded));
+    }
+    value
+}
+
+/// Stores the request body as a blob item with the key given by the request path
+/// Responds `201` if the item was stored, `401` without the configured access token,
+/// `403` if the storage or the stored item is read-only
+async fn storage_put(
+    req: Request<hyper::body::Incoming>,
+    context: &ServiceContext,
+) -> Result<Response<Full<Bytes>>> {
+    let key = match req
+        .uri()
+        .path()
+        .strip_prefix(STORAGE_PATH_PREFIX)
+        .and_then(percent_decode)
+    {
+        Some(key) if !key.is_empty() => key,
+        _ => return Ok(send_error_404()),
+    };
+
+    if !authorized(req.headers(), context) {
+        tracing::error!("could not store item without a valid access token: {}", key);
+        return Ok(send_error_401());
+    }
+
+    if context.storage.is_read_only() {
+        tracing::error!("could not store item into read-only storage: {}", key);
+        return Ok(send_error_403());
+    }
+
+    // reject early if the announced length is over the limit
+    let content_length = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if content_length.is_some_and(|content_length| content_length > context.max_body_bytes) {
+        tracing::error!("request body is too large: {:?}", content_length);
+        return Ok(send_error_413());
+    }
+
+    // the body is read up to the limit, so a body without or with a wrong length is bounded too
+    let max_body_bytes = usize::try_from(context.max_body_bytes).unwrap_or(usize::MAX);
+    let body = match Limited::new(req.into_body(), max_body_bytes)
+        .collect()
+        .await
+    {
+        Ok(collected) => collected.to_bytes(),
+        Err(err) if err.is::<LengthLimitError>() => {
+            tracing::error!("request body is too large: {}", err);
+            return Ok(send_error_413());
+        }
+        Err(err) => {
+            tracing::error!("could not read request body: {}", err);
+            return Ok(send_error_400());
+        }
+    };
+
+    let item_type = ItemType::Complex(ComplexType::Blob);
+    let item = match context.storage.new_item(&key, item_type, &body.to_vec()) {
+        Some(item) => item,
+        None => {
+            tracing::error!("could not encode item: {}", key);
+            return Ok(send_error_500());
+        }
+    };
+
+    // the protection of the stored item is checked and the item replaced without a change in between
+    let _global_lock = context.storage.global_lock();
+    if item_read_only(&context.storage, &key) {
+        tracing::error!("could not overwrite read-only item: {}", key);
+        return Ok(send_error_403());
+    }
+    match context.storage.insert(item) {
+        Ok(()) => Ok(blank_response(StatusCode::CREATED)),
+        Err(_) => Ok(send_error_400()),
+    }
+}
+
+/// Removes the item with the key given by the request path
+/// Responds `204` if the item was removed, `401` without the configured access token,
+/// `403` if the storage or the item is read-only
+fn storage_delete(
+    req: &Request<hyper::body::Incoming>,
+    context: &ServiceContext,
+) -> Result<Response<Full<Bytes>>> {
+    let key = match req
+        .uri()
+        .path()
+        .strip_prefix(STORAGE_PATH_PREFIX)
+        .and_then(percent_decode)
+    {
+        Some(key) if !key.is_empty() => key,
+        _ => return Ok(send_error_404()),
+    };
+
+    if !authorized(req.headers(), context) {
+        tracing::error!(
+            "could not remove item without a valid access token: {}",
+            key
+        );
+        return Ok(send_error_401());
+    }
+
+    if context.storage.is_read_only() {
+        tracing::error!("could not remove item from read-only storage: {}", key);
+        return Ok(send_error_403());
+    }
+
+    // the protection of the stored item is checked and the item removed without a change in between
+    let _global_lock = context.storage.global_lock();
+    if item_read_only(&context.storage, &key) {
+        tracing::error!("could not remove read-only item: {}", key);
+        return Ok(send_error_403());
+    }
+    if context.storage.remove(&key) {
+        Ok(blank_response(StatusCode::NO_CONTENT))
+    } else {
+        Ok(send_error_404())
+    }
+}
+
+/// Returns whether the request carries the configured access token as a bearer token
+/// Every request is authorized if no access token is configured
+fn authorized(headers: &hyper::HeaderMap, context: &ServiceContext) -> bool {
+    let access_token = match &context.access_token {
+        Some(access_token) => access_token,
+        None => return true,
+    };
+    headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .is_some_and(|(scheme, token)| {
+            scheme.eq_ignore_ascii_case(BEARER_AUTH_SCHEME)
+                && constant_time_eq(token.trim().as_bytes(), access_token.as_bytes())
+        })
+}
+
+/// Compares the bytes in a time independent of the position of the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Returns whether the stored item is protected by its read-only metafield
+fn item_read_only(storage: &Storage, key: &str) -> bool {
+    storage.get(key).is_some_and(|item| {
+        item.metafields
+            .as_ref()
+            .and_then(|metafields| metafields.get(READ_ONLY_METAFIELD))
+            .is_some_and(|value| value == "true")
+    })
+}
+
+/// HTTP status code 400
+fn send_error_400() -> Response<Full<Bytes>> {
+    blank_response(StatusCode::BAD_REQUEST)
+}
+
+/// HTTP status code 401, with the expected authentication scheme in the `WWW-Authenticate` header
+fn send_error_401() -> Response<Full<Bytes>> {
+    let mut response = blank_response(StatusCode::UNAUTHORIZED);
+    response.headers_mut().insert(
+        hyper::header::WWW_AUTHENTICATE,
+        hyper::header::HeaderValue::from_static(BEARER_AUTH_SCHEME),
+    );
+    response
 }
 
 /// HTTP status code 403
@@ -134,11 +718,55 @@ fn send_error_404() -> Response<Full<Bytes>> {
     blank_response(StatusCode::NOT_FOUND)
 }
 
+/// HTTP status code 405, with the supported methods in the `Allow` header
+fn send_error_405() -> Response<Full<Bytes>> {
+    let mut response = blank_response(StatusCode::METHOD_NOT_ALLOWED);
+    insert_allowed_methods(response.headers_mut(), hyper::header::ALLOW);
+    response
+}
+
+/// Answers an `OPTIONS` request with `204` and the supported methods in the `Allow` header
+/// A CORS preflight is answered with the allowed methods and headers, if cross-origin access is configured
+fn options_response(cors: Option<&CorsConfig>) -> Response<Full<Bytes>> {
+    let mut response = blank_response(StatusCode::NO_CONTENT);
+    let headers = response.headers_mut();
+    insert_allowed_methods(headers, hyper::header::ALLOW);
+    if let Some(cors) = cors {
+        insert_allowed_methods(headers, hyper::header::ACCESS_CONTROL_ALLOW_METHODS);
+        if let Ok(value) = hyper::header::HeaderValue::from_str(&cors.allow_headers.join(", ")) {
+            headers.insert(hyper::header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+        }
+    }
+    response
+}
+
+/// Inserts the header listing the supported methods
+fn insert_allowed_methods(headers: &mut hyper::HeaderMap, name: hyper::header::HeaderName) {
+    let allow = ALLOWED_METHODS
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Ok(value) = hyper::header::HeaderValue::from_str(&allow) {
+        headers.insert(name, value);
+    }
+}
+
+/// HTTP status code 413
+fn send_error_413() -> Response<Full<Bytes>> {
+    blank_response(StatusCode::PAYLOAD_TOO_LARGE)
+}
+
 /// HTTP status code 500
 fn send_error_500() -> Response<Full<Bytes>> {
     blank_response(StatusCode::INTERNAL_SERVER_ERROR)
 }
 
+/// HTTP status code 503
+fn send_error_503() -> Response<Full<Bytes>> {
+    blank_response(StatusCode::SERVICE_UNAVAILABLE)
+}
+
 /// A blank response with status code
 fn blank_response(status_code: StatusCode) -> Response<Full<Bytes>> {
     let mut response = Response::<Full<Bytes>>::new(Full::new(Bytes::new()));
@@ -146,9 +774,24 @@ fn blank_response(status_code: StatusCode) -> Response<Full<Bytes>> {
     response
 }
 
+/// Turns the response of a `GET` into the response of a `HEAD`
+/// The headers are kept and the length of the dropped body is sent as the content length
+fn head_response(response: Response<Full<Bytes>>) -> Response<Full<Bytes>> {
+    let (mut parts, body) = response.into_parts();
+    if let Some(content_length) = body.size_hint().exact() {
+        parts
+            .headers
+            .insert(hyper::header::CONTENT_LENGTH, content_length.into());
+    }
+    Response::from_parts(parts, Full::new(Bytes::new()))
+}
+
 async fn file_info(req: &Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>> {
-    let path = req.uri().path().replace('/', "");
-    let file_path = Path::new(&path);
+    let path = match request_file_path(req.uri().path()) {
+        Some(path) => path,
+        None => return Ok(send_error_400()),
+    };
+    let file_path = path.as_path();
     tracing::debug!("file path:{:?}", file_path);
 
     if file_path.file_name().is_none() {
@@ -158,7 +801,11 @@ async fn file_info(req: &Request<hyper::body::Incoming>) -> Result<Response<Full
 
     match get_file_len(file_path).await {
         Ok(file_len) => {
-            if let Ok(response) = Response::builder()
+            let mut builder = Response::builder();
+            if let Some(validators) = get_file_validators(file_path, file_len).await {
+                builder = validators.add_headers(builder);
+            }
+            if let Ok(response) = builder
                 .status(StatusCode::OK)
                 .header(hyper::header::ACCEPT_RANGES, http_range::RANGE_UNIT)
                 .header(hyper::header::CONTENT_LENGTH, file_len)
@@ -178,6 +825,54 @@ async fn file_info(req: &Request<hyper::body::Incoming>) -> Result<Response<Full
     }
 }
 
+/// Maps the request path to a file path relative to the web root
+/// The path is percent-decoded, its structure preserved
+/// Returns `None` if the path is not valid or leaves the web root
+fn request_file_path(uri_path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(uri_path)?;
+    let mut file_path = PathBuf::new();
+    for component in Path::new(decoded.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(segment) => file_path.push(segment),
+            Component::CurDir => {}
+            _ => {
+                tracing::error!("invalid request path: {}", uri_path);
+                return None;
+            }
+        }
+    }
+    Some(file_path)
+}
+
+/// Decodes `%XX` escapes of the request path, the decoded bytes need to be valid UTF-8
+fn percent_decode(encoded: &str) -> Option<String> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = bytes.get(index + 1..index + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let hex = std::str::from_utf8(hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+
+    match String::from_utf8(decoded) {
+        Ok(decoded) if !decoded.contains('\0') => Some(decoded),
+        _ => {
+            tracing::error!("invalid percent-encoding in request path: {}", encoded);
+            None
+        }
+    }
+}
+
 async fn get_file_len(filename: &Path) -> ServiceResult<u64> {
     let file = tokio::fs::File::open(filename).await?;
     let metadata = file.metadata().await?;
@@ -195,11 +890,28 @@ async fn get_file_len(filename: &Path) -> ServiceResult<u64> {
     Err(err_msg.into())
 }
 
+/// Returns the validators of the file, `None` if the modification time is not available
+async fn get_file_validators(filename: &Path, content_length: u64) -> Option<FileValidators> {
+    match tokio::fs::metadata(filename)
+        .await
+        .and_then(|m| m.modified())
+    {
+        Ok(modified) => Some(FileValidators::new(content_length, modified)),
+        Err(err) => {
+            tracing::debug!("no modification time of the file {:?}: {}", filename, err);
+            None
+        }
+    }
+}
+
 async fn file_send(req: &Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>> {
     let content_type: &str = "text/html; charset=utf-8";
 
-    let path = req.uri().path().replace('/', "");
-    let file_path = Path::new(&path);
+    let path = match request_file_path(req.uri().path()) {
+        Some(path) => path,
+        None => return Ok(send_error_400()),
+    };
+    let file_path = path.as_path();
     tracing::debug!("file path: {:?}", file_path);
 
     if file_path.file_name().is_none() {
@@ -215,29 +927,108 @@ async fn file_send(req: &Request<hyper::body::Incoming>) -> Result<Response<Full
         return Ok(send_error_404());
     }
 
+    let validators = get_file_validators(file_path, content_length).await;
+
     let headers = req.headers();
-    let http_range_option = if headers.contains_key(hyper::header::CONTENT_RANGE) {
-        let content_range = headers.get(hyper::header::CONTENT_RANGE).unwrap();
-        HttpRange::from_header(content_range.to_str().unwrap(), content_length)
-    } else {
-        None
+    let http_range_option = match headers.get(hyper::header::RANGE) {
+        Some(range) => match range.to_str() {
+            Ok(range) => parse_range(range, content_length),
+            Err(_) => return Ok(send_error_400()),
+        },
+        None => None,
+    };
+
+    // precedence of the conditions: `If-None-Match`, `If-Range`, then the range
+    // `304 Not Modified` answers an unchanged full request only, never a ranged one
+    if http_range_option.is_none() {
+        if let (Some(if_none_match), Some(validators)) = (
+            headers.get(hyper::header::IF_NONE_MATCH),
+            validators.as_ref(),
+        ) {
+            if if_none_match
+                .to_str()
+                .is_ok_and(|if_none_match| validators.if_none_match_matches(if_none_match))
+            {
+                return Ok(send_not_modified(validators));
+            }
+        }
+    }
+
+    // a stale `If-Range` turns the ranged request into a full one
+    let http_range_option = match headers.get(hyper::header::IF_RANGE) {
+        Some(if_range) if http_range_option.is_some() => {
+            let matches = if_range.to_str().is_ok_and(|if_range| {
+                validators
+                    .as_ref()
+                    .is_some_and(|validators| validators.if_range_matches(if_range))
+            });
+            if !matches {
+                tracing::debug!("If-Range does not match, sending the full content");
+            }
+            http_range_option.filter(|_| matches)
+        }
+        _ => http_range_option,
     };
 
     match http_range_option {
         // send a response in ranges
         Some(http_range) => {
-            send_file_range(file_path, content_type, content_length, &http_range).await
+            send_file_range(
+                file_path,
+                content_type,
+                content_length,
+                &http_range,
+                validators.as_ref(),
+            )
+            .await
         }
 
         // send a response with full content
-        None => send_file_full(file_path, content_type).await,
+        None => send_file_full(file_path, content_type, validators.as_ref()).await,
+    }
+}
+
+/// HTTP status code 304 with the validators of the unchanged file
+fn send_not_modified(validators: &FileValidators) -> Response<Full<Bytes>> {
+    match validators
+        .add_headers(Response::builder())
+        .status(StatusCode::NOT_MODIFIED)
+        .body(Full::new(Bytes::new()))
+    {
+        Ok(response) => response,
+        Err(_) => {
+            tracing::error!("unable to build response");
+            send_error_500()
+        }
     }
 }
 
-async fn send_file_full(filename: &Path, content_type: &str) -> Result<Response<Full<Bytes>>> {
+/// Parses the range header, inverted ranges (start > end) are dropped
+/// A range header with only inverted ranges is not satisfiable
+fn parse_range(header: &str, content_length: u64) -> Option<HttpRange> {
+    let mut http_range = HttpRange::from_header(header, content_length)?;
+    http_range.ranges.retain(|range| {
+        let valid = range.start <= range.end;
+        if !valid {
+            tracing::debug!("inverted range dropped: {:?}", range);
+        }
+        valid
+    });
+    Some(http_range)
+}
+
+async fn send_file_full(
+    filename: &Path,
+    content_type: &str,
+    validators: Option<&FileValidators>,
+) -> Result<Response<Full<Bytes>>> {
     if let Ok(contents) = tokio::fs::read(&filename).await {
         let body = contents.into();
-        if let Ok(response) = Response::builder()
+        let mut builder = Response::builder();
+        if let Some(validators) = validators {
+            builder = validators.add_headers(builder);
+        }
+        if let Ok(response) = builder
             .status(StatusCode::OK)
             .header(hyper::header::ACCEPT_RANGES, http_range::RANGE_UNIT)
             .header(hyper::header::CONTENT_TYPE, content_type)
@@ -257,6 +1048,7 @@ async fn send_file_range(
     content_type: &str,
     content_length: u64,
     http_range: &HttpRange,
+    validators: Option<&FileValidators>,
 ) -> Result<Response<Full<Bytes>>> {
     if http_range.none_satisfiable(content_length) {
         if let Ok(response) = Response::builder()
@@ -278,7 +1070,17 @@ async fn send_file_range(
 
     let ranges = &http_range.ranges;
     for range in ranges {
-        let capacity = (range.end - range.start + 1) as usize;
+        if range.start > range.end {
+            tracing::error!("invalid range {:?}", range);
+            continue;
+        }
+        let capacity = match usize::try_from(range.end - range.start + 1) {
+            Ok(capacity) => capacity,
+            Err(_) => {
+                tracing::error!("range is too large {:?}", range);
+                continue;
+            }
+        };
         if tracing::enabled!(tracing::Level::TRACE) {
             tracing::trace!("preparing the range to send {:?}", range);
             tracing::trace!("capacity {}", capacity);
@@ -292,7 +1094,11 @@ async fn send_file_range(
                     if let Ok(read_count) = file.read_exact(&mut buffer).await {
                         tracing::trace!("read_count {}", read_count);
                         let body: Bytes = buffer.into();
-                        if let Ok(response) = Response::builder()
+                        let mut builder = Response::builder();
+                        if let Some(validators) = validators {
+                            builder = validators.add_headers(builder);
+                        }
+                        if let Ok(response) = builder
                             .status(StatusCode::PARTIAL_CONTENT)
                             .header(hyper::header::ACCEPT_RANGES, http_range::RANGE_UNIT)
                             .header(
@@ -327,3 +1133,141 @@ async fn send_file_range(
 
     Ok(send_error_404())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_frame_test() {
+        let event = ChangeEvent {
+            key: "my_key".to_string(),
+            kind: ChangeKind::Set,
+        };
+        assert_eq!(
+            sse_frame(Ok(event)).unwrap(),
+            "event: set\ndata: my_key\n\n"
+        );
+
+        let event = ChangeEvent {
+            key: "first\nsecond".to_string(),
+            kind: ChangeKind::Removed,
+        };
+        assert_eq!(
+            sse_frame(Ok(event)).unwrap(),
+            "event: removed\ndata: first\ndata: second\n\n"
+        );
+
+        // a lagging subscriber is hinted to resync
+        assert_eq!(
+            sse_frame(Err(RecvError::Lagged(42))).unwrap(),
+            "event: resync\ndata: 42\n\n"
+        );
+        assert!(sse_frame(Err(RecvError::Closed)).is_none());
+    }
+
+    #[test]
+    fn request_timeout_test() {
+        let runtime = Runtime::new().unwrap();
+        let request_timeout = Duration::from_millis(100);
+        let slow_handler = async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(blank_response(StatusCode::OK))
+        };
+
+        let started_on = Instant::now();
+        let response = runtime
+            .block_on(with_request_timeout(Some(request_timeout), slow_handler))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(started_on.elapsed() >= request_timeout);
+        assert!(started_on.elapsed() < Duration::from_secs(5));
+
+        let fast_handler = async { Ok(blank_response(StatusCode::OK)) };
+        let response = runtime
+            .block_on(with_request_timeout(Some(request_timeout), fast_handler))
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn percent_decode_test() {
+        assert_eq!(percent_decode("/my%20file.txt").unwrap(), "/my file.txt");
+        assert_eq!(
+            percent_decode("/%D0%B0%D0%BD%D0%BE%D1%80").unwrap(),
+            "/анор"
+        );
+        assert_eq!(percent_decode("/plain").unwrap(), "/plain");
+        assert!(percent_decode("/bad%2").is_none());
+        assert!(percent_decode("/bad%zz").is_none());
+        assert!(percent_decode("/bad%+1").is_none());
+        assert!(percent_decode("/bad%ff").is_none());
+        assert!(percent_decode("/bad%00").is_none());
+    }
+
+    #[test]
+    fn if_range_matches_test() {
+        let modified = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let validators = FileValidators::new(1000, modified);
+
+        assert!(validators.if_range_matches(&validators.etag));
+        assert!(!validators.if_range_matches("\"stale\""));
+        assert!(!validators.if_range_matches(&format!("W/{}", validators.etag)));
+
+        assert!(validators.if_range_matches("Tue, 14 Nov 2023 22:13:20 GMT"));
+        assert!(!validators.if_range_matches("Tue, 14 Nov 2023 22:13:21 GMT"));
+        assert!(!validators.if_range_matches("not a date"));
+    }
+
+    #[test]
+    fn if_none_match_matches_test() {
+        let validators = FileValidators::new(1000, UNIX_EPOCH);
+
+        assert!(validators.if_none_match_matches(&validators.etag));
+        assert!(validators.if_none_match_matches(&format!("W/{}", validators.etag)));
+        assert!(validators.if_none_match_matches(&format!("\"a\", {}", validators.etag)));
+        assert!(validators.if_none_match_matches("*"));
+        assert!(!validators.if_none_match_matches("\"stale\""));
+    }
+
+    #[test]
+    fn parse_range_test() {
+        let http_range = parse_range("bytes=100-50", 1000).unwrap();
+        assert!(http_range.ranges.is_empty());
+        assert!(http_range.none_satisfiable(1000));
+
+        let http_range = parse_range("bytes=0-0", 1000).unwrap();
+        assert_eq!(http_range.ranges, vec![0..0]);
+        assert!(!http_range.none_satisfiable(1000));
+    }
+
+    #[test]
+    fn request_file_path_test() {
+        assert_eq!(
+            request_file_path("/docs/my%20file.txt").unwrap(),
+            Path::new("docs").join("my file.txt")
+        );
+        assert_eq!(
+            request_file_path("/./%D0%B0%D0%BD%D0%BE%D1%80.txt").unwrap(),
+            Path::new("анор.txt")
+        );
+        assert!(request_file_path("/docs/../../secret").is_none());
+        assert!(request_file_path("/%2E%2E/secret").is_none());
+    }
+
+    #[test]
+    fn content_disposition_test() {
+        assert_eq!(
+            content_disposition("report.pdf"),
+            r#"attachment; filename="report.pdf""#
+        );
+        assert_eq!(
+            content_disposition(r#"my "best"\report.pdf"#),
+            r#"attachment; filename="my \"best\"\\report.pdf""#
+        );
+        assert_eq!(
+            content_disposition("анор.txt"),
+            "attachment; filename=\"____.txt\"; filename*=UTF-8''%D0%B0%D0%BD%D0%BE%D1%80.txt"
+        );
+    }
+}
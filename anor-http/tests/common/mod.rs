@@ -0,0 +1,112 @@
+//! Harness shared by the http service tests
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{mpsc::channel, Arc};
+use std::thread::JoinHandle;
+
+use anor_http::service::http_service::Service;
+use anor_storage::Storage;
+use anor_utils::config::{Config, HttpConfig};
+use anor_utils::{Metrics, ShutdownToken};
+
+/// Returns the configuration of a service listening on a free port of the loopback interface
+pub fn http_config() -> HttpConfig {
+    HttpConfig {
+        listen_on: vec!["127.0.0.1:0".parse().unwrap()],
+        enabled: true,
+        max_body_bytes: 1024,
+        ..Default::default()
+    }
+}
+
+/// Returns the value of the response header, the name is matched case-insensitively
+pub fn header_value(response: &str, name: &str) -> String {
+    response
+        .lines()
+        .find_map(|line| {
+            let (key, value) = line.split_once(": ")?;
+            key.eq_ignore_ascii_case(name).then(|| value.to_string())
+        })
+        .unwrap()
+}
+
+/// Returns the body of the response
+pub fn body_of(response: &str) -> &str {
+    response.split_once("\r\n\r\n").unwrap().1
+}
+
+/// Running http service, shut down when dropped
+pub struct TestService {
+    pub addr: SocketAddr,
+    pub storage: Arc<Storage>,
+    pub metrics: Arc<Metrics>,
+    shutdown: Arc<ShutdownToken>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TestService {
+    /// Starts the service on an empty in-memory storage
+    pub fn start(http: HttpConfig) -> Self {
+        Self::start_with_storage(http, Arc::new(Storage::open_in_memory()))
+    }
+
+    /// Starts the service on the storage, returns once the service accepts connections
+    pub fn start_with_storage(http: HttpConfig, storage: Arc<Storage>) -> Self {
+        let config = Arc::new(Config {
+            http: Some(http),
+            ..Default::default()
+        });
+        let service = Service::with_config(storage.clone(), config);
+        let shutdown = Arc::new(ShutdownToken::new());
+        let (ready_sender, ready_receiver) = channel();
+        let handle = service.start(ready_sender, shutdown.clone());
+        ready_receiver.recv().unwrap();
+
+        TestService {
+            addr: service.local_addr().unwrap(),
+            storage,
+            metrics: service.metrics(),
+            shutdown,
+            handle: Some(handle),
+        }
+    }
+
+    /// Sends a request on a new connection and returns the whole response
+    /// The headers are given as `Name: value\r\n` lines, `Host` and `Connection: close` are added
+    pub fn request(&self, method: &str, path: &str, headers: &str, body: &[u8]) -> String {
+        let mut stream = TcpStream::connect(self.addr).unwrap();
+        let head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+            method, path, self.addr, headers
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    /// Sends a `GET` request of the path without additional headers
+    pub fn get(&self, path: &str) -> String {
+        self.request("GET", path, "", &[])
+    }
+
+    /// Shuts the service down and waits until its connections are closed
+    pub fn stop(mut self) {
+        self.shutdown.trigger();
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap();
+        }
+    }
+}
+
+impl Drop for TestService {
+    fn drop(&mut self) {
+        self.shutdown.trigger();
+        if let Some(handle) = self.handle.take() {
+            _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+/// subscriber collecting the fields of the info events as `name=value` lines
+struct LogCollector {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+struct FieldVisitor<'a>(&'a mut Vec<String>);
+
+impl tracing::field::Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.push(format!("{}={}", field.name(), value));
+    }
+}
+
+impl tracing::Subscriber for LogCollector {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        *metadata.level() == tracing::Level::INFO
+    }
+    fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+    fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+    fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut fields = vec![];
+        event.record(&mut FieldVisitor(&mut fields));
+        self.lines.lock().unwrap().push(fields.join(" "));
+    }
+    fn enter(&self, _span: &tracing::span::Id) {}
+    fn exit(&self, _span: &tracing::span::Id) {}
+}
+
+#[test]
+fn http_access_log() {
+    use anor_utils::config::HttpConfig;
+    use common::{http_config, TestService};
+
+    // the service logs from its own threads, so the subscriber is set globally
+    let lines = Arc::new(Mutex::new(vec![]));
+    tracing::subscriber::set_global_default(LogCollector {
+        lines: lines.clone(),
+    })
+    .unwrap();
+
+    for access_log in [true, false] {
+        let service = TestService::start(HttpConfig {
+            access_log,
+            ..http_config()
+        });
+
+        assert!(service.get("/LICENSE").starts_with("HTTP/1.1 200"));
+        assert!(service.get("/missing.txt").starts_with("HTTP/1.1 404"));
+
+        service.stop();
+
+        let access_lines: Vec<String> = lines
+            .lock()
+            .unwrap()
+            .drain(..)
+            .filter(|line| line.contains("request handled"))
+            .collect();
+        if access_log {
+            assert_eq!(access_lines.len(), 2);
+            assert!(access_lines[0].contains("method=GET"));
+            assert!(access_lines[0].contains("path=/LICENSE"));
+            assert!(access_lines[0].contains("status=200"));
+            assert!(access_lines[0].contains("duration_us="));
+            assert!(access_lines[1].contains("path=/missing.txt"));
+            assert!(access_lines[1].contains("status=404"));
+        } else {
+            assert!(access_lines.is_empty());
+        }
+    }
+}
@@ -0,0 +1,53 @@
+mod common;
+
+#[test]
+fn http_auth() {
+    use anor_storage::StorageItem;
+    use anor_utils::config::HttpConfig;
+    use common::{header_value, http_config, TestService};
+
+    let service = TestService::start(HttpConfig {
+        access_token: Some("my-token".to_string()),
+        ..http_config()
+    });
+    let item = StorageItem::builder("protected")
+        .metafield("read_only", "true")
+        .build(&String::from("abc"))
+        .unwrap();
+    service.storage.insert(item).unwrap();
+
+    let authorization = "Authorization: Bearer my-token\r\n";
+    let put = |headers: &str| {
+        let headers = format!("{}Content-Length: 3\r\n", headers);
+        service.request("PUT", "/storage/my_key", &headers, b"def")
+    };
+
+    // writes without the access token are rejected
+    let response = put("");
+    assert!(response.starts_with("HTTP/1.1 401"));
+    assert_eq!(header_value(&response, "www-authenticate"), "Bearer");
+    assert!(put("Authorization: Bearer other-token\r\n").starts_with("HTTP/1.1 401"));
+    assert!(put("Authorization: Basic my-token\r\n").starts_with("HTTP/1.1 401"));
+    assert!(service.storage.get("my_key").is_none());
+    let response = service.request("DELETE", "/storage/protected", "", &[]);
+    assert!(response.starts_with("HTTP/1.1 401"));
+
+    // writes with the access token are served, reads do not require it
+    assert!(put(authorization).starts_with("HTTP/1.1 201"));
+    assert!(service.get("/storage/my_key").ends_with("\r\n\r\ndef"));
+    let response = service.request("DELETE", "/storage/my_key", authorization, &[]);
+    assert!(response.starts_with("HTTP/1.1 204"));
+
+    // a read-only item is neither overwritten nor removed, even with the access token
+    let headers = format!("{}Content-Length: 3\r\n", authorization);
+    let response = service.request("PUT", "/storage/protected", &headers, b"def");
+    assert!(response.starts_with("HTTP/1.1 403"));
+    let response = service.request("DELETE", "/storage/protected", authorization, &[]);
+    assert!(response.starts_with("HTTP/1.1 403"));
+    assert_eq!(
+        service.storage.get_inner_object::<String>("protected"),
+        Some("abc".to_string())
+    );
+
+    service.stop();
+}
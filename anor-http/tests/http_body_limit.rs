@@ -0,0 +1,42 @@
+mod common;
+
+#[test]
+fn http_body_limit() {
+    use anor_utils::config::HttpConfig;
+    use common::{http_config, TestService};
+
+    const MAX_BODY_BYTES: usize = 16;
+
+    let service = TestService::start(HttpConfig {
+        max_body_bytes: MAX_BODY_BYTES as u64,
+        ..http_config()
+    });
+    let storage = service.storage.clone();
+
+    // over the limit, announced by the content length
+    let body = vec![b'a'; MAX_BODY_BYTES + 1];
+    let headers = format!("Content-Length: {}\r\n", body.len());
+    let response = service.request("PUT", "/storage/large", &headers, &body);
+    assert!(response.starts_with("HTTP/1.1 413"));
+    assert!(storage.get("large").is_none());
+
+    // over the limit, chunked without a content length
+    let body = format!(
+        "{:x}\r\n{}\r\n0\r\n\r\n",
+        MAX_BODY_BYTES + 1,
+        "a".repeat(MAX_BODY_BYTES + 1)
+    );
+    let headers = "Transfer-Encoding: chunked\r\n";
+    let response = service.request("PUT", "/storage/chunked", headers, body.as_bytes());
+    assert!(response.starts_with("HTTP/1.1 413"));
+    assert!(storage.get("chunked").is_none());
+
+    // under the limit
+    let body = vec![b'a'; MAX_BODY_BYTES];
+    let headers = format!("Content-Length: {}\r\n", body.len());
+    let response = service.request("PUT", "/storage/small", &headers, &body);
+    assert!(response.starts_with("HTTP/1.1 201"));
+    assert_eq!(storage.get_inner_object::<Vec<u8>>("small"), Some(body));
+
+    service.stop();
+}
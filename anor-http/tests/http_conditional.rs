@@ -0,0 +1,46 @@
+mod common;
+
+#[test]
+fn http_conditional() {
+    use common::{body_of, header_value, http_config, TestService};
+
+    let service = TestService::start(http_config());
+    let send_request = |headers: &str| service.request("GET", "/LICENSE", headers, &[]);
+
+    let license = std::fs::read_to_string("LICENSE").unwrap();
+    let etag = header_value(&send_request(""), "etag");
+
+    // conditional full request, unchanged
+    let response = send_request(&format!("If-None-Match: {}\r\n", etag));
+    assert!(response.starts_with("HTTP/1.1 304"));
+    assert_eq!(header_value(&response, "etag"), etag);
+    assert!(body_of(&response).is_empty());
+
+    // conditional full request, changed
+    let response = send_request("If-None-Match: \"stale\"\r\n");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert_eq!(body_of(&response), license);
+
+    // a ranged request is never answered with 304
+    let response = send_request(&format!("Range: bytes=0-9\r\nIf-None-Match: {}\r\n", etag));
+    assert!(response.starts_with("HTTP/1.1 206"));
+    assert_eq!(body_of(&response), &license[0..10]);
+
+    // conditional range, If-Range matches
+    let response = send_request(&format!(
+        "Range: bytes=0-9\r\nIf-None-Match: {}\r\nIf-Range: {}\r\n",
+        etag, etag
+    ));
+    assert!(response.starts_with("HTTP/1.1 206"));
+    assert_eq!(body_of(&response), &license[0..10]);
+
+    // conditional range, If-Range does not match
+    let response = send_request(&format!(
+        "Range: bytes=0-9\r\nIf-None-Match: {}\r\nIf-Range: \"stale\"\r\n",
+        etag
+    ));
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert_eq!(body_of(&response), license);
+
+    service.stop();
+}
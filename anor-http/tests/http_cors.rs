@@ -0,0 +1,39 @@
+mod common;
+
+#[test]
+fn http_cors() {
+    use anor_utils::config::{CorsConfig, HttpConfig};
+    use common::{http_config, TestService};
+
+    let service = TestService::start(HttpConfig {
+        cors: Some(CorsConfig {
+            allow_origin: "https://app.example".to_string(),
+            allow_headers: vec!["Content-Type".to_string(), "X-Request-Id".to_string()],
+        }),
+        ..http_config()
+    });
+
+    // the preflight of a cross-origin put
+    let response = service.request(
+        "OPTIONS",
+        "/storage/my_key",
+        "Origin: https://app.example\r\nAccess-Control-Request-Method: PUT\r\nAccess-Control-Request-Headers: content-type\r\n",
+        &[],
+    );
+    assert!(response.starts_with("HTTP/1.1 204"));
+    assert!(response.contains("access-control-allow-origin: https://app.example\r\n"));
+    assert!(response.contains("access-control-allow-methods: GET, HEAD, PUT, DELETE, OPTIONS\r\n"));
+    assert!(response.contains("access-control-allow-headers: Content-Type, X-Request-Id\r\n"));
+
+    // the actual request allows the origin as well
+    let response = service.request(
+        "PUT",
+        "/storage/my_key",
+        "Origin: https://app.example\r\nContent-Length: 3\r\n",
+        b"abc",
+    );
+    assert!(response.starts_with("HTTP/1.1 20"));
+    assert!(response.contains("access-control-allow-origin: https://app.example\r\n"));
+
+    service.stop();
+}
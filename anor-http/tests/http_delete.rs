@@ -0,0 +1,33 @@
+mod common;
+
+#[test]
+fn http_delete() {
+    use anor_storage::StorageItem;
+    use common::{http_config, TestService};
+
+    let service = TestService::start(http_config());
+    service
+        .storage
+        .insert(StorageItem::new("my_key", &String::from("abc")).unwrap())
+        .unwrap();
+    let send_request = |method: &str, path: &str| service.request(method, path, "", &[]);
+
+    // an existing item is removed
+    assert!(send_request("DELETE", "/storage/my_key").starts_with("HTTP/1.1 204"));
+    assert!(send_request("GET", "/storage/my_key").starts_with("HTTP/1.1 404"));
+
+    // an item protected by its metafield is kept
+    let item = StorageItem::builder("protected")
+        .metafield("read_only", "true")
+        .build(&String::from("abc"))
+        .unwrap();
+    service.storage.insert(item).unwrap();
+    assert!(send_request("DELETE", "/storage/protected").starts_with("HTTP/1.1 403"));
+    assert!(service.storage.contains_key("protected"));
+
+    // a missing item
+    assert!(send_request("DELETE", "/storage/my_key").starts_with("HTTP/1.1 404"));
+    assert!(send_request("DELETE", "/storage/missing").starts_with("HTTP/1.1 404"));
+
+    service.stop();
+}
@@ -0,0 +1,54 @@
+mod common;
+
+#[test]
+fn http_download() {
+    use anor_storage::storage::storage_item::{ComplexType, ItemType};
+    use anor_storage::StorageItem;
+    use common::{http_config, TestService};
+
+    let service = TestService::start(http_config());
+    let blob = b"%PDF-1.7".to_vec();
+    let item_type = ItemType::Complex(ComplexType::Blob);
+    service
+        .storage
+        .insert(StorageItem::with_type("report", item_type.clone(), &blob).unwrap())
+        .unwrap();
+    let item = StorageItem::builder("export")
+        .item_type(item_type)
+        .metafield("filename", "data.csv")
+        .build(&b"a,b\n".to_vec())
+        .unwrap();
+    service.storage.insert(item).unwrap();
+
+    // the filename of the query parameter
+    let response = service.get("/storage/report?download=report.pdf");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("content-disposition: attachment; filename=\"report.pdf\"\r\n"));
+    assert!(response.ends_with("\r\n\r\n%PDF-1.7"));
+
+    // quotes in the filename are escaped
+    let response = service.get("/storage/report?download=my%22report%22.pdf");
+    assert!(
+        response.contains("content-disposition: attachment; filename=\"my\\\"report\\\".pdf\"\r\n")
+    );
+
+    // the filename of the item metafield
+    let response = service.get("/storage/export");
+    assert!(response.contains("content-disposition: attachment; filename=\"data.csv\"\r\n"));
+
+    // no filename requested
+    let response = service.get("/storage/report");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(!response.contains("content-disposition"));
+
+    assert!(service
+        .get("/storage/missing?download=a.txt")
+        .starts_with("HTTP/1.1 404"));
+
+    // files are downloaded with the requested filename too
+    let response = service.get("/LICENSE?download=license.txt");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.contains("content-disposition: attachment; filename=\"license.txt\"\r\n"));
+
+    service.stop();
+}
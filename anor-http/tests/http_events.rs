@@ -0,0 +1,47 @@
+mod common;
+
+#[test]
+fn http_events() {
+    use anor_storage::storage::storage_item::{BasicType, ItemType};
+    use common::{http_config, TestService};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::time::Duration;
+
+    let service = TestService::start(http_config());
+    let storage = service.storage.clone();
+
+    let mut stream = TcpStream::connect(service.addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let request = format!("GET /_events HTTP/1.1\r\nHost: {}\r\n\r\n", service.addr);
+    stream.write_all(request.as_bytes()).unwrap();
+
+    // reads until the received data contains the pattern, returns the data received so far
+    let mut received = String::new();
+    let mut read_until = |pattern: &str| -> String {
+        let mut buf = [0_u8; 1024];
+        while !received.contains(pattern) {
+            let len = stream.read(&mut buf).unwrap();
+            assert!(len > 0, "stream closed before `{}`", pattern);
+            received.push_str(&String::from_utf8_lossy(&buf[..len]));
+        }
+        received.clone()
+    };
+
+    // the subscription is in place once the headers are received
+    let headers = read_until("\r\n\r\n");
+    assert!(headers.starts_with("HTTP/1.1 200"));
+    assert!(headers.contains("content-type: text/event-stream"));
+
+    storage
+        .set("my_key", ItemType::Basic(BasicType::U32), &42_u32)
+        .unwrap();
+    read_until("event: set\ndata: my_key\n\n");
+    assert!(storage.remove("my_key"));
+    read_until("event: removed\ndata: my_key\n\n");
+
+    // the shutdown ends the stream
+    service.stop();
+}
@@ -0,0 +1,36 @@
+mod common;
+
+#[test]
+fn http_head() {
+    use anor_storage::{ComplexType, ItemType, StorageItem};
+    use common::{http_config, TestService};
+
+    let service = TestService::start(http_config());
+    let content = b"stored only".to_vec();
+    service
+        .storage
+        .insert(
+            StorageItem::with_type("my_blob", ItemType::Complex(ComplexType::Blob), &content)
+                .unwrap(),
+        )
+        .unwrap();
+    let send_request = |method: &str, path: &str| service.request(method, path, "", &[]);
+
+    // the stored item is found with the headers of a GET and no body
+    let response = send_request("HEAD", "/storage/my_blob");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    let (headers, body) = response.split_once("\r\n\r\n").unwrap();
+    let headers = headers.to_ascii_lowercase();
+    assert!(headers.contains(&format!("content-length: {}\r\n", content.len())));
+    assert!(headers.contains("content-type: application/octet-stream"));
+    assert!(headers.contains("accept-ranges: none"));
+    assert!(body.is_empty());
+
+    let response = send_request("GET", "/storage/my_blob");
+    assert!(response.ends_with("\r\n\r\nstored only"));
+
+    // a missing item
+    assert!(send_request("HEAD", "/storage/missing").starts_with("HTTP/1.1 404"));
+
+    service.stop();
+}
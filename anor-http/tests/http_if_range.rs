@@ -0,0 +1,40 @@
+mod common;
+
+#[test]
+fn http_if_range() {
+    use common::{body_of, header_value, http_config, TestService};
+
+    let service = TestService::start(http_config());
+    let send_request = |headers: &str| service.request("GET", "/LICENSE", headers, &[]);
+
+    let license = std::fs::read_to_string("LICENSE").unwrap();
+
+    // the full response carries the validators
+    let response = send_request("");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    let etag = header_value(&response, "etag");
+    let last_modified = header_value(&response, "last-modified");
+
+    // matching entity tag, the range is sent
+    let response = send_request(&format!("Range: bytes=0-0\r\nIf-Range: {}\r\n", etag));
+    assert!(response.starts_with("HTTP/1.1 206"));
+    assert_eq!(body_of(&response), &license[0..1]);
+
+    // matching modification date, the range is sent
+    let response = send_request(&format!(
+        "Range: bytes=0-0\r\nIf-Range: {}\r\n",
+        last_modified
+    ));
+    assert!(response.starts_with("HTTP/1.1 206"));
+
+    // stale entity tag, the full content is sent
+    let response = send_request("Range: bytes=0-0\r\nIf-Range: \"stale\"\r\n");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert_eq!(body_of(&response), license);
+
+    // stale modification date, the full content is sent
+    let response = send_request("Range: bytes=0-0\r\nIf-Range: Thu, 01 Jan 1970 00:00:00 GMT\r\n");
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    service.stop();
+}
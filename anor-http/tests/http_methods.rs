@@ -0,0 +1,33 @@
+mod common;
+
+#[test]
+fn http_methods() {
+    use common::{http_config, TestService};
+
+    let service = TestService::start(http_config());
+    let send_request = |method: &str, path: &str| service.request(method, path, "", &[]);
+
+    // an existing resource with an unsupported method
+    let response = send_request("POST", "/LICENSE");
+    assert!(response.starts_with("HTTP/1.1 405"));
+    assert!(response.contains("allow: GET, HEAD, PUT, DELETE, OPTIONS\r\n"));
+
+    let response = send_request("PATCH", "/storage/my_key");
+    assert!(response.starts_with("HTTP/1.1 405"));
+    assert!(response.contains("allow: GET, HEAD, PUT, DELETE, OPTIONS\r\n"));
+
+    // options are answered without cross-origin headers, as cross-origin access is not configured
+    let response = send_request("OPTIONS", "/storage/my_key");
+    assert!(response.starts_with("HTTP/1.1 204"));
+    assert!(response.contains("allow: GET, HEAD, PUT, DELETE, OPTIONS\r\n"));
+    assert!(!response.contains("access-control-"));
+
+    // files cannot be written or deleted, the paths outside the storage are not found
+    assert!(send_request("PUT", "/LICENSE").starts_with("HTTP/1.1 404"));
+    assert!(send_request("DELETE", "/LICENSE").starts_with("HTTP/1.1 404"));
+
+    // supported methods are not affected
+    assert!(send_request("GET", "/LICENSE").starts_with("HTTP/1.1 200"));
+
+    service.stop();
+}
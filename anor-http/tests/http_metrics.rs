@@ -0,0 +1,24 @@
+mod common;
+
+#[test]
+fn http_metrics() {
+    use common::{http_config, TestService};
+
+    let service = TestService::start(http_config());
+    let metrics = service.metrics.clone();
+
+    for _ in 0..3 {
+        assert!(service.get("/LICENSE").starts_with("HTTP/1.1 200"));
+    }
+    assert!(service.get("/missing").starts_with("HTTP/1.1 404"));
+
+    assert_eq!(metrics.requests("GET", "200"), 3);
+    assert_eq!(metrics.requests("GET", "404"), 1);
+    assert_eq!(metrics.requests_total(), 4);
+    assert!(metrics.bytes_sent() > 0);
+
+    // shutdown the service
+    service.stop();
+
+    assert_eq!(metrics.active_connections(), 0);
+}
@@ -0,0 +1,28 @@
+mod common;
+
+#[test]
+fn http_paths() {
+    use common::{http_config, TestService};
+    use std::path::Path;
+
+    // files are served relative to the working directory
+    let files_path = Path::new("target").join("tmp").join("http_paths_files");
+    std::fs::create_dir_all(&files_path).unwrap();
+    std::fs::write(files_path.join("my file.txt"), "space").unwrap();
+    std::fs::write(files_path.join("файл.txt"), "unicode").unwrap();
+
+    let service = TestService::start(http_config());
+
+    let response = service.get("/target/tmp/http_paths_files/my%20file.txt");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("space"));
+
+    let response = service.get("/target/tmp/http_paths_files/%D1%84%D0%B0%D0%B9%D0%BB.txt");
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("unicode"));
+
+    let response = service.get("/target/tmp/http_paths_files/my%zzfile.txt");
+    assert!(response.starts_with("HTTP/1.1 400"));
+
+    service.stop();
+}
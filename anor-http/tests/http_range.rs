@@ -0,0 +1,29 @@
+mod common;
+
+#[test]
+fn http_range() {
+    use common::{body_of, http_config, TestService};
+
+    let service = TestService::start(http_config());
+    let send_request = |range: &str| {
+        let headers = format!("Range: {}\r\n", range);
+        service.request("GET", "/LICENSE", &headers, &[])
+    };
+
+    // inverted range is rejected
+    let response = send_request("bytes=100-50");
+    assert!(response.starts_with("HTTP/1.1 416"));
+
+    // single byte range
+    let response = send_request("bytes=0-0");
+    assert!(response.starts_with("HTTP/1.1 206"));
+    let license = std::fs::read_to_string("LICENSE").unwrap();
+    assert_eq!(body_of(&response), &license[0..1]);
+
+    // the range is requested by `Range`, a `Content-Range` request header is ignored
+    let response = service.request("GET", "/LICENSE", "Content-Range: bytes=0-0\r\n", &[]);
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert_eq!(body_of(&response), license);
+
+    service.stop();
+}
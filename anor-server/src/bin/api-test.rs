@@ -1,6 +1,6 @@
 use tracing_subscriber::{prelude::*, util::SubscriberInitExt};
 
-use anor_api::{client::api_client, SocketClient};
+use anor_api::{SocketClient, StorageApiClient};
 use anor_utils::config;
 
 fn main() {
@@ -19,10 +19,10 @@ fn main() {
     let config = config::load();
 
     // api client tests
-    let mut api_client1 = api_client::Client::with_config(config.clone());
+    let mut api_client1 = StorageApiClient::with_config(config.clone());
     api_client1.connect().expect("client connection error");
 
-    let keys = api_client1.keys();
+    let keys = api_client1.keys().expect("client request error");
     tracing::debug!("{:?}", keys);
 
     _ = api_client1.disconnect();
@@ -1,18 +1,18 @@
-use std::sync::atomic::Ordering;
 use std::thread::{self, JoinHandle};
 use std::{
-    sync::{atomic::AtomicBool, mpsc::channel, Arc},
-    time::Instant,
+    sync::{mpsc::channel, Arc},
+    time::{Duration, Instant},
 };
 
 use tokio::signal::unix::{signal, SignalKind};
 
 use tracing_subscriber::{prelude::*, util::SubscriberInitExt};
 
-use anor_api::{client::api_client, ApiService, SocketClient};
-use anor_http::{http_client, http_service};
+use anor_api::ApiService;
+use anor_http::http_service;
 use anor_storage::Storage;
 use anor_utils::config::{self, Config};
+use anor_utils::ShutdownToken;
 
 #[tokio::main]
 async fn main() {
@@ -27,6 +27,12 @@ async fn main() {
 
     tracing::info!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
+    // validate the configuration and exit, if requested
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(index) = args.iter().position(|arg| arg == "--check-config") {
+        std::process::exit(check_config(args.get(index + 1)));
+    }
+
     // load the configuration
     let config = config::load();
 
@@ -34,7 +40,16 @@ async fn main() {
     let storage = Storage::open_with_config(config.clone());
     let arc_storage = Arc::new(storage);
 
-    let server_shutdown = Arc::new(AtomicBool::new(false));
+    let server_shutdown = Arc::new(ShutdownToken::new());
+
+    // flush the modified storage periodically, if configured
+    let flush_interval = config
+        .storage
+        .as_ref()
+        .and_then(|storage| storage.flush_interval);
+    if let Some(flush_interval) = flush_interval {
+        start_autoflush(arc_storage.clone(), flush_interval, server_shutdown.clone());
+    }
 
     // starting API service
     let api_service = if config.api.is_some() && config.api.as_ref().unwrap().enabled {
@@ -59,7 +74,6 @@ async fn main() {
     };
 
     // hook for graceful shutdown
-    let config_cloned = config.clone();
     tokio::spawn(async move {
         let mut sigint = signal(SignalKind::interrupt()).unwrap();
         let mut sigterm = signal(SignalKind::terminate()).unwrap();
@@ -71,7 +85,7 @@ async fn main() {
                 tracing::debug!("Recieved SIGTERM");
             },
         };
-        graceful_shutdown(server_shutdown, config_cloned).await;
+        graceful_shutdown(&server_shutdown);
     });
 
     if let Some(service) = api_service {
@@ -87,10 +101,31 @@ async fn main() {
     tracing::info!("Anor Server shutdown successfully.");
 }
 
+/// Validates the configuration file without opening the storage or binding sockets
+/// Returns the process exit code
+fn check_config(config_filename: Option<&String>) -> i32 {
+    let result = match config_filename {
+        Some(filename) => config::try_load_from(filename),
+        None => config::try_load(),
+    };
+
+    match result {
+        Ok(config) => {
+            println!("{}", config);
+            println!("Configuration is valid.");
+            0
+        }
+        Err(err) => {
+            eprintln!("Configuration is invalid: {}", err);
+            1
+        }
+    }
+}
+
 fn start_api_service(
     config: Arc<Config>,
     storage: Arc<Storage>,
-    server_shutdown: Arc<AtomicBool>,
+    server_shutdown: Arc<ShutdownToken>,
 ) -> JoinHandle<()> {
     let launch_start = Instant::now();
     tracing::info!("Starting API service...");
@@ -122,7 +157,7 @@ fn start_api_service(
 fn start_http_service(
     config: Arc<Config>,
     storage: Arc<Storage>,
-    server_shutdown: Arc<AtomicBool>,
+    server_shutdown: Arc<ShutdownToken>,
 ) -> JoinHandle<()> {
     tracing::info!("Starting HTTP service...");
     let launch_start = Instant::now();
@@ -147,22 +182,32 @@ fn start_http_service(
     handle_http_service
 }
 
-async fn graceful_shutdown(server_shutdown: Arc<AtomicBool>, config: Arc<Config>) {
-    tracing::info!("Initializing the graceful shutdown process...");
-    server_shutdown.store(true, Ordering::SeqCst);
-
-    // a temporary solution to unblock socket listener
-    // make an empty connection to unblock listener and shutdown the api server
-    if config.api.is_some() && config.api.as_ref().unwrap().enabled {
-        let mut api_client_terminate = api_client::Client::with_config(config.clone());
-        api_client_terminate
-            .connect()
-            .expect("client connection error");
-        _ = api_client_terminate.disconnect();
-    }
+/// Flushes the storage every interval if modified, until the shutdown
+fn start_autoflush(
+    storage: Arc<Storage>,
+    flush_interval: Duration,
+    server_shutdown: Arc<ShutdownToken>,
+) {
+    tracing::info!("Flushing the storage every {:?}", flush_interval);
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(flush_interval) => {}
+                _ = server_shutdown.triggered() => break,
+            }
+            let storage = storage.clone();
+            match tokio::task::spawn_blocking(move || storage.flush_if_dirty()).await {
+                Ok(Ok(Some(report))) => tracing::debug!("autoflush: {:?}", report),
+                Ok(Ok(None)) => {}
+                Ok(Err(err)) => tracing::error!("autoflush failed: {}", err),
+                Err(err) => tracing::error!("autoflush failed: {}", err),
+            }
+        }
+    });
+}
 
-    if config.http.is_some() && config.http.as_ref().unwrap().enabled {
-        let url = http_client::parse_url_to_uri("http://127.0.0.1:8181/LICENSE");
-        _ = http_client::request_url("HEAD", url, None).await;
-    }
+fn graceful_shutdown(server_shutdown: &ShutdownToken) {
+    tracing::info!("Initializing the graceful shutdown process...");
+    // wakes the accept loops of both services
+    server_shutdown.trigger();
 }
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn fixture(filename: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(filename)
+}
+
+#[test]
+fn check_config_valid() {
+    let output = Command::new(env!("CARGO_BIN_EXE_server"))
+        .arg("--check-config")
+        .arg(fixture("valid-config.yaml"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("api:\n  listen_on: 127.0.0.1:9191\n  enabled: true\n"));
+    assert!(stdout.contains("Configuration is valid."));
+}
+
+#[test]
+fn check_config_invalid() {
+    let output = Command::new(env!("CARGO_BIN_EXE_server"))
+        .arg("--check-config")
+        .arg(fixture("invalid-config.yaml"))
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`api.listen_port`"));
+}
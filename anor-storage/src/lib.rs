@@ -3,8 +3,27 @@
 //! Project Stage
 //!
 //! **Development**: this project already has milestone releases, but is still under active development, you should not expect full stability yet.
+//!
+//! **Embedding**
+//!
+//! The `try_*` methods never panic and are the supported path for embedding the storage into another process:
+//! [`Storage::try_open`], [`Storage::try_open_with_config`], [`Storage::load`], [`Storage::try_flush`] and [`Storage::try_close`].
+//! The panicking methods, e.g. [`Storage::open`] and [`Storage::open_with_config`], are process-owning convenience wrappers
+//! for binaries and tests that have no better way to handle the failure, each one documents its non-panicking variant.
 
 pub mod storage;
 
 pub use storage::*;
+pub use storage_archive::*;
+pub use storage_audit::*;
+pub use storage_backend::*;
+pub use storage_batch::*;
+pub use storage_changes::*;
+pub use storage_codec::CodecError;
+pub use storage_dir::*;
+pub use storage_error::*;
+pub use storage_file_backend::*;
 pub use storage_item::*;
+pub use storage_mem_backend::*;
+pub use storage_timeseries::*;
+pub use storage_watch::*;
@@ -1,60 +1,201 @@
-use anor_utils::config::{self, Config};
-use fs2::FileExt;
+use anor_utils::config::{self, Config, IdScheme, KeyPolicy};
+use notify::Watcher;
 use std::{
+    any::{Any, TypeId},
+    borrow::Cow,
     collections::{HashMap, HashSet},
-    fs::{self, File, FileType},
-    path::PathBuf,
-    sync::{Arc, Mutex, MutexGuard, RwLock},
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::Receiver,
+        Arc, Mutex, MutexGuard, RwLock, TryLockError,
+    },
     thread::{self, ThreadId},
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+pub mod storage_archive;
+pub mod storage_audit;
+pub mod storage_backend;
+pub mod storage_batch;
+pub mod storage_changes;
 pub mod storage_codec;
 pub mod storage_const;
+pub mod storage_dir;
+pub mod storage_error;
+pub mod storage_file_backend;
 pub mod storage_item;
+pub mod storage_mem_backend;
 pub mod storage_persistence;
 pub mod storage_packet;
-
-use storage_codec::*;
-use storage_const::*;
+pub mod storage_timeseries;
+pub mod storage_watch;
+
+use storage_archive::*;
+use storage_audit::*;
+use storage_backend::*;
+use storage_batch::*;
+use storage_changes::*;
+use storage_codec::CodecError;
+use storage_const::{CHANGE_LOG_CAPACITY, FILE_STORAGE_AUDIT_LOG, FILE_STORAGE_INFO};
+use storage_dir::*;
+use storage_error::Error;
+use storage_file_backend::*;
 use storage_item::*;
-use storage_packet::*;
+use storage_mem_backend::MemBackend;
+use storage_persistence::StoragePersistence;
+use storage_timeseries::*;
+use storage_watch::*;
 
 macro_rules! take_guard {
     ($g:expr) => {
         match $g {
             Ok(guard) => guard,
-            Err(_) => {
-                // poisoned, log and terminate
-                let err = format!("{} is poisoned", stringify!($g));
-                tracing::error!("{}", err);
-                panic!("{}", err);
-                /*
+            Err(poisoned) => {
+                // poisoned by a thread panicking while holding the lock
+                // the protected data is left consistent by the storage operations, so the store keeps functioning
                 let guard = poisoned.into_inner();
-                tracing::warn!(
-                    "{} recovered from poisoning: {:?}",
-                    stringify!($g),
-                    *guard
-                );
+                tracing::warn!("{} recovered from poisoning", stringify!($g));
                 guard
-                */
             }
         }
     };
 }
 
+/// Interval of polling a lock held by another thread until a deadline
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Locks the mutex, giving up at the deadline if one is given
+/// The std mutex has no timed lock, so a held mutex is polled until the deadline
+fn lock_until<T>(mutex: &Mutex<T>, deadline: Option<Instant>) -> Option<MutexGuard<'_, T>> {
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Some(take_guard!(mutex.lock())),
+    };
+    loop {
+        match mutex.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(poisoned)) => {
+                tracing::warn!("lock recovered from poisoning");
+                return Some(poisoned.into_inner());
+            }
+            Err(TryLockError::WouldBlock) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                thread::sleep(LOCK_POLL_INTERVAL.min(deadline - now));
+            }
+        }
+    }
+}
+
+/// Decoded inner object along with the id and version of the item it was decoded from
+type CachedObject = (String, u64, Arc<dyn Any + Send + Sync>);
+
+/// Error of [`Storage::clone_item`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloneError {
+    /// the storage has no item with the source key
+    SourceNotFound(String),
+    /// the storage has an item with the target key already
+    TargetExists(String),
+    /// the target key does not comply with the key policy or the storage is read-only
+    Rejected(String),
+}
+
+impl std::fmt::Display for CloneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CloneError::SourceNotFound(key) => write!(f, "Source item `{}` not found", key),
+            CloneError::TargetExists(key) => write!(f, "Target item `{}` already exists", key),
+            CloneError::Rejected(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CloneError {}
+
+/// Error of [`Storage::cas_item`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CasError {
+    /// the storage has no item with the key
+    NotFound(String),
+    /// the stored item has another version than expected
+    VersionMismatch { expected: u64, actual: u64 },
+    /// the storage is read-only
+    Rejected(String),
+}
+
+impl std::fmt::Display for CasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CasError::NotFound(key) => write!(f, "Item `{}` not found", key),
+            CasError::VersionMismatch { expected, actual } => write!(
+                f,
+                "Version mismatch, expected: {}, actual: {}",
+                expected, actual
+            ),
+            CasError::Rejected(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for CasError {}
+
+/// Callback invoked with the payload size when the high-water mark is crossed
+type HighWaterCallback = Box<dyn Fn(usize) + Send + Sync>;
+
+/// Source of the current time in epoch seconds, stamped on the mutated items
+type Clock = Box<dyn Fn() -> u64 + Send + Sync>;
+
+/// Resolution of a key present in both storages on [`Storage::merge_from`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// the item of the source storage replaces the existing item
+    Overwrite,
+    /// the existing item is kept
+    KeepExisting,
+    /// the item with the higher version is kept, the existing item wins a tie
+    HigherVersionWins,
+}
+
 pub struct Storage {
-    storage_map: Arc<Mutex<StorageMap>>,
-    config: Arc<Config>,
-    instance_lock: File,
+    backend: Mutex<Box<dyn StorageBackend>>,
     global_lock: Mutex<()>,
     global_lock_param: RwLock<Option<ThreadId>>,
     method_lock_sync: Mutex<()>,
-    // saved: bool,
+    object_cache: Mutex<HashMap<(TypeId, String), CachedObject>>,
+    dirty: AtomicBool,
+    flush_lock: Mutex<()>,
+    key_policy: KeyPolicy,
+    flush_every_writes: Option<usize>,
+    writes: AtomicUsize,
+    config: Arc<Config>,
+    watchers: Watchers,
+    change_log: ChangeLog,
+    read_only: bool,
+    len_bytes: AtomicUsize,
+    high_water: Option<(usize, HighWaterCallback)>,
+    audit_log: Option<AuditLog>,
+    slow_lock_warn: Option<Duration>,
+    max_tags: Option<usize>,
+    max_metafields: Option<usize>,
+    clock: Clock,
+    evict_expired_on_load: bool,
+    id_scheme: IdScheme,
+    max_bytes: Option<usize>,
+    ttl_rules: Vec<(String, Duration)>,
 }
 
-type StorageMap = HashMap<String, StorageItem>;
-type StorageInfo = HashMap<String, (String, u64)>;
+/// Payload size of the storage before and after a write
+#[derive(Clone, Copy)]
+struct LenBytesChange {
+    prev: usize,
+    new: usize,
+}
 
 pub struct GlobalLock<'a> {
     storage: &'a Storage,
@@ -69,12 +210,17 @@ impl Drop for GlobalLock<'_> {
 impl GlobalLock<'_> {
     /// Returns an exclusive access to the storage operations
     pub fn lock(storage: &Storage) -> GlobalLock {
-        let guard = take_guard!(storage.global_lock.lock());
+        Self::lock_until(storage, None).expect("lock without a deadline")
+    }
+
+    /// Returns an exclusive access to the storage operations, or `None` if not acquired by the deadline
+    fn lock_until(storage: &Storage, deadline: Option<Instant>) -> Option<GlobalLock> {
+        let guard = lock_until(&storage.global_lock, deadline)?;
         Self::set_global_lock_param(storage, Some(thread::current().id()));
-        GlobalLock {
+        Some(GlobalLock {
             storage,
             guard: Some(guard),
-        }
+        })
     }
 
     /// Unlocks the exclusive access to the storage
@@ -104,263 +250,584 @@ impl Drop for Storage {
 // #[allow(clippy::arc_with_non_send_sync)]
 impl Storage {
     /// Opens a storage and loads persisted data
+    /// Uses the process-wide configuration of [`config::load`], the configuration file is parsed once per process
+    /// A process-owning convenience wrapper, embedders use [`Storage::try_open`] or [`Storage::try_open_with_config`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configuration could not be loaded or the storage could not be opened, see [`Storage::try_open`] for a non-panicking variant.
     pub fn open() -> Self {
         let config = config::load();
         Self::open_with_config(config)
     }
 
+    /// Opens a storage and loads persisted data
+    /// Uses the process-wide configuration of [`config::try_load`]
+    pub fn try_open() -> Result<Self, String> {
+        let config = config::try_load()?;
+        Self::try_open_with_config(config)
+    }
+
     /// Opens a storage with specified configuration and loads persisted data
+    /// The configuration file is never read, embedders pass the configuration they loaded once
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage could not be opened, see [`Storage::try_open_with_config`] for a non-panicking variant.
     pub fn open_with_config(config: Arc<Config>) -> Self {
-        let mut storage = Self::init(config.clone());
-        if let Err(err) = storage.load() {
-            storage.unlock();
-            tracing::error!("{}", err);
-            panic!("{}", err);
+        match Self::try_open_with_config(config) {
+            Ok(storage) => storage,
+            Err(err) => panic!("{}", err),
         }
-        storage
     }
 
-    pub fn sync() {
-        unimplemented!()
+    /// Opens a storage with specified configuration and loads persisted data
+    /// If another instance holds the storage, waits up to `lock_wait` of the storage config for its release
+    /// The storage is opened read-only if `read_only` of the storage config is set
+    pub fn try_open_with_config(config: Arc<Config>) -> Result<Self, String> {
+        let read_only = config
+            .storage
+            .as_ref()
+            .is_some_and(|storage_config| storage_config.read_only);
+        Self::try_open_with_mode(config, read_only)
     }
 
-    /// initialize the storage
-    fn init(config: Arc<Config>) -> Storage {
-        let storage_config = config.storage.as_ref().unwrap();
-        let storage_path = storage_config.data_path.as_path();
+    /// Opens a storage with specified configuration, creating it on first run
+    /// Returns whether the storage was created, i.e. the data path had no storage info yet, e.g. to seed defaults once
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage could not be opened, see [`Storage::try_open_or_create_with_config`] for a non-panicking variant.
+    pub fn open_or_create_with_config(config: Arc<Config>) -> (Self, bool) {
+        match Self::try_open_or_create_with_config(config) {
+            Ok(result) => result,
+            Err(err) => panic!("{}", err),
+        }
+    }
 
-        // create storage_path if not exists
-        if let Err(err) = std::fs::create_dir_all(storage_path) {
-            tracing::error!("{}", err);
-            panic!("{}", err);
-        };
+    /// Opens a storage with specified configuration, creating it on first run
+    /// The storage info of a created storage is persisted at once, so later opens are not taken as a first run
+    pub fn try_open_or_create_with_config(config: Arc<Config>) -> Result<(Self, bool), String> {
+        let storage = Self::try_open_with_config(config)?;
+        // the instance lock is held, no other instance creates the storage info meanwhile
+        let info_path = storage.data_path().join(FILE_STORAGE_INFO);
+        let created = matches!(info_path.try_exists(), Ok(false));
+        if created {
+            storage.flush()?;
+        }
+        Ok((storage, created))
+    }
 
-        // try to lock the local storage for exclusive access
-        // that prevents access to the stored data from other instances to ensure data consistency
-        let lock_filepath = storage_path.join(FILE_STORAGE_LOCK);
-        let instance_lock = match fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(&lock_filepath)
-        {
-            Ok(file) => file,
-            Err(err) => {
-                tracing::error!("{}", err);
-                panic!("{}", err);
-            }
-        };
+    /// Opens a storage with specified configuration read-only and loads persisted data
+    /// Read-only storages of the same data path coexist, mutating methods fail and nothing is flushed on close
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage could not be opened, see [`Storage::try_open_read_only_with_config`] for a non-panicking variant.
+    pub fn open_read_only_with_config(config: Arc<Config>) -> Self {
+        match Self::try_open_read_only_with_config(config) {
+            Ok(storage) => storage,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Opens a storage with specified configuration read-only and loads persisted data
+    /// If another instance holds the storage for exclusive access, waits up to `lock_wait` of the storage config for its release
+    pub fn try_open_read_only_with_config(config: Arc<Config>) -> Result<Self, String> {
+        Self::try_open_with_mode(config, true)
+    }
 
-        let mut lock_try_count = 100;
-        let lock_try_duration =
-            Duration::from_millis((INSTANCE_LOCK_TIMEOUT_MILLISECONDS / lock_try_count) as u64);
+    /// Opens an in-memory storage, e.g. for tests and ephemeral data
+    /// Nothing is read from or written to disk, so `flush` and `load` are no-ops
+    pub fn open_in_memory() -> Self {
+        Self::with_backend(Box::new(MemBackend::new()))
+    }
 
-        while let Err(err) = instance_lock.try_lock_exclusive() {
-            if lock_try_count == 0 {
-                let error_message = format!(
-                    "Could not obtain a lock `{}` to open the local storage! Error Message: {}",
-                    lock_filepath.to_string_lossy(),
-                    err
-                );
-                tracing::error!("{}", error_message);
-                panic!("{}", error_message);
-            }
-            thread::sleep(lock_try_duration);
-            lock_try_count -= 1;
+    fn try_open_with_mode(config: Arc<Config>, read_only: bool) -> Result<Self, String> {
+        let storage_config = get_storage_config(&config)?;
+        let key_policy = storage_config.key_policy.clone();
+        let flush_every_writes = storage_config.flush_every_writes;
+        let slow_lock_warn = storage_config.slow_lock_warn;
+        let (max_tags, max_metafields) = (storage_config.max_tags, storage_config.max_metafields);
+        let evict_expired_on_load = storage_config.evict_expired_on_load;
+        let id_scheme = storage_config.id_scheme;
+        let max_bytes = storage_config.max_bytes;
+        let ttl_rules = storage_config.ttl_rules.clone();
+        let audit_log = if storage_config.audit && !read_only {
+            let filepath = storage_config.data_path.join(FILE_STORAGE_AUDIT_LOG);
+            Some(filepath)
+        } else {
+            None
+        };
+        let mut backend = if read_only {
+            FileBackend::try_open_read_only(config.clone())?
+        } else {
+            FileBackend::try_open(config.clone())?
+        };
+        if let Err(err) = backend.load() {
+            tracing::error!("{}", err);
+            return Err(err.to_string());
         }
+        let mut storage = Self::with_backend(Box::new(backend))
+            .with_key_policy(key_policy)
+            .with_flush_every_writes(flush_every_writes)
+            .with_slow_lock_warn(slow_lock_warn)
+            .with_metadata_limits(max_tags, max_metafields)
+            .with_evict_expired_on_load(evict_expired_on_load)
+            .with_id_scheme(id_scheme)
+            .with_max_bytes(max_bytes)
+            .with_ttl_rules(ttl_rules);
+        storage.config = config;
+        storage.read_only = read_only;
+        storage.evict_expired_after_load()?;
+        if let Some(filepath) = audit_log {
+            storage.audit_log = Some(AuditLog::open(&filepath)?);
+        }
+        Ok(storage)
+    }
 
+    /// Opens a storage on top of the specified backend
+    /// The backend is expected to be loaded already
+    pub fn with_backend(backend: Box<dyn StorageBackend>) -> Self {
+        let len_bytes = backend_len_bytes(backend.as_ref());
         Storage {
-            storage_map: Arc::new(Mutex::new(HashMap::new())),
-            config,
-            instance_lock,
+            backend: Mutex::new(backend),
             global_lock: Mutex::new(()),
             global_lock_param: RwLock::new(None),
             method_lock_sync: Mutex::new(()),
-            // saved: true,
+            object_cache: Mutex::new(HashMap::new()),
+            dirty: AtomicBool::new(false),
+            flush_lock: Mutex::new(()),
+            key_policy: KeyPolicy::default(),
+            flush_every_writes: None,
+            writes: AtomicUsize::new(0),
+            config: Arc::new(Config::default()),
+            watchers: Watchers::default(),
+            change_log: ChangeLog::with_capacity(CHANGE_LOG_CAPACITY),
+            read_only: false,
+            len_bytes: AtomicUsize::new(len_bytes),
+            high_water: None,
+            audit_log: None,
+            slow_lock_warn: None,
+            max_tags: None,
+            max_metafields: None,
+            clock: Box::new(epoch_seconds),
+            evict_expired_on_load: true,
+            id_scheme: IdScheme::default(),
+            max_bytes: None,
+            ttl_rules: vec![],
+        }
+    }
+
+    /// Sets the rules for the keys of inserted items
+    pub fn with_key_policy(mut self, key_policy: KeyPolicy) -> Self {
+        self.key_policy = key_policy;
+        self
+    }
+
+    /// Sets the callback invoked when the payload size of the items reaches the high-water mark
+    /// The callback is invoked once per crossing from below the mark, e.g. to evict or flush items
+    pub fn with_high_water_mark<F>(mut self, mark: usize, callback: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.high_water = Some((mark, Box::new(callback)));
+        self
+    }
+
+    /// Sets the number of writes after which the storage is flushed automatically
+    pub fn with_flush_every_writes(mut self, flush_every_writes: Option<usize>) -> Self {
+        self.flush_every_writes = flush_every_writes;
+        self
+    }
+
+    /// Sets the lock wait duration above which a warning is logged
+    /// The warning names the thread holding the global lock, if any
+    pub fn with_slow_lock_warn(mut self, slow_lock_warn: Option<Duration>) -> Self {
+        self.slow_lock_warn = slow_lock_warn;
+        self
+    }
+
+    /// Sets the maximum number of tags and metafields per item, unbounded if not set
+    pub fn with_metadata_limits(
+        mut self,
+        max_tags: Option<usize>,
+        max_metafields: Option<usize>,
+    ) -> Self {
+        self.max_tags = max_tags;
+        self.max_metafields = max_metafields;
+        self
+    }
+
+    /// Sets the source of the current time in epoch seconds, stamped as `modified_at` on the mutated items
+    pub fn with_clock<F>(mut self, clock: F) -> Self
+    where
+        F: Fn() -> u64 + Send + Sync + 'static,
+    {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Sets whether items already expired are dropped on load and their files removed
+    pub fn with_evict_expired_on_load(mut self, evict_expired_on_load: bool) -> Self {
+        self.evict_expired_on_load = evict_expired_on_load;
+        self
+    }
+
+    /// Sets the scheme of the ids generated by the storage, for new items and the copies of items
+    pub fn with_id_scheme(mut self, id_scheme: IdScheme) -> Self {
+        self.id_scheme = id_scheme;
+        self
+    }
+
+    /// Sets the maximum total payload size in bytes, inserts beyond are rejected, unbounded if not set
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets the time to live of the items inserted without an expiry, by key prefix
+    /// The rule of the longest prefix matching the key applies
+    pub fn with_ttl_rules(mut self, ttl_rules: Vec<(String, Duration)>) -> Self {
+        self.ttl_rules = ttl_rules;
+        self
+    }
+
+    /// Returns a new item with an id of the scheme of the storage
+    /// Returns `None` if the object could not be encoded
+    pub fn new_item<T: bincode::Encode>(
+        &self,
+        key: &str,
+        item_type: ItemType,
+        obj: &T,
+    ) -> Option<StorageItem> {
+        StorageItem::builder(key)
+            .item_type(item_type)
+            .id_scheme(self.id_scheme)
+            .build(obj)
+    }
+
+    /// Returns the path where the storage data is persisted
+    /// The path is empty for a storage opened on a backend without a configuration
+    pub fn data_path(&self) -> &Path {
+        match &self.config.storage {
+            Some(storage_config) => &storage_config.data_path,
+            None => Path::new(""),
         }
     }
 
+    /// Returns the configuration the storage was opened with
+    pub fn config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
+    /// Persists storage data like [`Storage::flush`], for callers not interested in the report
+    /// Returns [`Error::ReadOnly`] if the storage was opened read-only
+    pub fn sync(&self) -> Result<(), Error> {
+        self.flush().map(|_| ())
+    }
+
     /// Loads persisted data into storage
-    pub fn load(&mut self) -> Result<(), String> {
+    /// Never panics, failures are returned as [`Error`]
+    pub fn load(&mut self) -> Result<(), Error> {
         let mut global_lock = self.global_lock();
-        self.clear();
-
-        // load storage info
-        match self.load_storage_info() {
-            Ok(storage_info) => {
-                // load items
-                for (item_id, _) in storage_info.values() {
-                    match self.load_item(item_id.clone()) {
-                        Ok(storage_item) => {
-                            // insert loaded item into storage
-                            self.insert(storage_item)
-                        }
-                        Err(err) => {
-                            tracing::error!("{}", err);
-                            return Err(err);
-                        }
-                    }
+        let result = {
+            let mut backend = self.lock_backend();
+            let result = backend.load();
+            self.len_bytes
+                .store(backend_len_bytes(backend.as_ref()), Ordering::SeqCst);
+            result
+        };
+        let result = result.and_then(|()| {
+            self.dirty.store(false, Ordering::SeqCst);
+            self.evict_expired_after_load()
+        });
+        global_lock.unlock();
+        result
+    }
+
+    /// Removes the items expired by the clock time after a load, if enabled
+    /// The removal is flushed at once, so the files of the expired items are deleted, a read-only storage only drops them from memory
+    fn evict_expired_after_load(&self) -> Result<(), Error> {
+        if !self.evict_expired_on_load {
+            return Ok(());
+        }
+        let now = (self.clock)();
+        let (expired, len_change) = {
+            let mut backend = self.lock_backend();
+            let mut expired = vec![];
+            backend.for_each(&mut |item| {
+                if item.expires_on.is_some_and(|expires_on| expires_on <= now) {
+                    expired.push(item.key.clone());
                 }
+            });
+            let mut removed = 0;
+            for key in &expired {
+                removed += backend.item_len(key).unwrap_or_default();
+                backend.remove(key);
             }
-            Err(err) => {
-                tracing::error!("{}", err);
-            }
+            (expired, self.account_len_bytes(0, removed))
         };
-        global_lock.unlock();
-        Ok(())
+        if expired.is_empty() {
+            return Ok(());
+        }
+        self.notify_high_water(len_change);
+        tracing::debug!("{} expired items evicted on load", expired.len());
+        for key in &expired {
+            self.notify_change(key, ChangeKind::Removed);
+        }
+        if self.read_only {
+            return Ok(());
+        }
+        self.flush().map(|_| ())
     }
 
     /// Persists storage data
-    pub fn flush(&mut self) -> Result<(), String> {
-        let mut global_lock = self.global_lock();
+    /// The storage is locked only while a snapshot of the data is taken, reads proceed while the snapshot is persisted
+    /// Returns a report of the work done
+    pub fn flush(&self) -> Result<FlushReport, Error> {
+        if self.check_writable().is_err() {
+            return Err(Error::ReadOnly);
+        }
 
-        // load locally persisted storage info
-        let persisted_info = match self.load_storage_info() {
-            Ok(objects) => Some(objects),
-            Err(err) => {
-                tracing::error!("{}", err);
-                None
+        // flushes are serialized, so snapshots are persisted in the order taken
+        let flush_guard = take_guard!(self.flush_lock.lock());
+        self.persist_snapshot(flush_guard)
+    }
+
+    /// Persists storage data unless another flush is in progress
+    /// Returns [`Error::FlushInProgress`] instead of waiting for the running flush to finish
+    pub fn try_flush(&self) -> Result<FlushReport, Error> {
+        if self.check_writable().is_err() {
+            return Err(Error::ReadOnly);
+        }
+
+        let flush_guard = match self.flush_lock.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(poisoned)) => {
+                tracing::warn!("self.flush_lock.try_lock() recovered from poisoning");
+                poisoned.into_inner()
             }
+            Err(TryLockError::WouldBlock) => return Err(Error::FlushInProgress),
         };
+        self.persist_snapshot(flush_guard)
+    }
 
-        let mut info_to_persist: StorageInfo = HashMap::new();
-        for key in self.keys() {
-            if let Some(item) = self.get(&key.clone()) {
-                info_to_persist.insert(key, (item.id.clone(), item.version));
-            }
+    /// Takes a snapshot of the storage data and persists it, holding the flush lock until done
+    fn persist_snapshot(&self, flush_guard: MutexGuard<()>) -> Result<FlushReport, Error> {
+        let snapshot = {
+            let backend = self.lock_backend();
+            self.dirty.store(false, Ordering::SeqCst);
+            self.writes.store(0, Ordering::SeqCst);
+            backend.snapshot()
+        };
+
+        let result = snapshot.flush();
+        if result.is_err() {
+            self.set_dirty();
         }
 
-        // persist the storage info
-        if let Err(err) = self.persist_storage_info(&info_to_persist) {
+        drop(flush_guard);
+        result
+    }
+
+    /// Persists storage data only if the storage was modified since the last flush
+    /// Returns the report of the flush, `None` if there was nothing to flush
+    pub fn flush_if_dirty(&self) -> Result<Option<FlushReport>, Error> {
+        if self.is_dirty() && !self.read_only {
+            self.flush().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns whether the storage was modified since the last flush
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::SeqCst)
+    }
+
+    /// Returns whether the storage was opened read-only
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns an error if the storage was opened read-only
+    fn check_writable(&self) -> Result<(), String> {
+        if self.read_only {
+            let err = "The storage is opened read-only!".to_string();
             tracing::error!("{}", err);
             return Err(err);
         }
+        Ok(())
+    }
 
-        // create storage_data_path if not exists
-        let storage_data_path = self.get_storage_data_path();
-        if let Err(err) = std::fs::create_dir_all(&storage_data_path) {
-            tracing::error!("{}", err);
-            return Err(err.to_string());
-        };
+    /// Returns the total payload size in bytes of the stored items
+    /// Changes made through [`Storage::lock`] are not accounted
+    pub fn len_bytes(&self) -> usize {
+        self.len_bytes.load(Ordering::SeqCst)
+    }
 
-        // analyze existing blob files
-        let item_ids: HashSet<_> = info_to_persist
-            .values()
-            .map(|v| v.0.to_ascii_lowercase())
-            .collect();
-        let mut to_remove = vec![];
-        if let Ok(entries) = std::fs::read_dir(&storage_data_path) {
-            for entry in entries.flatten() {
-                if let Ok(file_type) = entry.file_type() {
-                    if FileType::is_file(&file_type) {
-                        let filename = entry.file_name().to_string_lossy().to_ascii_lowercase();
-                        if !item_ids.contains(&filename) {
-                            to_remove.push(entry.path());
-                        }
-                    }
-                }
+    /// Returns the time to live of the rule with the longest prefix matching the key
+    fn ttl_rule(&self, key: &str) -> Option<Duration> {
+        self.ttl_rules
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, ttl)| *ttl)
+    }
+
+    /// Sets the expiry of an item without one by the rule matching its key, if any
+    /// Called by every write creating or replacing an item, so the rules apply whichever way an item is stored
+    fn apply_ttl_rule(&self, storage_item: &mut StorageItem) {
+        if storage_item.expires_on.is_none() {
+            if let Some(ttl) = self.ttl_rule(&storage_item.key) {
+                storage_item.expires_on = Some((self.clock)() + ttl.as_secs());
             }
         }
+    }
 
-        // remove blob files corresponding to removed items
-        for path in to_remove {
-            if let Err(err) = std::fs::remove_file(path) {
-                tracing::error!("Could not remove unused item blob file: {}", err);
-            }
+    /// Returns an error if the payload size change of a write would exceed the maximum of the storage
+    fn check_max_bytes(&self, added: usize, removed: usize) -> Result<(), String> {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return Ok(()),
+        };
+        let len_bytes = self.len_bytes().saturating_sub(removed) + added;
+        if added > removed && len_bytes > max_bytes {
+            let err = format!(
+                "Storage size of {} bytes would exceed the maximum of {} bytes",
+                len_bytes, max_bytes
+            );
+            tracing::error!("{}", err);
+            return Err(err);
         }
+        Ok(())
+    }
 
-        for (item_key, (item_id, item_version)) in info_to_persist {
-            if let Some(item) = self.get(&item_key) {
-                // check if item is replaced or updated
-                let needs_persist = if let Some(prev) = &persisted_info {
-                    if let Some((prev_id, prev_version)) = prev.get(&item.key) {
-                        // need to check the id first as the item can be removed and a new item with the same key is created then
-                        (item_id != *prev_id) || (item_version > *prev_version)
-                    } else {
-                        // new item needs persist
-                        true
-                    }
-                } else {
-                    // initial storage needs persist
-                    true
-                };
+    /// Drops the decoded objects cached for the key
+    /// Called with the backend locked, so a concurrent read does not cache the replaced item
+    fn forget_cached_objects(&self, key: &str) {
+        take_guard!(self.object_cache.lock()).retain(|(_, cached_key), _| cached_key != key);
+    }
 
-                if needs_persist {
-                    if let Err(err) = self.persist_item(&item) {
-                        tracing::error!("{}", err);
-                        return Err(err);
-                    }
-                }
+    /// Accounts the payload size change of a write
+    /// Called with the backend locked, so a concurrent write checks the maximum against the accounted size
+    fn account_len_bytes(&self, added: usize, removed: usize) -> LenBytesChange {
+        if added >= removed {
+            let prev = self.len_bytes.fetch_add(added - removed, Ordering::SeqCst);
+            LenBytesChange {
+                prev,
+                new: prev + (added - removed),
+            }
+        } else {
+            let prev = self.len_bytes.fetch_sub(removed - added, Ordering::SeqCst);
+            LenBytesChange {
+                prev,
+                new: prev - (removed - added),
             }
         }
-        global_lock.unlock();
-        Ok(())
     }
 
-    fn load_storage_info(&self) -> Result<StorageInfo, String> {
-        let storage_config = self.config.storage.as_ref().unwrap();
-        let storage_path = storage_config.data_path.as_path();
-        let filepath = storage_path.join(FILE_STORAGE_INFO);
-        decode_from_file(filepath)
+    /// Invokes the high-water callback if the payload size change crossed the mark
+    /// Called with the backend unlocked, so the callback may access the storage
+    fn notify_high_water(&self, change: LenBytesChange) {
+        if let Some((mark, callback)) = &self.high_water {
+            if change.prev < *mark && change.new >= *mark {
+                callback(change.new);
+            }
+        }
     }
 
-    fn persist_storage_info(&self, storage_info: &StorageInfo) -> Result<(), String> {
-        let storage_config = self.config.storage.as_ref().unwrap();
-        let storage_path = storage_config.data_path.as_path();
-        let filepath = storage_path.join(FILE_STORAGE_INFO);
-        encode_to_file(filepath, storage_info, StroragePacketType::StrorageInfo)
+    /// Records the mutation in the audit log if enabled
+    fn audit(&self, op: AuditOp, key: Option<&str>) {
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(op, key, None);
+        }
     }
 
-    fn get_storage_data_path(&self) -> PathBuf {
-        let storage_config = self.config.storage.as_ref().unwrap();
-        let storage_path = storage_config.data_path.as_path();
-        storage_path.join(DIR_STORAGE_DATA)
+    /// Records the change in the change log and delivers it to the watchers
+    fn notify_change(&self, key: &str, kind: ChangeKind) {
+        self.change_log.record(key, kind);
+        self.watchers.notify(key, kind);
     }
 
-    fn persist_item(&self, item: &StorageItem) -> Result<(), String> {
-        let storage_data_path = self.get_storage_data_path();
-        let filepath = storage_data_path.join(&item.id);
-        encode_to_file(filepath, item, StroragePacketType::StrorageItem)
+    fn set_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
     }
 
-    fn load_item(&self, item_id: String) -> Result<StorageItem, String> {
-        let storage_data_path = self.get_storage_data_path();
-        let filepath = storage_data_path.join(item_id);
-        decode_from_file(filepath)
+    /// Marks the storage as modified and flushes it once `flush_every_writes` writes are reached
+    /// Must be called without holding the backend lock, as the flush takes it
+    fn record_write(&self) {
+        self.set_dirty();
+        if let Some(flush_every_writes) = self.flush_every_writes {
+            let writes = self.writes.fetch_add(1, Ordering::SeqCst) + 1;
+            if writes >= flush_every_writes {
+                match self.flush() {
+                    Ok(report) => tracing::debug!("flushed after {} writes: {:?}", writes, report),
+                    Err(err) => tracing::error!("{}", err),
+                }
+            }
+        }
     }
 
-    /// Unlocks the storage
-    fn unlock(&mut self) {
-        if let Err(err) = self.instance_lock.unlock() {
-            tracing::error!("{}", err);
-        }
+    /// Closes the storage, persisting the data modified since the last flush
+    /// Unlike dropping the storage, the error of the final flush is returned to the caller
+    /// The storage is released in either case, the unpersisted modifications of a failed flush are lost
+    pub fn try_close(self) -> Result<(), Error> {
+        let result = self.flush_if_dirty().map(|_| ());
+        // the failed flush is not retried on drop
+        self.dirty.store(false, Ordering::SeqCst);
+        result
     }
 
     /// Closes the storage
+    /// A storage not modified since it was opened or last flushed is closed without taking a lock or touching any file
     fn close(&mut self) {
-        if let Err(err) = self.flush() {
+        if let Err(err) = self.flush_if_dirty() {
             tracing::error!("{}", err);
         }
-        self.unlock();
     }
 
     /// Returns a guarded lock to access to the storage operations
-    pub fn lock(&self) -> MutexGuard<StorageMap> {
+    /// The storage is considered modified, as the backend can be changed through the guard
+    /// Changes made through the guard are not delivered to the watchers, nor persisted by a read-only storage
+    pub fn lock(&self) -> MutexGuard<Box<dyn StorageBackend>> {
+        self.set_dirty();
+        self.lock_backend()
+    }
+
+    /// Returns a guarded lock like [`Storage::lock`], giving up after the timeout
+    /// Returns `None` if the storage or the global lock held by another thread is not released in time
+    pub fn try_lock_for(&self, timeout: Duration) -> Option<MutexGuard<Box<dyn StorageBackend>>> {
+        let guard = self.lock_backend_until(Some(Instant::now() + timeout))?;
+        self.set_dirty();
+        Some(guard)
+    }
+
+    fn lock_backend(&self) -> MutexGuard<Box<dyn StorageBackend>> {
+        self.lock_backend_until(None)
+            .expect("lock without a deadline")
+    }
+
+    /// Locks the backend, giving up at the deadline if one is given
+    fn lock_backend_until(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Option<MutexGuard<Box<dyn StorageBackend>>> {
+        let wait_started = Instant::now();
+
         // this method needs synchronization as is has a critical execution point not covered by other locks
-        let guard_method_lock = take_guard!(self.method_lock_sync.lock());
+        let guard_method_lock = lock_until(&self.method_lock_sync, deadline)?;
 
         // when global lock used, only the thread that owns the global lock should have access to storage operations
         // other threads need to wait until global lock released
         // (1) making the decision about the need of a global lock
-        let wait_for_global_lock_release = {
+        let global_lock_holder = {
             // RwLockReadGuard needs to drop before obtaining a global lock to avoid deadlocks
             let read_guard = take_guard!(self.global_lock_param.read());
-            if let Some(global_lock_thread_id) = read_guard.to_owned() {
-                global_lock_thread_id != thread::current().id()
-            } else {
-                false
-            }
+            read_guard.filter(|thread_id| *thread_id != thread::current().id())
         };
+        let wait_for_global_lock_release = global_lock_holder.is_some();
 
         // -> this critical execution point protected with `method_lock_sync`
         // there is a moment between (1) making the decision and (2) taking the actual lock phases
@@ -368,10 +835,11 @@ impl Storage {
         let mut option_global_lock = None;
         if wait_for_global_lock_release {
             // (2) taking the global lock
-            option_global_lock = Some(self.global_lock());
+            option_global_lock = Some(GlobalLock::lock_until(self, deadline)?);
         }
 
-        let guard_storage = take_guard!(self.storage_map.lock());
+        let guard_storage = lock_until(&self.backend, deadline)?;
+        self.warn_slow_lock("storage", wait_started, global_lock_holder);
 
         if let Some(mut global_lock) = option_global_lock {
             global_lock.unlock();
@@ -380,680 +848,4289 @@ impl Storage {
         // release the method_lock_sync
         drop(guard_method_lock);
 
-        guard_storage
+        Some(guard_storage)
     }
 
     /// Returns a global lock to exclusive thread access to the storage operations
     pub fn global_lock(&self) -> GlobalLock {
-        GlobalLock::lock(self)
+        let wait_started = Instant::now();
+        let global_lock_holder = *take_guard!(self.global_lock_param.read());
+        let global_lock = GlobalLock::lock(self);
+        self.warn_slow_lock("global", wait_started, global_lock_holder);
+        global_lock
+    }
+
+    /// Returns a global lock like [`Storage::global_lock`], giving up after the timeout
+    /// Returns `None` if the global lock held by another thread is not released in time
+    pub fn try_global_lock_for(&self, timeout: Duration) -> Option<GlobalLock> {
+        GlobalLock::lock_until(self, Some(Instant::now() + timeout))
+    }
+
+    /// Logs a warning if the wait for the lock exceeded the slow lock threshold
+    fn warn_slow_lock(&self, lock_name: &str, wait_started: Instant, holder: Option<ThreadId>) {
+        let slow_lock_warn = match self.slow_lock_warn {
+            Some(slow_lock_warn) => slow_lock_warn,
+            None => return,
+        };
+        let waited = wait_started.elapsed();
+        if waited <= slow_lock_warn {
+            return;
+        }
+        match holder {
+            Some(thread_id) => tracing::warn!(
+                "waited {} ms for the {} lock, global lock held by {:?}",
+                waited.as_millis(),
+                lock_name,
+                thread_id
+            ),
+            None => tracing::warn!(
+                "waited {} ms for the {} lock",
+                waited.as_millis(),
+                lock_name
+            ),
+        }
+    }
+
+    /// Returns whether the current thread owns the global lock
+    fn is_global_lock_owner(&self) -> bool {
+        let read_guard = take_guard!(self.global_lock_param.read());
+        *read_guard == Some(thread::current().id())
     }
 
     /// Inserts an item into the storage
     /// If the storage has an item with the key present, the item will be updated
-    pub fn insert(&self, storage_item: StorageItem) {
-        self.lock().insert(storage_item.key.clone(), storage_item);
+    /// An item without an expiry gets the time to live of the rule matching its key, if any
+    /// Returns an error if the item key does not comply with the key policy, the storage is read-only
+    /// or the payload size would exceed the maximum of the storage
+    pub fn insert(&self, mut storage_item: StorageItem) -> Result<(), String> {
+        self.check_writable()?;
+        storage_item.key = self.normalize_key(&storage_item.key)?;
+        self.check_metadata_limits(&storage_item)?;
+        self.apply_ttl_rule(&mut storage_item);
+        let key = storage_item.key.clone();
+        let added = storage_item.data.len();
+        let len_change = {
+            let mut backend = self.lock_backend();
+            let removed = backend.item_len(&key).unwrap_or_default();
+            self.check_max_bytes(added, removed)?;
+            storage_item.modified_at = (self.clock)();
+            self.forget_cached_objects(&storage_item.key);
+            backend.insert(storage_item);
+            self.account_len_bytes(added, removed)
+        };
+        self.notify_high_water(len_change);
+        self.audit(AuditOp::Insert, Some(&key));
+        self.record_write();
+        self.notify_change(&key, ChangeKind::Set);
+        Ok(())
     }
 
-    /// Updates an item into the storage
-    /// The item will be inserted if the storage does not have an item with the key present
-    pub fn update(&self, storage_item: StorageItem) {
-        self.insert(storage_item);
+    /// Replaces the whole item corresponding to the key, including its metadata and type,
+    /// only if the stored item has the expected version
+    /// The replacement keeps the key and id of the stored item and gets the next version, which is returned
+    /// A replacement exceeding the maximum payload size of the storage is rejected
+    pub fn cas_item(
+        &self,
+        key: &str,
+        expected_version: u64,
+        mut new_item: StorageItem,
+    ) -> Result<u64, CasError> {
+        self.check_writable().map_err(CasError::Rejected)?;
+        self.check_metadata_limits(&new_item)
+            .map_err(CasError::Rejected)?;
+        let key = self.lookup_key(key).into_owned();
+        let (version, len_change) = {
+            let mut backend = self.lock_backend();
+            let stored = match backend.get(&key) {
+                Some(stored) => stored,
+                None => return Err(CasError::NotFound(key)),
+            };
+            if stored.version != expected_version {
+                return Err(CasError::VersionMismatch {
+                    expected: expected_version,
+                    actual: stored.version,
+                });
+            }
+            let added = new_item.data.len();
+            self.check_max_bytes(added, stored.data.len())
+                .map_err(CasError::Rejected)?;
+            new_item.id = stored.id;
+            new_item.key = key.clone();
+            self.apply_ttl_rule(&mut new_item);
+            new_item.version = stored.version + 1;
+            new_item.modified_at = (self.clock)();
+            let version = new_item.version;
+            self.forget_cached_objects(&new_item.key);
+            backend.insert(new_item);
+            (version, self.account_len_bytes(added, stored.data.len()))
+        };
+        self.notify_high_water(len_change);
+        self.audit(AuditOp::Update, Some(&key));
+        self.record_write();
+        self.notify_change(&key, ChangeKind::Set);
+        Ok(version)
     }
 
-    /// Gets an item from the storage corresponding to the key
-    pub fn get(&self, key: &str) -> Option<StorageItem> {
-        self.lock().get(key).cloned()
+    /// Encodes the object into a new item of the type and inserts it, replacing an item with the same key
+    /// Fails if the object could not be encoded or the item could not be inserted
+    pub fn set<T: bincode::Encode>(
+        &self,
+        key: &str,
+        item_type: ItemType,
+        obj: &T,
+    ) -> Result<(), String> {
+        match StorageItem::with_type(key, item_type, obj) {
+            Some(item) => self.insert(item),
+            None => {
+                let err = format!("Could not encode the object of item `{}`", key);
+                tracing::error!("{}", err);
+                Err(err)
+            }
+        }
     }
 
-    /// Removes an item from the storage
-    pub fn remove(&self, key: &str) {
-        self.lock().remove(key);
+    /// Updates an item into the storage
+    /// The item will be inserted if the storage does not have an item with the key present
+    pub fn update(&self, storage_item: StorageItem) -> Result<(), String> {
+        self.insert(storage_item)
     }
 
-    /// Clears the storage, removing all items
-    pub fn clear(&self) {
-        self.lock().clear();
+    /// Copies the item under a new key
+    /// The copy gets a new id, so it is persisted on its own, and starts over with version 0
+    /// Fails if the source item is missing, an item with the target key exists or the copy would exceed the maximum payload size
+    pub fn clone_item(&self, from: &str, to: &str) -> Result<(), CloneError> {
+        self.check_writable().map_err(CloneError::Rejected)?;
+        let from = self.lookup_key(from);
+        let to = self.normalize_key(to).map_err(CloneError::Rejected)?;
+
+        let len_change = {
+            let mut backend = self.lock_backend();
+            if backend.contains_key(&to) {
+                return Err(CloneError::TargetExists(to));
+            }
+            let mut item = match backend.get(&from) {
+                Some(item) => item,
+                None => return Err(CloneError::SourceNotFound(from.into_owned())),
+            };
+            self.check_max_bytes(item.data.len(), 0)
+                .map_err(CloneError::Rejected)?;
+            item.id = new_item_id(self.id_scheme);
+            item.key = to.clone();
+            self.apply_ttl_rule(&mut item);
+            item.version = 0;
+            item.modified_at = (self.clock)();
+            let added = item.data.len();
+            self.forget_cached_objects(&item.key);
+            backend.insert(item);
+            self.account_len_bytes(added, 0)
+        };
+
+        self.notify_high_water(len_change);
+        self.audit(AuditOp::Insert, Some(&to));
+        self.record_write();
+        self.notify_change(&to, ChangeKind::Set);
+        Ok(())
     }
 
-    /// Returns the keys of the stored items
-    pub fn keys(&self) -> Vec<String> {
-        self.lock().keys().cloned().collect()
+    /// Returns a batch of writes applied at once on commit
+    pub fn batch(&self) -> WriteBatch {
+        WriteBatch::new(self)
     }
 
-    /// Returns the inner object of the item corresponding to the key
-    pub fn get_inner_object<T: bincode::Decode>(&self, key: &str) -> Option<T> {
-        if let Some(item) = self.get(key) {
-            let object: Option<T> = item.get_object();
-            return object;
+    /// Applies the operations of a write batch under the global lock and flushes once
+    fn commit_batch(&self, ops: Vec<BatchOp>) -> Result<(), String> {
+        self.check_writable()?;
+        if ops.is_empty() {
+            return Ok(());
         }
-        None
-    }
 
-    /// Updates the inner object of the item corresponding to the key
-    pub fn update_inner_object<T: bincode::Encode>(&self, key: &str, obj: &T) -> bool {
-        let mut guard = self.lock();
-        if let Some(item) = guard.get_mut(key) {
-            item.update_object(obj);
-            return true;
+        // the keys are validated before any operation is applied
+        let mut normalized_ops = Vec::with_capacity(ops.len());
+        for op in ops {
+            normalized_ops.push(match op {
+                BatchOp::Insert(mut storage_item) => {
+                    storage_item.key = self.normalize_key(&storage_item.key)?;
+                    self.check_metadata_limits(&storage_item)?;
+                    self.apply_ttl_rule(&mut storage_item);
+                    BatchOp::Insert(storage_item)
+                }
+                BatchOp::Remove(key) => BatchOp::Remove(self.lookup_key(&key).into_owned()),
+            });
         }
-        false
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{thread, time::Duration};
+        // the global lock is not reentrant, take it only if not owned by the current thread yet
+        let global_lock = if self.is_global_lock_owner() {
+            None
+        } else {
+            Some(self.global_lock())
+        };
 
-    use super::*;
+        let mut changes = Vec::with_capacity(normalized_ops.len());
+        let len_change = {
+            let mut backend = self.lock_backend();
 
-    const THREADS_COUNT: usize = 100;
-    const MAP_ENTRIES_PER_THREAD: usize = 10;
+            // the size change of the whole batch is checked before any operation is applied
+            if self.max_bytes.is_some() {
+                let (batch_added, batch_removed) =
+                    batch_len_change(backend.as_ref(), &normalized_ops);
+                self.check_max_bytes(batch_added, batch_removed)?;
+            }
 
-    #[test]
-    fn storage_open_test() {
-        let storage = Storage::open();
+            let (mut added, mut removed) = (0, 0);
+            for op in normalized_ops {
+                match op {
+                    BatchOp::Insert(mut storage_item) => {
+                        removed += backend.item_len(&storage_item.key).unwrap_or_default();
+                        storage_item.modified_at = (self.clock)();
+                        added += storage_item.data.len();
+                        changes.push((storage_item.key.clone(), ChangeKind::Set));
+                        self.forget_cached_objects(&storage_item.key);
+                        backend.insert(storage_item);
+                    }
+                    BatchOp::Remove(key) => {
+                        if let Some(item_len) = backend.item_len(&key) {
+                            self.forget_cached_objects(&key);
+                            backend.remove(&key);
+                            removed += item_len;
+                            changes.push((key, ChangeKind::Removed));
+                        }
+                    }
+                }
+            }
+            self.account_len_bytes(added, removed)
+        };
 
-        // clean up the storage
-        storage.clear();
-        assert!(storage.keys().is_empty());
+        self.notify_high_water(len_change);
+        self.set_dirty();
+        for (key, kind) in changes {
+            let op = match kind {
+                ChangeKind::Set => AuditOp::Insert,
+                ChangeKind::Removed => AuditOp::Remove,
+            };
+            self.audit(op, Some(&key));
+            self.notify_change(&key, kind);
+        }
+
+        let result = self.flush().map(|_| ()).map_err(String::from);
+        drop(global_lock);
+        result
     }
 
-    #[test]
-    fn storage_insert_test() {
-        let storage = Storage::open();
+    /// Validates and normalizes the key according to the key policy
+    /// Checks the number of tags and metafields of the item against the limits of the storage
+    fn check_metadata_limits(&self, storage_item: &StorageItem) -> Result<(), String> {
+        let tags = storage_item.tags.as_ref().map_or(0, Vec::len);
+        if let Some(max_tags) = self.max_tags {
+            if tags > max_tags {
+                let err = format!(
+                    "Invalid item `{}`. The item has {} tags, the maximum is {}",
+                    storage_item.key, tags, max_tags
+                );
+                tracing::error!("{}", err);
+                return Err(err);
+            }
+        }
 
-        // clean up the storage
-        storage.clear();
+        let metafields = storage_item.metafields.as_ref().map_or(0, HashMap::len);
+        if let Some(max_metafields) = self.max_metafields {
+            if metafields > max_metafields {
+                let err = format!(
+                    "Invalid item `{}`. The item has {} metafields, the maximum is {}",
+                    storage_item.key, metafields, max_metafields
+                );
+                tracing::error!("{}", err);
+                return Err(err);
+            }
+        }
 
-        let key = "my_string1";
-        let my_string = String::from("abc1");
-        let storage_item =
-            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+        Ok(())
+    }
 
-        storage.insert(storage_item);
+    fn normalize_key(&self, key: &str) -> Result<String, String> {
+        let key_policy = &self.key_policy;
+        let key = self.lookup_key(key);
 
-        let keys = storage.keys();
-        assert_eq!(keys.len(), 1);
-        assert_eq!(keys[0], key);
+        if let Some(max_key_len) = key_policy.max_key_len {
+            if key.len() > max_key_len {
+                let err = format!(
+                    "Invalid key `{}`. The key exceeds the maximum length of {} bytes",
+                    key, max_key_len
+                );
+                tracing::error!("{}", err);
+                return Err(err);
+            }
+        }
 
-        // clean up the storage
-        storage.clear();
+        if let Some(allowed_pattern) = &key_policy.allowed_pattern {
+            if !allowed_pattern.is_match(&key) {
+                let err = format!(
+                    "Invalid key `{}`. The key does not match the allowed pattern `{}`",
+                    key, allowed_pattern
+                );
+                tracing::error!("{}", err);
+                return Err(err);
+            }
+        }
+
+        Ok(key.into_owned())
     }
 
-    #[test]
-    fn storage_update_test() {
-        let storage = Storage::open();
+    /// Returns the key to look up an item according to the key policy
+    fn lookup_key<'a>(&self, key: &'a str) -> Cow<'a, str> {
+        if self.key_policy.lowercase {
+            Cow::Owned(key.to_lowercase())
+        } else {
+            Cow::Borrowed(key)
+        }
+    }
 
-        // clean up the storage
-        storage.clear();
+    /// Gets an item from the storage corresponding to the key
+    pub fn get(&self, key: &str) -> Option<StorageItem> {
+        self.lock_backend().get(&self.lookup_key(key))
+    }
 
-        let key = "my_string2";
-        let my_string = String::from("abc2");
-        let mut storage_item =
-            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
-        storage_item.description = Some("abc".to_string());
+    /// Returns whether the storage has an item with the key present
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.lock_backend().contains_key(&self.lookup_key(key))
+    }
 
-        storage.insert(storage_item);
+    /// Removes an item from the storage
+    /// Returns whether the storage had an item with the key present, a read-only storage does not remove the item
+    pub fn remove(&self, key: &str) -> bool {
+        if self.check_writable().is_err() {
+            return false;
+        }
+        let key = self.lookup_key(key);
+        let len_change = {
+            let mut backend = self.lock_backend();
+            let removed_len = backend.item_len(&key);
+            self.forget_cached_objects(&key);
+            backend.remove(&key);
+            removed_len.map(|removed_len| self.account_len_bytes(0, removed_len))
+        };
+        let removed = len_change.is_some();
+        if let Some(len_change) = len_change {
+            self.notify_high_water(len_change);
+            self.audit(AuditOp::Remove, Some(&key));
+            self.record_write();
+            self.notify_change(&key, ChangeKind::Removed);
+        }
+        removed
+    }
 
-        assert_eq!(storage.keys().len(), 1);
-        let mut item = storage.get(key).unwrap();
-        assert_eq!(item.description, Some("abc".to_string()));
+    /// Clears the storage, removing all items
+    /// A read-only storage is not cleared
+    pub fn clear(&self) {
+        if self.check_writable().is_err() {
+            return;
+        }
+        take_guard!(self.object_cache.lock()).clear();
+        let removed_keys = {
+            let mut backend = self.lock_backend();
+            let keys = backend.keys();
+            backend.clear();
+            self.len_bytes.store(0, Ordering::SeqCst);
+            keys
+        };
+        self.audit(AuditOp::Clear, None);
+        self.record_write();
+        for key in removed_keys {
+            self.notify_change(&key, ChangeKind::Removed);
+        }
+    }
 
-        item.description = Some("abcd".to_string());
-        storage.update(item);
-        assert_eq!(
-            storage.get(key).unwrap().description,
-            Some("abcd".to_string())
-        );
+    /// Reserves capacity for at least `additional` more items, e.g. ahead of a bulk import
+    /// Avoids repeated rehashing while the items are inserted, the stored items are not affected
+    pub fn reserve(&self, additional: usize) {
+        self.lock_backend().reserve(additional);
+    }
 
-        // clean up the storage
-        storage.clear();
+    /// Takes all items out of the storage and leaves it empty
+    /// Reading and clearing happen under one lock, no item can be inserted in between
+    /// Returns an empty map if the storage is read-only
+    pub fn drain(&self) -> HashMap<String, StorageItem> {
+        if self.check_writable().is_err() {
+            return HashMap::new();
+        }
+        take_guard!(self.object_cache.lock()).clear();
+        let items = {
+            let mut backend = self.lock_backend();
+            let items = backend
+                .keys()
+                .into_iter()
+                .filter_map(|key| backend.get(&key).map(|item| (key, item)))
+                .collect::<HashMap<_, _>>();
+            backend.clear();
+            self.len_bytes.store(0, Ordering::SeqCst);
+            items
+        };
+        self.audit(AuditOp::Clear, None);
+        self.record_write();
+        for key in items.keys() {
+            self.notify_change(key, ChangeKind::Removed);
+        }
+        items
     }
 
-    #[test]
-    fn storage_remove_test() {
-        let storage = Storage::open();
+    /// Returns a receiver of the changes made to the item corresponding to the key
+    /// Removals are delivered once the storage lock is released, also for each item of a `clear`
+    /// The subscription ends when the receiver is dropped
+    pub fn watch(&self, key: &str) -> Receiver<ChangeEvent> {
+        self.watchers.subscribe_key(&self.lookup_key(key))
+    }
 
-        // clean up the storage
-        storage.clear();
+    /// Returns a receiver of the changes made to the items with a key starting with the prefix
+    /// The subscription ends when the receiver is dropped
+    pub fn watch_prefix(&self, prefix: &str) -> Receiver<ChangeEvent> {
+        self.watchers.subscribe(prefix)
+    }
 
-        let key = "my_string3";
-        let my_string = String::from("abc3");
-        let storage_item =
-            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+    /// Returns an async receiver of the changes made to all items, e.g. to push them from an async service
+    /// A receiver falling behind by more than `CHANGE_BROADCAST_CAPACITY` changes misses the oldest ones
+    /// and gets `RecvError::Lagged` first, the subscription ends when the receiver is dropped
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<ChangeEvent> {
+        self.watchers.subscribe_async()
+    }
 
-        storage.insert(storage_item);
+    /// Returns a receiver of the changes made to the item corresponding to the key,
+    /// rapid successive changes are collapsed into a single event once the debounce window passes quietly
+    /// The subscription ends when the receiver is dropped
+    pub fn watch_coalesced(&self, key: &str, debounce: Duration) -> Receiver<ChangeEvent> {
+        let key = self.lookup_key(key).into_owned();
+        let receiver = self.watchers.subscribe_key(&key);
+        coalesce(receiver, key, debounce)
+    }
 
-        let keys = storage.keys();
-        assert_eq!(keys.len(), 1);
-        assert_eq!(keys[0], key);
+    /// Returns the sequence number of the latest change, zero if nothing changed yet
+    /// The sequence number is bumped on every change and starts over when the storage is opened
+    pub fn change_seq(&self) -> u64 {
+        self.change_log.seq()
+    }
 
-        storage.remove(key);
-        assert!(storage.keys().is_empty());
+    /// Returns the changes made after the sequence number and the current sequence number
+    /// Only the latest changes are kept, if the first returned change does not follow `seq` directly, older changes were dropped
+    pub fn changes_since(&self, seq: u64) -> (Vec<ChangeRecord>, u64) {
+        self.change_log.changes_since(seq)
     }
 
-    #[test]
-    fn storage_clear_test() {
-        let storage = Storage::open();
+    /// Regenerates the storage info from the item files of the data path, e.g. if the storage info is lost
+    /// Recovered items missing in the storage are added like a batch of inserts, checked against the maximum size,
+    /// recorded in the audit log and delivered to the watchers, item files that do not decode are quarantined
+    /// Returns the number of recovered items
+    pub fn rebuild_info(&self) -> Result<usize, String> {
+        self.check_writable()?;
+        let storage_config = get_storage_config(&self.config)?;
+
+        // a flush in between would persist the storage info without the recovered items and remove their files
+        let flush_guard = take_guard!(self.flush_lock.lock());
+        let items = rebuild_storage_info(&storage_config.data_path, storage_config.temp_path())?;
+        let recovered = items.len();
+        let (added_keys, len_change) = {
+            let mut backend = self.lock_backend();
+            let items = items
+                .into_iter()
+                .filter(|item| !backend.contains_key(&item.key))
+                .collect::<Vec<_>>();
+            let added = items.iter().map(|item| item.data.len()).sum();
+            self.check_max_bytes(added, 0)?;
+            let mut added_keys = Vec::with_capacity(items.len());
+            for item in items {
+                added_keys.push(item.key.clone());
+                self.forget_cached_objects(&item.key);
+                backend.insert(item);
+            }
+            (added_keys, self.account_len_bytes(added, 0))
+        };
+        drop(flush_guard);
 
-        // clean up the storage
-        storage.clear();
+        self.notify_high_water(len_change);
+        if !added_keys.is_empty() {
+            self.set_dirty();
+        }
+        for key in added_keys {
+            self.audit(AuditOp::Insert, Some(&key));
+            self.notify_change(&key, ChangeKind::Set);
+        }
+        Ok(recovered)
+    }
 
-        let key = "my_string4";
-        let my_string = String::from("abc4");
-        let storage_item =
-            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+    /// Returns the keys of the stored items
+    pub fn keys(&self) -> Vec<String> {
+        self.lock_backend().keys()
+    }
 
-        storage.insert(storage_item);
+    /// Returns the keys of the stored items in lexicographic order
+    /// Unlike [`Storage::keys`] the order does not depend on the backend, e.g. for reproducible listings
+    pub fn keys_sorted(&self) -> Vec<String> {
+        let mut keys = self.keys();
+        keys.sort_unstable();
+        keys
+    }
 
-        let keys = storage.keys();
-        assert_eq!(keys.len(), 1);
-        assert_eq!(keys[0], key);
+    /// Returns the version of the item corresponding to the key as persisted on disk by the last flush
+    /// Compared with the version of [`Storage::get`] it tells whether the latest write of the item is flushed
+    /// Returns `None` if the item is not persisted or the storage is not persisted on disk
+    pub fn persisted_version(&self, key: &str) -> Option<u64> {
+        let storage_config = self.config.storage.as_ref()?;
+        match persisted_item_version(&storage_config.data_path, &self.lookup_key(key)) {
+            Ok(version) => version,
+            Err(err) => {
+                tracing::error!("{}", err);
+                None
+            }
+        }
+    }
 
-        storage.clear();
-        assert!(storage.keys().is_empty());
+    /// Returns the keys of the items with an item type matching the predicate
+    /// The storage is locked once for the scan
+    pub fn keys_by_type<F>(&self, predicate: F) -> Vec<String>
+    where
+        F: Fn(&ItemType) -> bool,
+    {
+        let mut keys = vec![];
+        self.lock_backend().for_each(&mut |item| {
+            if predicate(&item.item_type) {
+                keys.push(item.key.clone());
+            }
+        });
+        keys
     }
 
-    #[test]
-    fn storage_object_test() {
-        let storage = Storage::open();
+    /// Returns copies of the items matching the predicate
+    /// The storage is locked once for the scan, the predicate must not access the storage
+    pub fn filter_items<F>(&self, predicate: F) -> Vec<StorageItem>
+    where
+        F: Fn(&StorageItem) -> bool,
+    {
+        let mut items = vec![];
+        self.lock_backend().for_each(&mut |item| {
+            if predicate(item) {
+                items.push(item.clone());
+            }
+        });
+        items
+    }
 
-        // clean up the storage
-        storage.clear();
+    /// Returns the keys of the items modified after the timestamp in epoch seconds
+    /// The storage is locked once for the scan
+    pub fn keys_modified_since(&self, ts: u64) -> Vec<String> {
+        let mut keys = vec![];
+        self.lock_backend().for_each(&mut |item| {
+            if item.modified_at > ts {
+                keys.push(item.key.clone());
+            }
+        });
+        keys
+    }
 
-        let key = "my_map1";
+    /// Returns a page of the sorted keys and the total number of keys
+    /// Sorting keeps the pages stable across calls as long as the storage is not modified
+    pub fn keys_paginated(&self, offset: usize, limit: usize) -> (Vec<String>, usize) {
+        let keys = self.keys_sorted();
+        let total = keys.len();
+        let page = keys.into_iter().skip(offset).take(limit).collect();
+        (page, total)
+    }
 
-        let mut my_map1 = HashMap::<String, String>::new();
-        my_map1.insert("1".into(), "One".into());
-        my_map1.insert("2".into(), "Two".into());
-        my_map1.insert("3".into(), "Three".into());
+    /// Writes a CSV report of the stored items ordered by key
+    /// Columns: `key,item_type,version,bytes,tags,expires_on`, tags are separated by `;`
+    pub fn report<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let items: Vec<StorageItem> = {
+            let backend = self.lock_backend();
+            let mut keys = backend.keys();
+            keys.sort();
+            keys.iter().filter_map(|key| backend.get(key)).collect()
+        };
 
-        let storage_type =
-            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
-        let storage_item = StorageItem::with_type(key, storage_type, &my_map1).unwrap();
+        writeln!(w, "key,item_type,version,bytes,tags,expires_on")?;
+        for item in items {
+            let tags = item.tags.as_deref().unwrap_or_default().join(";");
+            let expires_on = item.expires_on.map(|v| v.to_string()).unwrap_or_default();
+            writeln!(
+                w,
+                "{},{},{},{},{},{}",
+                csv_field(&item.key),
+                csv_field(&format!("{:?}", item.item_type)),
+                item.version,
+                item.data.len(),
+                csv_field(&tags),
+                expires_on
+            )?;
+        }
+        w.flush()
+    }
 
-        storage.insert(storage_item);
+    /// Writes all items into a single-file archive stream ordered by key, e.g. to move the storage as one file
+    /// Returns the number of bytes written
+    pub fn export_archive<W: Write>(&self, mut w: W) -> io::Result<usize> {
+        // the items are encoded under the lock and written to the stream afterwards
+        let mut encoded_items: Vec<(String, Vec<u8>)> = vec![];
+        let mut encode_result = Ok(());
+        self.lock_backend().for_each(&mut |item| {
+            if encode_result.is_ok() {
+                match encode_archive_item(item) {
+                    Ok(encoded_item) => encoded_items.push((item.key.clone(), encoded_item)),
+                    Err(err) => encode_result = Err(err),
+                }
+            }
+        });
+        encode_result?;
+        encoded_items.sort_by(|a, b| a.0.cmp(&b.0));
+        let encoded_items: Vec<Vec<u8>> = encoded_items
+            .into_iter()
+            .map(|(_, encoded_item)| encoded_item)
+            .collect();
+        write_encoded_archive(&mut w, &encoded_items)
+    }
 
-        let decoded_map1: HashMap<String, String> = storage.get_inner_object(key).unwrap();
-        assert_eq!(my_map1, decoded_map1);
+    /// Loads the items of an archive stream written by [`Storage::export_archive`], keeping their ids
+    /// Items with present keys are replaced, nothing is imported if the archive is malformed, truncated or holds an item
+    /// which is invalid or rejected by the storage, e.g. by the key policy or the maximum payload size
+    /// The items are applied at once like a [`WriteBatch`] and flushed, returns the number of imported items
+    pub fn import_archive<R: Read>(&self, mut r: R) -> Result<usize, String> {
+        self.check_writable()?;
+        let items =
+            read_archive(&mut r).map_err(|err| format!("Could not read archive: {}", err))?;
+        for item in &items {
+            if let Err(err) = item.validate() {
+                return Err(format!("Invalid item `{}` in archive: {}", item.key, err));
+            }
+        }
 
-        my_map1.insert("4".into(), "Four".into());
-        assert!(storage.update_inner_object(key, &my_map1));
+        let imported = items.len();
+        self.commit_batch(items.into_iter().map(BatchOp::Insert).collect())?;
+        Ok(imported)
+    }
 
-        let decoded_map2 = storage
-            .get_inner_object::<HashMap<String, String>>(key)
-            .unwrap();
-        assert_eq!(my_map1, decoded_map2);
+    /// Imports the files of the directory tree as blob items keyed by the prefix and their `/`-separated relative path,
+    /// e.g. the file `css/main.css` is imported as `site/css/main.css` with the prefix `site`
+    /// Files stored with the same content are skipped, returns the number of imported files
+    pub fn import_dir(&self, dir: &Path, prefix: &str) -> Result<usize, String> {
+        self.check_writable()?;
+        let files = dir_files(dir)
+            .map_err(|err| format!("Could not read directory {}: {}", dir.display(), err))?;
+        let mut imported = 0;
+        for file in files {
+            if self.import_dir_file(dir, &file, prefix)? {
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    }
 
-        // clean up the storage
-        storage.clear();
+    /// Imports the directory tree like [`Storage::import_dir`] and keeps watching it,
+    /// re-importing the files changed on disk and removing the keys of the files removed on disk
+    /// Watching stops when the returned watcher or the storage is dropped
+    pub fn watch_dir(self: &Arc<Self>, dir: &Path, prefix: &str) -> Result<DirWatcher, String> {
+        let dir = dir
+            .canonicalize()
+            .map_err(|err| format!("Could not resolve directory {}: {}", dir.display(), err))?;
+        self.import_dir(&dir, prefix)?;
+
+        // the watcher does not keep the storage alive
+        let storage = Arc::downgrade(self);
+        let watched_dir = dir.clone();
+        let watched_prefix = prefix.to_string();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => {
+                        tracing::error!("Watch of {} failed: {}", watched_dir.display(), err);
+                        return;
+                    }
+                };
+                if event.kind.is_access() {
+                    return;
+                }
+                if let Some(storage) = storage.upgrade() {
+                    for path in &event.paths {
+                        if let Err(err) = storage.sync_dir_path(&watched_dir, path, &watched_prefix)
+                        {
+                            tracing::error!("{}", err);
+                        }
+                    }
+                }
+            })
+            .map_err(|err| format!("Could not create directory watcher: {}", err))?;
+        watcher
+            .watch(&dir, notify::RecursiveMode::Recursive)
+            .map_err(|err| format!("Could not watch directory {}: {}", dir.display(), err))?;
+
+        Ok(DirWatcher {
+            dir,
+            prefix: prefix.to_string(),
+            _watcher: watcher,
+        })
     }
 
-    #[test]
-    fn multithread_map_insert_test() {
-        let key = "my_map";
-        let storage = Arc::new(Storage::open());
+    /// Brings the key of the path within the imported directory in line with the disk
+    fn sync_dir_path(&self, dir: &Path, path: &Path, prefix: &str) -> Result<(), String> {
+        let key = match dir_item_key(dir, path, prefix) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        if path.is_file() {
+            self.import_dir_file(dir, path, prefix)?;
+        } else if path.is_dir() {
+            self.import_dir(path, &key)?;
+        } else {
+            // a removed directory removes the keys of its files
+            let nested = format!("{}/", key);
+            self.remove(&key);
+            for nested_key in self.keys() {
+                if nested_key.starts_with(&nested) {
+                    self.remove(&nested_key);
+                }
+            }
+        }
+        Ok(())
+    }
 
-        // clean up the storage
-        storage.clear();
+    /// Imports the file of the directory unless it is stored with the same content
+    /// Returns whether the file was imported
+    fn import_dir_file(&self, dir: &Path, file: &Path, prefix: &str) -> Result<bool, String> {
+        let key = dir_item_key(dir, file, prefix)
+            .ok_or_else(|| format!("Could not derive a key of {}", file.display()))?;
+        let content =
+            fs::read(file).map_err(|err| format!("Could not read {}: {}", file.display(), err))?;
+        if let Some(item) = self.get(&key) {
+            if matches!(item.item_type, ItemType::Complex(ComplexType::Blob))
+                && item.get_object::<Vec<u8>>().as_ref() == Some(&content)
+            {
+                return Ok(false);
+            }
+        }
+        match self.new_item(&key, ItemType::Complex(ComplexType::Blob), &content) {
+            Some(item) => self.insert(item)?,
+            None => {
+                return Err(format!(
+                    "Could not encode the content of {}",
+                    file.display()
+                ))
+            }
+        }
+        Ok(true)
+    }
 
-        // create a new map and insert into storage
-        let my_map = HashMap::<String, String>::new();
+    /// Copies all items of the other storage into this storage
+    /// Keys present in both storages are resolved according to the conflict policy
+    /// Item ids are kept if `preserve_ids` is set, otherwise the copied items get new ids
+    /// The conflicts are resolved and the items checked before the merged items are applied at once like a [`WriteBatch`],
+    /// so nothing is merged if an item is rejected, returns the number of copied items
+    pub fn merge_from(
+        &self,
+        other: &Storage,
+        conflict: ConflictPolicy,
+        preserve_ids: bool,
+    ) -> Result<usize, String> {
+        self.check_writable()?;
+        if std::ptr::eq(self, other) {
+            return Ok(0);
+        }
 
-        let storage_type =
-            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
-        let storage_item = StorageItem::with_type(key, storage_type, &my_map).unwrap();
+        // the source items are collected first, so both storages are never locked at once
+        let mut items: Vec<StorageItem> = vec![];
+        other
+            .lock_backend()
+            .for_each(&mut |item| items.push(item.clone()));
+        for item in &mut items {
+            item.key = self.normalize_key(&item.key)?;
+        }
 
-        storage.insert(storage_item);
+        // the global lock is not reentrant, take it only if not owned by the current thread yet
+        let global_lock = if self.is_global_lock_owner() {
+            None
+        } else {
+            Some(self.global_lock())
+        };
 
-        // inserting map entires in multiple threads
-        let mut threads = Vec::with_capacity(THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let storage_clone = storage.clone();
-            let entries_count = MAP_ENTRIES_PER_THREAD;
-            let handler = thread::spawn(move || {
-                let mut global_lock = storage_clone.global_lock();
-                let mut map: HashMap<String, String> = storage_clone.get_inner_object(key).unwrap();
-                for entry_number in 0..entries_count {
-                    let entry_key = format!("{}-{}", thread_number, entry_number);
-                    let entry_value = format!("{}", thread_number * entry_number);
-                    map.insert(entry_key, entry_value);
+        let merged_items: Vec<StorageItem> = {
+            // the versions of the conflicting items are read in one scan, without copying them
+            let source_keys: HashSet<&str> = items.iter().map(|item| item.key.as_str()).collect();
+            let mut existing_versions = HashMap::new();
+            self.lock_backend().for_each(&mut |existing| {
+                if source_keys.contains(existing.key.as_str()) {
+                    existing_versions.insert(existing.key.clone(), existing.version);
                 }
-                storage_clone.update_inner_object(key, &map);
-                global_lock.unlock();
-                thread::sleep(Duration::from_millis(1));
             });
-            threads.push(handler);
+            items
+                .into_iter()
+                .filter(|item| {
+                    let existing_version = match existing_versions.get(&item.key) {
+                        Some(existing_version) => *existing_version,
+                        None => return true,
+                    };
+                    match conflict {
+                        ConflictPolicy::Overwrite => true,
+                        ConflictPolicy::KeepExisting => false,
+                        ConflictPolicy::HigherVersionWins => existing_version < item.version,
+                    }
+                })
+                .collect()
+        };
+
+        let merged = merged_items.len();
+        let ops = merged_items
+            .into_iter()
+            .map(|mut item| {
+                if !preserve_ids {
+                    item.id = new_item_id(self.id_scheme);
+                }
+                BatchOp::Insert(item)
+            })
+            .collect();
+        let result = self.commit_batch(ops);
+
+        drop(global_lock);
+        result.map(|()| merged)
+    }
+
+    /// Returns the inner object of the item corresponding to the key
+    pub fn get_inner_object<T: bincode::Decode>(&self, key: &str) -> Option<T> {
+        if let Some(item) = self.get(key) {
+            let object: Option<T> = item.get_object();
+            return object;
         }
+        None
+    }
 
-        // wait until the finish of all the spawned threads
-        for handler in threads {
-            handler.join().unwrap();
+    /// Returns the inner object of the item corresponding to the key, `None` if the item is missing
+    /// Unlike [`Storage::get_inner_object`], an item not decodable as `T` is reported as an error
+    pub fn try_get_inner_object<T: bincode::Decode>(
+        &self,
+        key: &str,
+    ) -> Result<Option<T>, CodecError> {
+        match self.get(key) {
+            Some(item) => item.try_get_object().map(Some),
+            None => Ok(None),
         }
+    }
 
-        // verify entries
-        let map = storage
-            .get_inner_object::<HashMap<String, String>>(key)
-            .unwrap();
-        assert_eq!(map.keys().count(), THREADS_COUNT * MAP_ENTRIES_PER_THREAD);
-        for thread_number in 0..THREADS_COUNT {
-            for entry_number in 0..MAP_ENTRIES_PER_THREAD {
-                let entry_key = format!("{}-{}", thread_number, entry_number);
-                let entry_value = format!("{}", thread_number * entry_number);
-                assert_eq!(map.get(&entry_key).unwrap(), &entry_value);
-            }
+    /// Returns the inner object of the item corresponding to the key
+    /// Falls back to the default value if the item is missing or not decodable as `T`
+    pub fn get_or_default<T: bincode::Decode + Default>(&self, key: &str) -> T {
+        match self.get(key) {
+            Some(item) => item.get_object().unwrap_or_else(|| {
+                tracing::warn!(
+                    "Item `{}` could not be decoded, the default value is used",
+                    key
+                );
+                T::default()
+            }),
+            None => T::default(),
         }
+    }
 
-        // clean up the storage
-        storage.clear();
+    /// Returns the inner objects of the items corresponding to the keys
+    /// The storage is locked once, missing items and items not decodable as `T` are omitted
+    pub fn get_many_objects<T: bincode::Decode>(&self, keys: &[String]) -> HashMap<String, T> {
+        let items: Vec<(&String, StorageItem)> = {
+            let backend = self.lock_backend();
+            keys.iter()
+                .filter_map(|key| Some((key, backend.get(&self.lookup_key(key))?)))
+                .collect()
+        };
+        items
+            .into_iter()
+            .filter_map(|(key, item)| Some((key.clone(), item.get_object()?)))
+            .collect()
     }
 
-    #[test]
-    fn multithread_map_get_test() {
-        let storage = Arc::new(Storage::open());
+    /// Returns a shared reference to the inner object of the item corresponding to the key
+    /// Decoded objects are cached per type, repeated reads of an unchanged item are not decoded again
+    /// The cached object is invalidated when the item version changes
+    pub fn get_inner_object_ref<T>(&self, key: &str) -> Option<Arc<T>>
+    where
+        T: bincode::Decode + Send + Sync + 'static,
+    {
+        // the cache is locked before the backend is released, a concurrent write cannot be cached over
+        let (item, mut cache) = {
+            let backend = self.lock_backend();
+            let item = backend.get(&self.lookup_key(key))?;
+            (item, take_guard!(self.object_cache.lock()))
+        };
+        let cache_key = (TypeId::of::<T>(), item.key.clone());
 
-        // clean up the storage
-        storage.clear();
+        if let Some((id, version, object)) = cache.get(&cache_key) {
+            if *id == item.id && *version == item.version {
+                if let Ok(object) = object.clone().downcast::<T>() {
+                    return Some(object);
+                }
+            }
+        }
 
-        // create a new map and insert entries
+        let object: Arc<T> = Arc::new(item.get_object()?);
+        cache.insert(cache_key, (item.id, item.version, object.clone()));
+        Some(object)
+    }
+
+    /// Updates the inner object of the item corresponding to the key
+    /// Returns whether the item was updated, a read-only storage is not updated
+    pub fn update_inner_object<T: bincode::Encode>(&self, key: &str, obj: &T) -> bool {
+        if self.check_writable().is_err() {
+            return false;
+        }
+        let len_change = {
+            let mut guard = self.lock_backend();
+            match guard.get(&self.lookup_key(key)) {
+                Some(mut item) => {
+                    let removed = item.data.len();
+                    item.update_object(obj);
+                    let added = item.data.len();
+                    if self.check_max_bytes(added, removed).is_err() {
+                        return false;
+                    }
+                    item.modified_at = (self.clock)();
+                    self.forget_cached_objects(&item.key);
+                    guard.insert(item);
+                    Some(self.account_len_bytes(added, removed))
+                }
+                None => None,
+            }
+        };
+        match len_change {
+            Some(len_change) => {
+                let key = self.lookup_key(key);
+                self.notify_high_water(len_change);
+                self.audit(AuditOp::Update, Some(&key));
+                self.record_write();
+                self.notify_change(&key, ChangeKind::Set);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Appends a point to the time series item corresponding to the key, the item is created if missing
+    /// Points are kept sorted by timestamp, a point with a timestamp already present goes after the present ones
+    /// Fails if the item is not a time series
+    pub fn ts_append(&self, key: &str, ts: u64, value: f64) -> Result<(), String> {
+        self.check_writable()?;
+        let key = self.normalize_key(key)?;
+        let (len_change, op) = {
+            let mut backend = self.lock_backend();
+            match backend.get(&key) {
+                Some(mut item) => {
+                    let mut points = time_series_points(&item).inspect_err(|err| {
+                        tracing::error!("{}", err);
+                    })?;
+                    insert_point(&mut points, (ts, value));
+                    let removed = item.data.len();
+                    item.update_object(&points);
+                    let added = item.data.len();
+                    self.check_max_bytes(added, removed)?;
+                    item.modified_at = (self.clock)();
+                    self.forget_cached_objects(&item.key);
+                    backend.insert(item);
+                    (self.account_len_bytes(added, removed), AuditOp::Update)
+                }
+                None => {
+                    let item_type = ItemType::Complex(ComplexType::TimeSeries);
+                    let points: Vec<TimeSeriesPoint> = vec![(ts, value)];
+                    let mut item = match StorageItem::with_type(&key, item_type, &points) {
+                        Some(item) => item,
+                        None => return Err(format!("Could not encode time series `{}`", key)),
+                    };
+                    self.apply_ttl_rule(&mut item);
+                    let added = item.data.len();
+                    self.check_max_bytes(added, 0)?;
+                    item.modified_at = (self.clock)();
+                    self.forget_cached_objects(&item.key);
+                    backend.insert(item);
+                    (self.account_len_bytes(added, 0), AuditOp::Insert)
+                }
+            }
+        };
+        self.notify_high_water(len_change);
+        self.audit(op, Some(&key));
+        self.record_write();
+        self.notify_change(&key, ChangeKind::Set);
+        Ok(())
+    }
+
+    /// Returns the points of the time series item corresponding to the key with a timestamp within `from..=to`
+    /// Returns no points if the item is missing or not a time series
+    pub fn ts_range(&self, key: &str, from: u64, to: u64) -> Vec<TimeSeriesPoint> {
+        let item = match self.get(key) {
+            Some(item) => item,
+            None => return vec![],
+        };
+        match time_series_points(&item) {
+            Ok(points) => points_in_range(&points, from, to).to_vec(),
+            Err(err) => {
+                tracing::error!("{}", err);
+                vec![]
+            }
+        }
+    }
+
+    /// Adds the tag to the item corresponding to the key
+    /// Returns whether the storage had an item with the key present, the item data is left untouched
+    /// The tag is rejected if the item already has the maximum number of tags
+    pub fn add_tag(&self, key: &str, tag: &str) -> bool {
+        let max_tags = self.max_tags;
+        self.update_item_metadata(key, |item| {
+            let tags = item.tags.as_ref().map_or(0, Vec::len);
+            if max_tags.is_some_and(|max_tags| tags >= max_tags) {
+                tracing::error!(
+                    "Cannot add the tag `{}` to the item `{}`, the maximum of {} tags is reached",
+                    tag,
+                    item.key,
+                    tags
+                );
+                return false;
+            }
+            item.add_tag(tag);
+            true
+        })
+    }
+
+    /// Removes the tag from the item corresponding to the key
+    /// Returns whether the item had the tag
+    pub fn remove_tag(&self, key: &str, tag: &str) -> bool {
+        self.update_item_metadata(key, |item| item.remove_tag(tag))
+    }
+
+    /// Sets the metafield of the item corresponding to the key
+    /// Returns whether the storage had an item with the key present, the item data is left untouched
+    /// A new metafield is rejected if the item already has the maximum number of metafields
+    pub fn set_metafield(&self, key: &str, name: &str, value: &str) -> bool {
+        let max_metafields = self.max_metafields;
+        self.update_item_metadata(key, |item| {
+            let metafields = item.metafields.as_ref();
+            let exists = metafields.is_some_and(|metafields| metafields.contains_key(name));
+            let count = metafields.map_or(0, HashMap::len);
+            if !exists && max_metafields.is_some_and(|max_metafields| count >= max_metafields) {
+                tracing::error!(
+                    "Cannot add the metafield `{}` to the item `{}`, the maximum of {} metafields is reached",
+                    name,
+                    item.key,
+                    count
+                );
+                return false;
+            }
+            item.add_metafield(name, value);
+            true
+        })
+    }
+
+    /// Sets the description of the item corresponding to the key
+    /// Returns whether the storage had an item with the key present, the item data is left untouched
+    pub fn set_description(&self, key: &str, description: &str) -> bool {
+        self.update_item_metadata(key, |item| {
+            item.set_description(description);
+            true
+        })
+    }
+
+    /// Sets the persistence mode of the item corresponding to the key
+    /// Returns whether the storage had an item with the key present, the item data is left untouched
+    pub fn set_persistence(&self, key: &str, mode: StoragePersistence) -> bool {
+        self.update_item_metadata(key, |item| {
+            item.persistence = mode;
+            true
+        })
+    }
+
+    /// Modifies the metadata of the stored item in place and bumps its version if modified
+    /// The closure returns whether the item was modified, a read-only storage is not modified
+    fn update_item_metadata<F>(&self, key: &str, f: F) -> bool
+    where
+        F: FnOnce(&mut StorageItem) -> bool,
+    {
+        if self.check_writable().is_err() {
+            return false;
+        }
+        let key = self.lookup_key(key);
+        let len_change = {
+            let mut backend = self.lock_backend();
+            match backend.get(&key) {
+                Some(mut item) => {
+                    let removed = item.data.len();
+                    if !f(&mut item) {
+                        return false;
+                    }
+                    let added = item.data.len();
+                    if self.check_max_bytes(added, removed).is_err() {
+                        return false;
+                    }
+                    item.version += 1;
+                    item.modified_at = (self.clock)();
+                    self.forget_cached_objects(&item.key);
+                    backend.insert(item);
+                    Some(self.account_len_bytes(added, removed))
+                }
+                None => None,
+            }
+        };
+        let modified = len_change.is_some();
+        if let Some(len_change) = len_change {
+            self.notify_high_water(len_change);
+            self.audit(AuditOp::Update, Some(&key));
+            self.record_write();
+            self.notify_change(&key, ChangeKind::Set);
+        }
+        modified
+    }
+
+    /// Updates the inner object of the item corresponding to the key in place
+    /// The object is decoded, modified by the closure and encoded back under the global lock
+    /// Returns whether the storage had an item with the key present
+    pub fn update_inner_object_with<T, F>(&self, key: &str, f: F) -> bool
+    where
+        T: bincode::Encode + bincode::Decode,
+        F: FnOnce(&mut T),
+    {
+        // the global lock is not reentrant, take it only if not owned by the current thread yet
+        let global_lock = if self.is_global_lock_owner() {
+            None
+        } else {
+            Some(self.global_lock())
+        };
+
+        let updated = match self.get_inner_object::<T>(key) {
+            Some(mut obj) => {
+                f(&mut obj);
+                self.update_inner_object(key, &obj)
+            }
+            None => false,
+        };
+
+        drop(global_lock);
+        updated
+    }
+}
+
+/// Returns the current time in epoch seconds
+fn epoch_seconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Returns the payload bytes added and removed by applying the batch operations in order
+fn batch_len_change(backend: &dyn StorageBackend, ops: &[BatchOp]) -> (usize, usize) {
+    // sizes of the items written by the batch so far, `None` for the removed ones
+    let mut item_lens: HashMap<&str, Option<usize>> = HashMap::new();
+    let (mut added, mut removed) = (0, 0);
+    for op in ops {
+        let (key, new_len) = match op {
+            BatchOp::Insert(storage_item) => {
+                (storage_item.key.as_str(), Some(storage_item.data.len()))
+            }
+            BatchOp::Remove(key) => (key.as_str(), None),
+        };
+        let old_len = match item_lens.get(key) {
+            Some(item_len) => *item_len,
+            None => backend.item_len(key),
+        };
+        removed += old_len.unwrap_or_default();
+        added += new_len.unwrap_or_default();
+        item_lens.insert(key, new_len);
+    }
+    (added, removed)
+}
+
+/// Returns the total payload size in bytes of the items of the backend
+fn backend_len_bytes(backend: &dyn StorageBackend) -> usize {
+    backend
+        .keys()
+        .iter()
+        .filter_map(|key| backend.item_len(key))
+        .sum()
+}
+
+/// Quotes a CSV field if needed
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf, thread, time::Duration};
+
+    use super::*;
+    use super::{storage_codec::*, storage_const::*};
+    use anor_utils::config::StorageConfig;
+
+    const THREADS_COUNT: usize = 100;
+    const MAP_ENTRIES_PER_THREAD: usize = 10;
+
+    /// subscriber collecting the messages of the events with the level
+    struct LogCollector {
+        level: tracing::Level,
+        messages: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl LogCollector {
+        fn new(level: tracing::Level) -> Self {
+            LogCollector {
+                level,
+                messages: Arc::new(Mutex::new(vec![])),
+            }
+        }
+    }
+
+    struct MessageVisitor<'a>(&'a mut String);
+
+    impl tracing::field::Visit for MessageVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                *self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    impl tracing::Subscriber for LogCollector {
+        fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+            *metadata.level() == self.level
+        }
+        fn new_span(&self, _span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut message = String::new();
+            event.record(&mut MessageVisitor(&mut message));
+            self.messages.lock().unwrap().push(message);
+        }
+        fn enter(&self, _span: &tracing::span::Id) {}
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn storage_open_test() {
+        let storage = Storage::open();
+
+        // clean up the storage
+        storage.clear();
+        assert!(storage.keys().is_empty());
+    }
+
+    #[test]
+    fn storage_insert_test() {
+        let storage = Storage::open();
+
+        // clean up the storage
+        storage.clear();
+
+        let key = "my_string1";
+        let my_string = String::from("abc1");
+        let storage_item =
+            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+
+        storage.insert(storage_item).unwrap();
+
+        let keys = storage.keys();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0], key);
+
+        // clean up the storage
+        storage.clear();
+    }
+
+    #[test]
+    fn storage_update_test() {
+        let storage = Storage::open();
+
+        // clean up the storage
+        storage.clear();
+
+        let key = "my_string2";
+        let my_string = String::from("abc2");
+        let mut storage_item =
+            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+        storage_item.description = Some("abc".to_string());
+
+        storage.insert(storage_item).unwrap();
+
+        assert_eq!(storage.keys().len(), 1);
+        let mut item = storage.get(key).unwrap();
+        assert_eq!(item.description, Some("abc".to_string()));
+
+        item.description = Some("abcd".to_string());
+        storage.update(item).unwrap();
+        assert_eq!(
+            storage.get(key).unwrap().description,
+            Some("abcd".to_string())
+        );
+
+        // clean up the storage
+        storage.clear();
+    }
+
+    #[test]
+    fn storage_remove_test() {
+        let storage = Storage::open();
+
+        // clean up the storage
+        storage.clear();
+
+        let key = "my_string3";
+        let my_string = String::from("abc3");
+        let storage_item =
+            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+
+        storage.insert(storage_item).unwrap();
+
+        let keys = storage.keys();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0], key);
+
+        storage.remove(key);
+        assert!(storage.keys().is_empty());
+    }
+
+    #[test]
+    fn storage_clear_test() {
+        let storage = Storage::open();
+
+        // clean up the storage
+        storage.clear();
+
+        let key = "my_string4";
+        let my_string = String::from("abc4");
+        let storage_item =
+            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+
+        storage.insert(storage_item).unwrap();
+
+        let keys = storage.keys();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0], key);
+
+        storage.clear();
+        assert!(storage.keys().is_empty());
+    }
+
+    #[test]
+    fn time_series_test() {
+        let storage = Storage::open_in_memory();
+        for (ts, value) in [(300, 3.0), (100, 1.0), (500, 5.0), (200, 2.0), (400, 4.0)] {
+            storage.ts_append("cpu", ts, value).unwrap();
+        }
+        let item = storage.get("cpu").unwrap();
+        assert!(matches!(
+            item.item_type,
+            ItemType::Complex(ComplexType::TimeSeries)
+        ));
+        assert_eq!(item.version, 4);
+
+        assert_eq!(
+            storage.ts_range("cpu", 150, 400),
+            vec![(200, 2.0), (300, 3.0), (400, 4.0)]
+        );
+        assert_eq!(storage.ts_range("cpu", 0, u64::MAX).len(), 5);
+        assert!(storage.ts_range("cpu", 600, 700).is_empty());
+        assert!(storage.ts_range("missing", 0, u64::MAX).is_empty());
+
+        // other item types are not time series
+        storage
+            .insert(StorageItem::new("plain", &String::from("abc")).unwrap())
+            .unwrap();
+        assert!(storage.ts_append("plain", 100, 1.0).is_err());
+        assert!(storage.ts_range("plain", 0, u64::MAX).is_empty());
+        assert_eq!(storage.get_inner_object::<String>("plain").unwrap(), "abc");
+    }
+
+    #[test]
+    fn set_test() {
+        struct Unencodable;
+
+        impl bincode::Encode for Unencodable {
+            fn encode<E: bincode::enc::Encoder>(
+                &self,
+                _encoder: &mut E,
+            ) -> Result<(), bincode::error::EncodeError> {
+                Err(bincode::error::EncodeError::Other("not encodable"))
+            }
+        }
+
+        let storage = Storage::open_in_memory();
+        storage
+            .set(
+                "greeting",
+                ItemType::Basic(BasicType::String),
+                &String::from("hello"),
+            )
+            .unwrap();
+        assert_eq!(
+            storage.get_inner_object::<String>("greeting").unwrap(),
+            "hello"
+        );
+        assert!(matches!(
+            storage.get("greeting").unwrap().item_type,
+            ItemType::Basic(BasicType::String)
+        ));
+
+        let result = storage.set("broken", ItemType::Custom, &Unencodable);
+        assert!(result.unwrap_err().contains("`broken`"));
+        assert!(!storage.contains_key("broken"));
+    }
+
+    #[test]
+    fn cas_item_test() {
+        let storage = Storage::open_in_memory();
+        storage
+            .insert(StorageItem::new("doc", &String::from("draft")).unwrap())
+            .unwrap();
+        let fetched = storage.get("doc").unwrap();
+
+        // data, metadata and type are swapped together
+        let mut modified = StorageItem::with_type(
+            "ignored",
+            ItemType::Basic(BasicType::String),
+            &String::from("final"),
+        )
+        .unwrap();
+        modified.set_description("reviewed");
+        let version = storage
+            .cas_item("doc", fetched.version, modified.clone())
+            .unwrap();
+        assert_eq!(version, fetched.version + 1);
+
+        let item = storage.get("doc").unwrap();
+        assert_eq!(item.key, "doc");
+        assert_eq!(item.id, fetched.id);
+        assert_eq!(item.version, version);
+        assert_eq!(item.description.as_deref(), Some("reviewed"));
+        assert!(matches!(item.item_type, ItemType::Basic(BasicType::String)));
+        assert_eq!(storage.get_inner_object::<String>("doc").unwrap(), "final");
+
+        // the fetched version is stale now
+        assert_eq!(
+            storage.cas_item("doc", fetched.version, modified.clone()),
+            Err(CasError::VersionMismatch {
+                expected: fetched.version,
+                actual: version,
+            })
+        );
+        assert_eq!(storage.get("doc").unwrap().version, version);
+
+        assert_eq!(
+            storage.cas_item("missing", 0, modified),
+            Err(CasError::NotFound("missing".into()))
+        );
+    }
+
+    #[test]
+    fn reserve_test() {
+        let storage = Storage::open_in_memory();
+        storage
+            .insert(StorageItem::new("existing", &0_usize).unwrap())
+            .unwrap();
+
+        storage.reserve(1000);
+        assert_eq!(storage.keys().len(), 1);
+        for index in 1..=1000_usize {
+            let key = format!("key_{}", index);
+            storage
+                .insert(StorageItem::new(&key, &index).unwrap())
+                .unwrap();
+        }
+
+        assert_eq!(storage.keys().len(), 1001);
+        assert_eq!(storage.get_inner_object::<usize>("existing"), Some(0));
+        for index in 1..=1000 {
+            let key = format!("key_{}", index);
+            assert_eq!(storage.get_inner_object::<usize>(&key), Some(index));
+        }
+    }
+
+    #[test]
+    fn drain_test() {
+        let storage = Arc::new(Storage::open_in_memory());
+        let inserters = 4;
+        let per_inserter = 250;
+
+        let handlers = (0..inserters)
+            .map(|n| {
+                let storage = storage.clone();
+                thread::spawn(move || {
+                    for i in 0..per_inserter {
+                        let key = format!("drain_{n}_{i}");
+                        storage.insert(StorageItem::new(&key, &i).unwrap()).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let drained = storage.drain();
+        for handler in handlers {
+            handler.join().unwrap();
+        }
+
+        let remaining = storage.keys();
+        assert!(remaining.iter().all(|key| !drained.contains_key(key)));
+        assert_eq!(drained.len() + remaining.len(), inserters * per_inserter);
+        for (key, item) in &drained {
+            assert_eq!(key, &item.key);
+        }
+
+        let drained_again = storage.drain();
+        assert_eq!(drained_again.len(), remaining.len());
+        assert!(storage.keys().is_empty());
+        assert_eq!(storage.len_bytes(), 0);
+    }
+
+    #[test]
+    fn storage_object_test() {
+        let storage = Storage::open();
+
+        // clean up the storage
+        storage.clear();
+
+        let key = "my_map1";
+
+        let mut my_map1 = HashMap::<String, String>::new();
+        my_map1.insert("1".into(), "One".into());
+        my_map1.insert("2".into(), "Two".into());
+        my_map1.insert("3".into(), "Three".into());
+
+        let storage_type =
+            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+        let storage_item = StorageItem::with_type(key, storage_type, &my_map1).unwrap();
+
+        storage.insert(storage_item).unwrap();
+
+        let decoded_map1: HashMap<String, String> = storage.get_inner_object(key).unwrap();
+        assert_eq!(my_map1, decoded_map1);
+
+        my_map1.insert("4".into(), "Four".into());
+        assert!(storage.update_inner_object(key, &my_map1));
+
+        let decoded_map2 = storage
+            .get_inner_object::<HashMap<String, String>>(key)
+            .unwrap();
+        assert_eq!(my_map1, decoded_map2);
+
+        // clean up the storage
+        storage.clear();
+    }
+
+    #[test]
+    fn multithread_map_insert_test() {
+        let key = "my_map";
+        let storage = Arc::new(Storage::open());
+
+        // clean up the storage
+        storage.clear();
+
+        // create a new map and insert into storage
+        let my_map = HashMap::<String, String>::new();
+
+        let storage_type =
+            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+        let storage_item = StorageItem::with_type(key, storage_type, &my_map).unwrap();
+
+        storage.insert(storage_item).unwrap();
+
+        // inserting map entires in multiple threads
+        let mut threads = Vec::with_capacity(THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let storage_clone = storage.clone();
+            let entries_count = MAP_ENTRIES_PER_THREAD;
+            let handler = thread::spawn(move || {
+                let mut global_lock = storage_clone.global_lock();
+                let mut map: HashMap<String, String> = storage_clone.get_inner_object(key).unwrap();
+                for entry_number in 0..entries_count {
+                    let entry_key = format!("{}-{}", thread_number, entry_number);
+                    let entry_value = format!("{}", thread_number * entry_number);
+                    map.insert(entry_key, entry_value);
+                }
+                storage_clone.update_inner_object(key, &map);
+                global_lock.unlock();
+                thread::sleep(Duration::from_millis(1));
+            });
+            threads.push(handler);
+        }
+
+        // wait until the finish of all the spawned threads
+        for handler in threads {
+            handler.join().unwrap();
+        }
+
+        // verify entries
+        let map = storage
+            .get_inner_object::<HashMap<String, String>>(key)
+            .unwrap();
+        assert_eq!(map.keys().count(), THREADS_COUNT * MAP_ENTRIES_PER_THREAD);
+        for thread_number in 0..THREADS_COUNT {
+            for entry_number in 0..MAP_ENTRIES_PER_THREAD {
+                let entry_key = format!("{}-{}", thread_number, entry_number);
+                let entry_value = format!("{}", thread_number * entry_number);
+                assert_eq!(map.get(&entry_key).unwrap(), &entry_value);
+            }
+        }
+
+        // clean up the storage
+        storage.clear();
+    }
+
+    #[test]
+    fn multithread_map_update_with_test() {
+        let key = "my_map_update_with";
+        let storage = Arc::new(Storage::open());
+
+        // clean up the storage
+        storage.clear();
+
+        let storage_type =
+            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+        let storage_item =
+            StorageItem::with_type(key, storage_type, &HashMap::<String, String>::new()).unwrap();
+        storage.insert(storage_item).unwrap();
+
+        // each thread pushes entries into the shared map in place
+        thread::scope(|scope| {
+            for thread_number in 0..THREADS_COUNT {
+                let storage_clone = storage.clone();
+                scope.spawn(move || {
+                    for entry_number in 0..MAP_ENTRIES_PER_THREAD {
+                        let entry_key = format!("{}-{}", thread_number, entry_number);
+                        let entry_value = format!("{}", thread_number * entry_number);
+                        assert!(storage_clone.update_inner_object_with(
+                            key,
+                            |map: &mut HashMap<String, String>| {
+                                map.insert(entry_key, entry_value);
+                            }
+                        ));
+                    }
+                });
+            }
+        });
+
+        // ensure no lost updates
+        let map = storage
+            .get_inner_object::<HashMap<String, String>>(key)
+            .unwrap();
+        assert_eq!(map.len(), THREADS_COUNT * MAP_ENTRIES_PER_THREAD);
+
+        // works when the global lock is already owned by the current thread
+        let global_lock = storage.global_lock();
+        assert!(
+            storage.update_inner_object_with(key, |map: &mut HashMap<String, String>| {
+                map.clear();
+            })
+        );
+        drop(global_lock);
+
+        // missing key
+        assert!(!storage.update_inner_object_with("missing", |_: &mut String| {}));
+
+        // clean up the storage
+        storage.clear();
+    }
+
+    #[test]
+    fn multithread_map_get_test() {
+        let storage = Arc::new(Storage::open());
+
+        // clean up the storage
+        storage.clear();
+
+        // create a new map and insert entries
+        let key = "my_map";
+        let mut my_map = HashMap::<String, String>::new();
+
+        for thread_number in 0..THREADS_COUNT {
+            for entry_number in 0..MAP_ENTRIES_PER_THREAD {
+                let entry_key = format!("{}-{}", thread_number, entry_number);
+                let entry_value = format!("{}", thread_number * entry_number);
+                my_map.insert(entry_key, entry_value);
+            }
+        }
+
+        let storage_type =
+            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+        let storage_item = StorageItem::with_type(key, storage_type, &my_map).unwrap();
+
+        storage.insert(storage_item).unwrap();
+
+        // get map entires in multiple threads
+        let mut threads = Vec::with_capacity(THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let storage_clone = storage.clone();
+            let entries_count = MAP_ENTRIES_PER_THREAD;
+            let handler = thread::spawn(move || {
+                let map: HashMap<String, String> = storage_clone.get_inner_object(key).unwrap();
+                for entry_number in 0..entries_count {
+                    let entry_key = format!("{}-{}", thread_number, entry_number);
+                    let entry_value = format!("{}", thread_number * entry_number);
+                    assert_eq!(map.get(&entry_key).unwrap(), &entry_value);
+                }
+                storage_clone.update_inner_object(key, &map);
+                thread::sleep(Duration::from_millis(1));
+            });
+            threads.push(handler);
+        }
+
+        // wait until the finish of all the spawned threads
+        for handler in threads {
+            handler.join().unwrap();
+        }
+
+        // check entries count
+        let map = storage
+            .get_inner_object::<HashMap<String, String>>(key)
+            .unwrap();
+        assert_eq!(map.keys().count(), THREADS_COUNT * MAP_ENTRIES_PER_THREAD);
+
+        // clean up the storage
+        storage.clear();
+    }
+
+    #[test]
+    fn multithread_map_remove_test() {
+        let storage = Arc::new(Storage::open());
+
+        // clean up the storage
+        storage.clear();
+
+        // create a new map and insert entries
         let key = "my_map";
         let mut my_map = HashMap::<String, String>::new();
 
-        for thread_number in 0..THREADS_COUNT {
-            for entry_number in 0..MAP_ENTRIES_PER_THREAD {
-                let entry_key = format!("{}-{}", thread_number, entry_number);
-                let entry_value = format!("{}", thread_number * entry_number);
-                my_map.insert(entry_key, entry_value);
-            }
-        }
+        for thread_number in 0..THREADS_COUNT {
+            for entry_number in 0..MAP_ENTRIES_PER_THREAD {
+                let entry_key = format!("{}-{}", thread_number, entry_number);
+                let entry_value = format!("{}", thread_number * entry_number);
+                my_map.insert(entry_key, entry_value);
+            }
+        }
+
+        let storage_type =
+            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+        let storage_item = StorageItem::with_type(key, storage_type, &my_map).unwrap();
+
+        storage.insert(storage_item).unwrap();
+
+        // verify and remove map entires in multiple threads
+        let mut threads = Vec::with_capacity(THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let storage_clone = storage.clone();
+            let entries_count = MAP_ENTRIES_PER_THREAD;
+            let handler = thread::spawn(move || {
+                let mut global_lock = storage_clone.global_lock();
+                let mut map: HashMap<String, String> = storage_clone.get_inner_object(key).unwrap();
+                for entry_number in 0..entries_count {
+                    let entry_key = format!("{}-{}", thread_number, entry_number);
+                    let entry_value = format!("{}", thread_number * entry_number);
+                    assert_eq!(map.remove(&entry_key).unwrap(), entry_value);
+                }
+                storage_clone.update_inner_object(key, &map);
+                global_lock.unlock();
+                thread::sleep(Duration::from_millis(1));
+            });
+            threads.push(handler);
+        }
+
+        // wait until the finish of all the spawned threads
+        for handler in threads {
+            handler.join().unwrap();
+        }
+
+        // ensure the map is empty
+        let map = storage
+            .get_inner_object::<HashMap<String, String>>(key)
+            .unwrap();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn multithread_multiobject_test() {
+        let storage = Arc::new(Storage::open());
+
+        // clean up the storage
+        storage.clear();
+
+        let key_prefix = "my_map";
+
+        // creating and inserting map objects in multiple threads
+        let mut threads = Vec::with_capacity(THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let storage_clone = storage.clone();
+            let object_key = format!("{}-{}", key_prefix, thread_number);
+            let handler = thread::spawn(move || {
+                let map = HashMap::<String, String>::new();
+                let storage_type =
+                    ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+                let storage_item = StorageItem::with_type(&object_key, storage_type, &map).unwrap();
+
+                storage_clone.insert(storage_item).unwrap();
+                thread::sleep(Duration::from_millis(1));
+            });
+            threads.push(handler);
+        }
+
+        // wait until the finish of all the spawned threads
+        for handler in threads {
+            handler.join().unwrap();
+        }
+
+        // verify inserted objects
+        let object_keys = storage.keys();
+        assert_eq!(object_keys.len(), THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let object_key = format!("{}-{}", key_prefix, thread_number);
+            assert!(object_keys.contains(&object_key));
+        }
+
+        // inserting map entires in multiple threads
+        let mut threads = Vec::with_capacity(THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let storage_clone = storage.clone();
+            let object_key = format!("{}-{}", key_prefix, thread_number);
+            let entries_count = MAP_ENTRIES_PER_THREAD;
+            let handler = thread::spawn(move || {
+                let mut map: HashMap<String, String> =
+                    storage_clone.get_inner_object(&object_key).unwrap();
+                for entry_number in 0..entries_count {
+                    let entry_key = format!("{}-{}", thread_number, entry_number);
+                    let entry_value = format!("{}", thread_number * entry_number);
+                    map.insert(entry_key, entry_value);
+                }
+                storage_clone.update_inner_object(&object_key, &map);
+                thread::sleep(Duration::from_millis(1));
+            });
+            threads.push(handler);
+        }
+
+        // wait until the finish of all the spawned threads
+        for handler in threads {
+            handler.join().unwrap();
+        }
+
+        // verify and remove map entires in multiple threads
+        let mut threads = Vec::with_capacity(THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let storage_clone = storage.clone();
+            let object_key = format!("{}-{}", key_prefix, thread_number);
+            let entries_count = MAP_ENTRIES_PER_THREAD;
+            let handler = thread::spawn(move || {
+                let mut map: HashMap<String, String> =
+                    storage_clone.get_inner_object(&object_key).unwrap();
+                for entry_number in 0..entries_count {
+                    let entry_key = format!("{}-{}", thread_number, entry_number);
+                    let entry_value = format!("{}", thread_number * entry_number);
+                    assert_eq!(map.remove(&entry_key).unwrap(), entry_value);
+                }
+                storage_clone.update_inner_object(&object_key, &map);
+                thread::sleep(Duration::from_millis(1));
+            });
+            threads.push(handler);
+        }
+
+        // wait until the finish of all the spawned threads
+        for handler in threads {
+            handler.join().unwrap();
+        }
+
+        // verify and remove storage items in multiple threads
+        let mut threads = Vec::with_capacity(THREADS_COUNT);
+        for thread_number in 0..THREADS_COUNT {
+            let storage_clone = storage.clone();
+            let object_key = format!("{}-{}", key_prefix, thread_number);
+            let handler = thread::spawn(move || {
+                let map: HashMap<String, String> =
+                    storage_clone.get_inner_object(&object_key).unwrap();
+                assert!(map.is_empty());
+
+                // remove storage object
+                storage_clone.remove(&object_key);
+                thread::sleep(Duration::from_millis(1));
+            });
+            threads.push(handler);
+        }
+
+        // wait until the finish of all the spawned threads
+        for handler in threads {
+            handler.join().unwrap();
+        }
+
+        // ensure empty storage
+        assert!(storage.keys().is_empty());
+    }
+
+    #[test]
+    fn multithread_scoped_multiobject_test() {
+        let storage = Arc::new(Storage::open());
+
+        // clean up the storage
+        storage.clear();
+
+        let key_prefix = "my_map";
+
+        // create and insert map objects into storage in multiple threads
+        thread::scope(|scope| {
+            for thread_number in 0..THREADS_COUNT {
+                let storage_clone = storage.clone();
+                scope.spawn(move || {
+                    let map = HashMap::<String, String>::new();
+                    let storage_type =
+                        ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+
+                    let object_key = format!("{}-{}", key_prefix, thread_number);
+                    let storage_item =
+                        StorageItem::with_type(&object_key, storage_type, &map).unwrap();
+
+                    storage_clone.insert(storage_item).unwrap();
+                });
+            }
+        });
+
+        // verify inserted objects
+        {
+            let object_keys = storage.keys();
+            assert_eq!(object_keys.len(), THREADS_COUNT);
+            for thread_number in 0..THREADS_COUNT {
+                let object_key = format!("{}-{}", key_prefix, thread_number);
+                assert!(object_keys.contains(&object_key));
+            }
+        }
+
+        // inserting map entires in multiple threads
+        thread::scope(|scope| {
+            for thread_number in 0..THREADS_COUNT {
+                let storage_clone = storage.clone();
+                scope.spawn(move || {
+                    let object_key = format!("{}-{}", key_prefix, thread_number);
+
+                    let mut map: HashMap<String, String> =
+                        storage_clone.get_inner_object(&object_key).unwrap();
+
+                    for entry_number in 0..MAP_ENTRIES_PER_THREAD {
+                        let entry_key = format!("{}-{}", thread_number, entry_number);
+                        let entry_value = format!("{}", thread_number * entry_number);
+                        map.insert(entry_key, entry_value);
+                    }
+
+                    storage_clone.update_inner_object(&object_key, &map);
+                });
+            }
+        });
+
+        // verify and remove map entires in multiple threads
+        thread::scope(|scope| {
+            for thread_number in 0..THREADS_COUNT {
+                let storage_clone = storage.clone();
+                scope.spawn(move || {
+                    let object_key = format!("{}-{}", key_prefix, thread_number);
+                    let mut map: HashMap<String, String> =
+                        storage_clone.get_inner_object(&object_key).unwrap();
+
+                    for entry_number in 0..MAP_ENTRIES_PER_THREAD {
+                        let entry_key = format!("{}-{}", thread_number, entry_number);
+                        let entry_value = format!("{}", thread_number * entry_number);
+                        assert_eq!(map.remove(&entry_key).unwrap(), entry_value);
+                    }
+
+                    storage_clone.update_inner_object(&object_key, &map);
+                });
+            }
+        });
+
+        // verify and remove storage items in multiple threads
+        thread::scope(|scope| {
+            for thread_number in 0..THREADS_COUNT {
+                let storage_clone = storage.clone();
+                scope.spawn(move || {
+                    let object_key = format!("{}-{}", key_prefix, thread_number);
+                    let map: HashMap<String, String> =
+                        storage_clone.get_inner_object(&object_key).unwrap();
+                    assert!(map.is_empty());
+
+                    // remove storage object
+                    storage_clone.remove(&object_key);
+                });
+            }
+        });
+
+        // ensure empty storage
+        assert!(storage.keys().is_empty());
+    }
+
+    #[test]
+    fn storage_flush_load_test() {
+        use std::fs;
+        use std::path::Path;
+
+        let mut storage = Storage::open();
+
+        // clean up the storage
+        storage.clear();
+
+        assert!(storage.flush().is_ok());
+
+        // check the storage info is empty
+        let result = load_storage_info();
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_empty());
+
+        let storage_data_path = storage_path().join(DIR_STORAGE_DATA);
+
+        // check the storage blob directory exists
+        assert!(Path::new(&storage_data_path).exists());
+
+        // check the storage blob directory is empty
+        let paths = fs::read_dir(&storage_data_path).unwrap();
+        assert_eq!(paths.count(), 0);
+
+        let key = "my_map1";
+        let mut my_map1 = HashMap::<String, String>::new();
+        my_map1.insert("1".into(), "One".into());
+        my_map1.insert("2".into(), "Two".into());
+        my_map1.insert("3".into(), "Three".into());
+
+        // insert the map into storage
+        let storage_type =
+            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+        let storage_item = StorageItem::with_type(key, storage_type, &my_map1).unwrap();
+        storage.insert(storage_item).unwrap();
+
+        // persist the storage
+        assert!(storage.flush().is_ok());
+
+        // check the storage info has the map
+        let result = load_storage_info();
+        assert!(result.is_ok());
+
+        let storage_info = result.unwrap();
+        assert!(storage_info.contains_key(key));
+
+        // check the storage blob directory exists
+        assert!(Path::new(&storage_data_path).exists());
+
+        // check the storage blob directory has a single entry
+        let paths = fs::read_dir(&storage_data_path).unwrap();
+        let entries: Vec<_> = paths.flatten().map(|v| v.file_name()).collect();
+        assert_eq!(entries.len(), 1);
+
+        // check the entry id
+        let item_id = storage_info.get(key).unwrap().0.to_ascii_lowercase();
+        assert_eq!(entries[0].to_string_lossy().to_ascii_lowercase(), item_id);
+
+        // clean up the storage
+        storage.clear();
+
+        let object_keys = storage.keys();
+        assert!(object_keys.is_empty());
+
+        // load storage
+        assert!(storage.load().is_ok());
+
+        // verify loaded storage
+        let object_keys = storage.keys();
+        assert_eq!(object_keys.len(), 1);
+        assert_eq!(object_keys[0], key);
+
+        let map: HashMap<String, String> = storage.get_inner_object(key).unwrap();
+        assert_eq!(my_map1, map);
+
+        // clean up the storage
+        storage.clear();
+    }
+
+    #[test]
+    fn mem_backend_test() {
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+        assert!(storage.keys().is_empty());
+
+        // insert
+        let key = "my_string";
+        let my_string = String::from("abc");
+        let mut storage_item =
+            StorageItem::with_type(key, ItemType::Basic(BasicType::String), &my_string).unwrap();
+        storage_item.description = Some("abc".to_string());
+        storage.insert(storage_item).unwrap();
+
+        let keys = storage.keys();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0], key);
+
+        // get & update
+        let mut item = storage.get(key).unwrap();
+        assert_eq!(item.description, Some("abc".to_string()));
+        item.description = Some("abcd".to_string());
+        storage.update(item).unwrap();
+        assert_eq!(
+            storage.get(key).unwrap().description,
+            Some("abcd".to_string())
+        );
+
+        // inner object
+        assert_eq!(storage.get_inner_object::<String>(key).unwrap(), my_string);
+        assert!(storage.update_inner_object(key, &String::from("def")));
+        assert_eq!(storage.get_inner_object::<String>(key).unwrap(), "def");
+
+        // flush and load are no-op
+        assert!(storage.flush().is_ok());
+
+        // remove & clear
+        assert!(storage.remove(key));
+        assert!(!storage.remove(key));
+        assert!(storage.keys().is_empty());
+
+        storage
+            .insert(StorageItem::new(key, &my_string).unwrap())
+            .unwrap();
+        storage.clear();
+        assert!(storage.keys().is_empty());
+    }
+
+    #[test]
+    fn inner_object_ref_cache_test() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DECODE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, PartialEq, bincode::Encode)]
+        struct Counted(u64);
+
+        impl bincode::Decode for Counted {
+            fn decode<D: bincode::de::Decoder>(
+                decoder: &mut D,
+            ) -> Result<Self, bincode::error::DecodeError> {
+                DECODE_COUNT.fetch_add(1, Ordering::SeqCst);
+                Ok(Counted(u64::decode(decoder)?))
+            }
+        }
+
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+        let key = "counted";
+        storage
+            .insert(StorageItem::new(key, &Counted(1)).unwrap())
+            .unwrap();
+
+        // the second read of an unchanged item does not decode again
+        let first = storage.get_inner_object_ref::<Counted>(key).unwrap();
+        let second = storage.get_inner_object_ref::<Counted>(key).unwrap();
+        assert_eq!(*first, Counted(1));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(DECODE_COUNT.load(Ordering::SeqCst), 1);
+
+        // an update invalidates the cached object
+        assert!(storage.update_inner_object(key, &Counted(2)));
+        assert_eq!(
+            *storage.get_inner_object_ref::<Counted>(key).unwrap(),
+            Counted(2)
+        );
+        assert_eq!(DECODE_COUNT.load(Ordering::SeqCst), 2);
+
+        // a replaced item invalidates the cached object
+        storage
+            .insert(StorageItem::new(key, &Counted(3)).unwrap())
+            .unwrap();
+        assert_eq!(
+            *storage.get_inner_object_ref::<Counted>(key).unwrap(),
+            Counted(3)
+        );
+        assert_eq!(DECODE_COUNT.load(Ordering::SeqCst), 3);
+
+        // an item inserted back with the same id and version invalidates the cached object
+        let mut item = storage.get(key).unwrap();
+        item.data = StorageItem::new(key, &Counted(4)).unwrap().data;
+        storage.insert(item).unwrap();
+        assert_eq!(storage.get_inner_object::<Counted>(key), Some(Counted(4)));
+        assert_eq!(
+            *storage.get_inner_object_ref::<Counted>(key).unwrap(),
+            Counted(4)
+        );
+
+        // as does an item committed in a batch, e.g. on import
+        let mut item = storage.get(key).unwrap();
+        item.data = StorageItem::new(key, &Counted(5)).unwrap().data;
+        let mut batch = storage.batch();
+        batch.insert(item);
+        batch.commit().unwrap();
+        assert_eq!(
+            *storage.get_inner_object_ref::<Counted>(key).unwrap(),
+            Counted(5)
+        );
+
+        storage.remove(key);
+        assert!(storage.get_inner_object_ref::<Counted>(key).is_none());
+    }
+
+    #[test]
+    fn load_missing_storage_info_test() {
+        let config = temp_storage_config("load_missing_storage_info");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        // a fresh data path is an empty storage, not an error
+        let subscriber = LogCollector::new(tracing::Level::ERROR);
+        let errors = subscriber.messages.clone();
+        tracing::subscriber::with_default(subscriber, || {
+            let mut storage = Storage::try_open_with_config(config.clone()).unwrap();
+            assert!(storage.keys().is_empty());
+            assert!(storage.load().is_ok());
+        });
+        assert!(errors.lock().unwrap().is_empty());
+
+        // a corrupt storage info is still reported
+        std::fs::write(data_path.join(FILE_STORAGE_INFO), b"corrupt").unwrap();
+        let subscriber = LogCollector::new(tracing::Level::ERROR);
+        let errors = subscriber.messages.clone();
+        tracing::subscriber::with_default(subscriber, || {
+            let storage = Storage::try_open_with_config(config.clone()).unwrap();
+            assert!(storage.keys().is_empty());
+        });
+        assert!(!errors.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn missing_codec_test() {
+        use super::storage_packet::*;
+
+        let config = temp_storage_config("missing_codec");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        let storage = Storage::open_with_config(config.clone());
+        for key in ["kept", "packed"] {
+            storage
+                .insert(StorageItem::new(key, &key.to_string()).unwrap())
+                .unwrap();
+        }
+        storage.flush().unwrap();
+        let item_id = storage.get("packed").unwrap().id;
+        drop(storage);
+
+        // rewrite the item file claiming a codec not enabled in this build
+        let item_path = data_path.join(DIR_STORAGE_DATA).join(&item_id);
+        let packet = build_storage_packet(
+            vec![0xc0],
+            StroragePacketType::StrorageItem,
+            StrorageCodecType::MessagePack,
+        );
+        let mut buf = packet.header.to_vec();
+        buf.extend(&packet.data);
+        std::fs::write(&item_path, buf).unwrap();
+
+        let subscriber = LogCollector::new(tracing::Level::ERROR);
+        let errors = subscriber.messages.clone();
+        let storage = tracing::subscriber::with_default(subscriber, || {
+            Storage::try_open_with_config(config.clone()).unwrap()
+        });
+        assert!(storage.contains_key("kept"));
+        assert!(!storage.contains_key("packed"));
+        assert!(errors.lock().unwrap().iter().any(|err| err.contains(
+            "Item `packed` requires codec MessagePack which is not enabled in this build"
+        )));
+
+        // the item file is kept aside
+        assert!(!item_path.exists());
+        assert!(data_path
+            .join(DIR_STORAGE_QUARANTINE)
+            .join(&item_id)
+            .exists());
+    }
+
+    #[test]
+    fn rebuild_info_test() {
+        let config = temp_storage_config("rebuild_info");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        let storage = Storage::open_with_config(config.clone());
+        for key in ["first", "second", "third"] {
+            storage
+                .insert(StorageItem::new(key, &key.to_string()).unwrap())
+                .unwrap();
+        }
+        storage.flush().unwrap();
+        drop(storage);
+
+        // lose the storage info, the item files survive
+        std::fs::remove_file(data_path.join(FILE_STORAGE_INFO)).unwrap();
+        std::fs::write(data_path.join(DIR_STORAGE_DATA).join("garbage"), b"garbage").unwrap();
+
+        let storage = Storage::open_with_config(config.clone());
+        assert!(storage.keys().is_empty());
+        assert_eq!(storage.rebuild_info(), Ok(3));
+        assert_eq!(storage.keys().len(), 3);
+        assert!(storage.is_dirty());
+        let (changes, _) = storage.changes_since(0);
+        assert_eq!(changes.len(), 3);
+        drop(storage);
+
+        let storage = Storage::open_with_config(config.clone());
+        for key in ["first", "second", "third"] {
+            let value: String = storage.get_inner_object(key).unwrap();
+            assert_eq!(value, key);
+        }
+        assert!(data_path
+            .join(DIR_STORAGE_QUARANTINE)
+            .join("garbage")
+            .exists());
+        drop(storage);
+
+        // recovered items over the maximum size are not added
+        std::fs::remove_file(data_path.join(FILE_STORAGE_INFO)).unwrap();
+        let storage = Storage::open_with_config(config).with_max_bytes(Some(8));
+        assert!(storage.rebuild_info().unwrap_err().contains("maximum"));
+        assert!(storage.keys().is_empty());
+        assert_eq!(storage.len_bytes(), 0);
+
+        assert!(Storage::open_in_memory().rebuild_info().is_err());
+    }
+
+    #[test]
+    fn temp_dir_test() {
+        let data_path = temp_data_path("temp_dir");
+        let temp_dir = data_path.join("staging");
+        let config = Arc::new(Config {
+            storage: Some(StorageConfig {
+                data_path: data_path.clone(),
+                temp_dir: Some(temp_dir.clone()),
+                ..Default::default()
+            }),
+            api: None,
+            http: None,
+            remote: None,
+        });
+
+        // a leftover of an interrupted write
+        let leftover = data_path.join(DIR_STORAGE_DATA).join("leftover.tmp");
+        std::fs::create_dir_all(leftover.parent().unwrap()).unwrap();
+        std::fs::write(&leftover, b"partial").unwrap();
+
+        let storage = Storage::open_with_config(config.clone());
+        assert!(temp_dir.is_dir());
+        for key in ["first", "second"] {
+            storage
+                .insert(StorageItem::new(key, &key.to_string()).unwrap())
+                .unwrap();
+        }
+        let report = storage.flush().unwrap();
+        assert_eq!(report.items_written, 2);
+        assert_eq!(report.files_removed, 0);
+        assert!(leftover.exists());
+        assert_eq!(std::fs::read_dir(&temp_dir).unwrap().count(), 0);
+
+        // writes go through the temp dir
+        std::fs::remove_dir_all(&temp_dir).unwrap();
+        storage
+            .insert(StorageItem::new("third", &"third".to_string()).unwrap())
+            .unwrap();
+        assert!(storage.flush().is_err());
+        std::fs::create_dir_all(&temp_dir).unwrap();
+        storage.flush().unwrap();
+        drop(storage);
+
+        let storage = Storage::open_with_config(config);
+        assert_eq!(storage.keys().len(), 3);
+        assert!(leftover.exists());
+    }
+
+    #[test]
+    fn open_or_create_test() {
+        let config = temp_storage_config("open_or_create");
+
+        // the first run is reported even if nothing is stored
+        let (storage, created) = Storage::open_or_create_with_config(config.clone());
+        assert!(created);
+        assert!(storage.keys().is_empty());
+        drop(storage);
+
+        let (storage, created) = Storage::open_or_create_with_config(config.clone());
+        assert!(!created);
+        storage
+            .insert(StorageItem::new("seed", &String::from("abc")).unwrap())
+            .unwrap();
+        drop(storage);
+
+        let (storage, created) = Storage::open_or_create_with_config(config);
+        assert!(!created);
+        assert!(storage.contains_key("seed"));
+    }
+
+    #[test]
+    fn evict_expired_on_load_test() {
+        let config = temp_storage_config("evict_expired_on_load");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        let now = Arc::new(AtomicUsize::new(1_000));
+        let clock = now.clone();
+        let mut storage = Storage::open_with_config(config.clone())
+            .with_clock(move || clock.load(Ordering::SeqCst) as u64);
+        let expiring = StorageItem::builder("expiring")
+            .expires_on(1_010)
+            .build(&String::from("abc"))
+            .unwrap();
+        let expiring_file = data_path.join(DIR_STORAGE_DATA).join(&expiring.id);
+        storage.insert(expiring).unwrap();
+        storage
+            .insert(StorageItem::new("lasting", &String::from("abc")).unwrap())
+            .unwrap();
+        storage.flush().unwrap();
+
+        // not expired yet
+        storage.load().unwrap();
+        assert!(storage.contains_key("expiring"));
+        assert!(expiring_file.exists());
+
+        now.store(1_010, Ordering::SeqCst);
+        storage.load().unwrap();
+        assert!(!storage.contains_key("expiring"));
+        assert!(storage.contains_key("lasting"));
+        assert!(!expiring_file.exists());
+        drop(storage);
+
+        let storage = Storage::open_with_config(config);
+        assert!(!storage.contains_key("expiring"));
+        assert!(storage.contains_key("lasting"));
+    }
+
+    #[test]
+    fn persisted_version_test() {
+        let config = temp_storage_config("persisted_version");
+
+        let storage = Storage::open_with_config(config);
+        storage
+            .insert(StorageItem::new("my_key", &1_u32).unwrap())
+            .unwrap();
+        assert_eq!(storage.persisted_version("my_key"), None);
+        storage.flush().unwrap();
+        assert_eq!(storage.persisted_version("my_key"), Some(0));
+
+        assert!(storage.update_inner_object("my_key", &2_u32));
+        let version = storage.get("my_key").unwrap().version;
+        assert_eq!(version, 1);
+        assert_eq!(storage.persisted_version("my_key"), Some(0));
+        storage.flush().unwrap();
+        assert_eq!(storage.persisted_version("my_key"), Some(version));
+
+        assert_eq!(storage.persisted_version("missing"), None);
+        assert_eq!(Storage::open_in_memory().persisted_version("my_key"), None);
+    }
+
+    #[test]
+    fn close_unmodified_test() {
+        use std::time::SystemTime;
+
+        let config = temp_storage_config("close_unmodified");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        let storage = Storage::open_with_config(config.clone());
+        for key in ["first", "second"] {
+            storage
+                .insert(StorageItem::new(key, &key.to_string()).unwrap())
+                .unwrap();
+        }
+        drop(storage);
+
+        // modification times of the persisted files, the lock file is rewritten on every open
+        let modified_times = || {
+            let mut files = vec![data_path.join(FILE_STORAGE_INFO)];
+            for entry in std::fs::read_dir(data_path.join(DIR_STORAGE_DATA)).unwrap() {
+                files.push(entry.unwrap().path());
+            }
+            files
+                .into_iter()
+                .map(|path| {
+                    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+                    (path, modified)
+                })
+                .collect::<HashMap<PathBuf, SystemTime>>()
+        };
+        let before = modified_times();
+        assert_eq!(before.len(), 3);
+
+        let storage = Storage::open_with_config(config);
+        assert_eq!(storage.keys().len(), 2);
+        assert!(storage.get("first").is_some());
+        assert!(!storage.is_dirty());
+        thread::sleep(Duration::from_millis(20));
+        drop(storage);
+
+        assert_eq!(modified_times(), before);
+    }
+
+    #[test]
+    fn max_bytes_write_paths_test() {
+        let storage = Storage::open_in_memory().with_max_bytes(Some(64));
+        let blob = |len: usize| {
+            StorageItem::with_type(
+                "blob",
+                ItemType::Complex(ComplexType::Blob),
+                &vec![1_u8; len],
+            )
+            .unwrap()
+        };
+        let mut first = blob(40);
+        first.key = "first".to_string();
+        storage.insert(first).unwrap();
+        let len_bytes = storage.len_bytes();
+
+        // a clone over the maximum is rejected
+        let result = storage.clone_item("first", "copy");
+        assert!(matches!(result, Err(CloneError::Rejected(err)) if err.contains("maximum")));
+        assert!(!storage.contains_key("copy"));
+
+        // a batch over the maximum is rejected as a whole
+        let mut batch = storage.batch();
+        batch.insert(StorageItem::new("small", &1_u8).unwrap());
+        let mut second = blob(40);
+        second.key = "second".to_string();
+        batch.insert(second);
+        assert!(batch.commit().unwrap_err().contains("maximum"));
+        assert!(!storage.contains_key("small"));
+        assert!(!storage.contains_key("second"));
+        assert_eq!(storage.len_bytes(), len_bytes);
+
+        // the removals of the batch are accounted before its inserts
+        let mut batch = storage.batch();
+        batch.remove("first");
+        let mut second = blob(40);
+        second.key = "second".to_string();
+        batch.insert(second);
+        batch.commit().unwrap();
+        assert!(storage.contains_key("second"));
+
+        // replacing and updating objects are checked as well
+        let version = storage.get("second").unwrap().version;
+        let result = storage.cas_item("second", version, blob(80));
+        assert!(matches!(result, Err(CasError::Rejected(err)) if err.contains("maximum")));
+        assert!(!storage.update_inner_object("second", &vec![1_u8; 80]));
+        let mut result = Ok(());
+        for ts in 0..64 {
+            result = storage.ts_append("series", ts, 0.0);
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(result.unwrap_err().contains("maximum"));
+        assert!(storage.len_bytes() <= 64);
+    }
+
+    #[test]
+    fn max_bytes_concurrent_test() {
+        let storage = Arc::new(Storage::open_in_memory().with_max_bytes(Some(1000)));
+        let inserters = 8;
+        let per_inserter = 50;
+
+        let handlers = (0..inserters)
+            .map(|n| {
+                let storage = storage.clone();
+                thread::spawn(move || {
+                    for i in 0..per_inserter {
+                        let key = format!("max_bytes_{n}_{i}");
+                        _ = storage.insert(StorageItem::new(&key, &[1_u8; 8]).unwrap());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handler in handlers {
+            handler.join().unwrap();
+        }
+
+        let stored = storage
+            .keys()
+            .iter()
+            .map(|key| storage.get(key).unwrap().data.len())
+            .sum::<usize>();
+        assert!(storage.len_bytes() <= 1000);
+        assert_eq!(storage.len_bytes(), stored);
+    }
+
+    #[test]
+    fn storage_tuning_test() {
+        let data_path = temp_data_path("storage_tuning");
+        let config = Arc::new(Config {
+            storage: Some(StorageConfig {
+                data_path: data_path.clone(),
+                durable: true,
+                max_bytes: Some(64),
+                ..Default::default()
+            }),
+            api: None,
+            http: None,
+            remote: None,
+        });
+
+        let storage = Storage::open_with_config(config.clone());
+        storage
+            .set(
+                "first",
+                ItemType::Complex(ComplexType::Blob),
+                &vec![1_u8; 40],
+            )
+            .unwrap();
+        let err = storage
+            .set(
+                "second",
+                ItemType::Complex(ComplexType::Blob),
+                &vec![2_u8; 40],
+            )
+            .unwrap_err();
+        assert!(err.contains("maximum of 64 bytes"));
+        assert!(!storage.contains_key("second"));
+
+        // replacing an item accounts the size of the replaced one
+        storage
+            .set(
+                "first",
+                ItemType::Complex(ComplexType::Blob),
+                &vec![3_u8; 60],
+            )
+            .unwrap();
+        storage.remove("first");
+        storage
+            .set(
+                "second",
+                ItemType::Complex(ComplexType::Blob),
+                &vec![2_u8; 40],
+            )
+            .unwrap();
+
+        // a durable flush syncs the written files
+        let report = storage.flush().unwrap();
+        assert_eq!(report.items_written, 1);
+        drop(storage);
+        let storage = Storage::open_with_config(config);
+        assert_eq!(storage.keys(), vec!["second".to_string()]);
+    }
+
+    #[test]
+    fn ttl_rules_test() {
+        let storage = Storage::open_in_memory()
+            .with_clock(|| 1_000)
+            .with_ttl_rules(vec![
+                ("session:".to_string(), Duration::from_secs(30 * 60)),
+                ("session:admin:".to_string(), Duration::from_secs(5 * 60)),
+            ]);
+        let insert = |key: &str| {
+            storage
+                .insert(StorageItem::new(key, &String::from("abc")).unwrap())
+                .unwrap();
+            storage.get(key).unwrap().expires_on
+        };
+
+        assert_eq!(insert("session:abc"), Some(1_000 + 30 * 60));
+        // the longest matching prefix wins
+        assert_eq!(insert("session:admin:abc"), Some(1_000 + 5 * 60));
+        assert_eq!(insert("user:abc"), None);
+
+        // an explicit expiry is kept
+        let item = StorageItem::builder("session:def")
+            .expires_on(5_000)
+            .build(&String::from("abc"))
+            .unwrap();
+        storage.insert(item).unwrap();
+        assert_eq!(storage.get("session:def").unwrap().expires_on, Some(5_000));
+
+        // the rules apply to the items created by the other writes as well
+        let mut batch = storage.batch();
+        batch.insert(StorageItem::new("session:batch", &1_u8).unwrap());
+        batch.commit().unwrap();
+        let expires_on = |key: &str| storage.get(key).unwrap().expires_on;
+        assert_eq!(expires_on("session:batch"), Some(1_000 + 30 * 60));
+
+        insert("user:def");
+        storage.clone_item("user:def", "session:copy").unwrap();
+        assert_eq!(expires_on("session:copy"), Some(1_000 + 30 * 60));
+
+        storage.ts_append("session:series", 1, 1.0).unwrap();
+        assert_eq!(expires_on("session:series"), Some(1_000 + 30 * 60));
+
+        let item = StorageItem::new("replacement", &2_u8).unwrap();
+        storage.cas_item("session:abc", 0, item).unwrap();
+        assert_eq!(expires_on("session:abc"), Some(1_000 + 30 * 60));
+    }
+
+    #[test]
+    fn try_lock_for_test() {
+        use std::sync::Barrier;
+
+        let storage = Arc::new(Storage::open_in_memory());
+        storage
+            .insert(StorageItem::new("my_key", &42_u32).unwrap())
+            .unwrap();
+
+        let locked = Arc::new(Barrier::new(2));
+        let release = Arc::new(Barrier::new(2));
+        let holder = {
+            let storage = storage.clone();
+            let locked = locked.clone();
+            let release = release.clone();
+            thread::spawn(move || {
+                let _global_lock = storage.global_lock();
+                locked.wait();
+                release.wait();
+            })
+        };
+        locked.wait();
+
+        // the global lock held by another thread is not released in time
+        let timeout = Duration::from_millis(50);
+        let started_on = Instant::now();
+        assert!(storage.try_global_lock_for(timeout).is_none());
+        assert!(started_on.elapsed() >= timeout);
+        assert!(storage.try_lock_for(timeout).is_none());
+
+        release.wait();
+        holder.join().unwrap();
+
+        let mut global_lock = storage.try_global_lock_for(timeout).unwrap();
+        // the owner of the global lock keeps access to the storage operations
+        assert!(storage.try_lock_for(timeout).is_some());
+        global_lock.unlock();
+        let guard = storage.try_lock_for(timeout).unwrap();
+        assert!(guard.get("my_key").is_some());
+    }
+
+    #[test]
+    fn concurrent_flush_test() {
+        use std::sync::Barrier;
+
+        let config = temp_storage_config("concurrent_flush");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        let storage = Arc::new(Storage::open_with_config(config.clone()));
+        for i in 0..50 {
+            let key = format!("key_{}", i);
+            storage.insert(StorageItem::new(&key, &i).unwrap()).unwrap();
+        }
+        // the replaced items leave files to be cleaned up by the flushes
+        storage.flush().unwrap();
+        for i in 0..50 {
+            let key = format!("key_{}", i);
+            storage
+                .insert(StorageItem::new(&key, &(i * 2)).unwrap())
+                .unwrap();
+        }
+
+        let barrier = Arc::new(Barrier::new(2));
+        let flushes: Vec<_> = (0..2)
+            .map(|_| {
+                let storage = storage.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    storage.flush()
+                })
+            })
+            .collect();
+        for flush in flushes {
+            assert!(flush.join().unwrap().is_ok());
+        }
+
+        // a flush does not wait for a running one with `try_flush`
+        let flush_guard = storage.flush_lock.lock().unwrap();
+        assert!(matches!(storage.try_flush(), Err(Error::FlushInProgress)));
+        drop(flush_guard);
+        assert!(storage.try_flush().is_ok());
+
+        // exactly the files of the items are left
+        let mut ids: Vec<String> = storage
+            .keys()
+            .iter()
+            .map(|key| storage.get(key).unwrap().id)
+            .collect();
+        ids.sort();
+        let mut files: Vec<String> = std::fs::read_dir(data_path.join(DIR_STORAGE_DATA))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, ids);
+
+        drop(storage);
+        let storage = Storage::open_with_config(config);
+        assert_eq!(storage.keys().len(), 50);
+        assert_eq!(storage.get_inner_object::<i32>("key_7"), Some(14));
+    }
+
+    #[test]
+    fn id_scheme_test() {
+        let storage = Storage::open_in_memory().with_id_scheme(IdScheme::Uuid7);
+
+        let keys: Vec<String> = (0..100).map(|i| format!("key_{:03}", i)).collect();
+        let ids: Vec<String> = keys
+            .iter()
+            .map(|key| storage.new_item(key, ItemType::Custom, key).unwrap().id)
+            .collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, ids);
+        assert!(ids
+            .iter()
+            .all(|id| uuid::Uuid::parse_str(id).unwrap().get_version_num() == 7));
+
+        // copies of items get ids of the scheme too
+        storage
+            .insert(
+                storage
+                    .new_item("source", ItemType::Custom, &1_u32)
+                    .unwrap(),
+            )
+            .unwrap();
+        storage.clone_item("source", "copy").unwrap();
+        let id = storage.get("copy").unwrap().id;
+        assert_eq!(uuid::Uuid::parse_str(&id).unwrap().get_version_num(), 7);
+        assert!(id > ids[99]);
+
+        let storage = Storage::open_in_memory();
+        let item = storage.new_item("key", ItemType::Custom, &1_u32).unwrap();
+        assert_eq!(
+            uuid::Uuid::parse_str(&item.id).unwrap().get_version_num(),
+            4
+        );
+    }
+
+    #[test]
+    fn archive_test() {
+        let storage = Storage::open_in_memory();
+        storage
+            .insert(StorageItem::new("my_number", &42_u32).unwrap())
+            .unwrap();
+        let item = StorageItem::builder("my_string")
+            .item_type(ItemType::Basic(BasicType::String))
+            .description("described")
+            .tag("first")
+            .metafield("name", "value")
+            .build(&String::from("abc"))
+            .unwrap();
+        storage.insert(item).unwrap();
+        assert!(storage.update_inner_object("my_number", &43_u32));
+
+        let mut archive = vec![];
+        let bytes_written = storage.export_archive(&mut archive).unwrap();
+        assert_eq!(bytes_written, archive.len());
+
+        let imported = Storage::open_in_memory();
+        assert_eq!(imported.import_archive(archive.as_slice()), Ok(2));
+        let mut keys = imported.keys();
+        keys.sort();
+        assert_eq!(keys, vec!["my_number", "my_string"]);
+        for key in keys {
+            let (expected, item) = (storage.get(&key).unwrap(), imported.get(&key).unwrap());
+            assert_eq!(item.id, expected.id);
+            assert_eq!(item.version, expected.version);
+            assert_eq!(item.data, expected.data);
+            assert_eq!(item.description, expected.description);
+            assert_eq!(item.tags, expected.tags);
+            assert_eq!(item.metafields, expected.metafields);
+            assert_eq!(
+                format!("{:?}", item.item_type),
+                format!("{:?}", expected.item_type)
+            );
+        }
+        assert_eq!(imported.get_inner_object::<u32>("my_number"), Some(43));
+
+        // nothing is imported from a truncated archive
+        let truncated = Storage::open_in_memory();
+        let result = truncated.import_archive(&archive[..archive.len() - 4]);
+        assert!(result.unwrap_err().contains("Could not read archive"));
+        assert!(truncated.keys().is_empty());
+
+        // nothing is imported if the last item is rejected by the key policy
+        let source = Storage::open_in_memory();
+        for (key, value) in [("alpha", 2_u32), ("beta", 2), ("zeta-1", 2)] {
+            source
+                .insert(StorageItem::new(key, &value).unwrap())
+                .unwrap();
+        }
+        let mut archive = vec![];
+        source.export_archive(&mut archive).unwrap();
+        let key_policy = KeyPolicy {
+            allowed_pattern: Some(regex::Regex::new("^[a-z]+$").unwrap()),
+            ..Default::default()
+        };
+        let target = Storage::open_in_memory().with_key_policy(key_policy);
+        target
+            .insert(StorageItem::new("alpha", &1_u32).unwrap())
+            .unwrap();
+        assert!(target.import_archive(archive.as_slice()).is_err());
+        assert_eq!(target.keys(), vec!["alpha"]);
+        assert_eq!(target.get_inner_object::<u32>("alpha"), Some(1));
+    }
+
+    #[test]
+    fn initial_capacity_test() {
+        let data_path = temp_data_path("initial_capacity");
+        let config = |initial_capacity| {
+            Arc::new(Config {
+                storage: Some(StorageConfig {
+                    data_path: data_path.clone(),
+                    initial_capacity,
+                    ..Default::default()
+                }),
+                api: None,
+                http: None,
+                remote: None,
+            })
+        };
+
+        let storage = Storage::open_with_config(config(None));
+        for i in 0..500_u32 {
+            storage
+                .insert(StorageItem::new(&format!("key_{}", i), &i).unwrap())
+                .unwrap();
+        }
+        drop(storage);
+
+        // the loaded items do not depend on the capacity the storage is sized for
+        for initial_capacity in [None, Some(16), Some(1000)] {
+            let storage = Storage::open_with_config(config(initial_capacity));
+            assert_eq!(storage.keys().len(), 500);
+            for i in [0_u32, 250, 499] {
+                let key = format!("key_{}", i);
+                assert_eq!(storage.get_inner_object::<u32>(&key), Some(i));
+            }
+        }
+    }
+
+    #[test]
+    fn flush_report_test() {
+        let config = temp_storage_config("flush_report");
+
+        let storage = Storage::open_with_config(config);
+        for key in ["first", "second", "third"] {
+            storage
+                .insert(StorageItem::new(key, &key.to_string()).unwrap())
+                .unwrap();
+        }
+        let report = storage.flush().unwrap();
+        assert_eq!(report.items_written, 3);
+        assert_eq!(report.items_skipped, 0);
+        assert_eq!(report.files_removed, 0);
+        assert!(report.bytes_written > 0);
+
+        // only the updated item is written again
+        assert!(storage.update_inner_object("second", &String::from("2nd")));
+        let report = storage.flush().unwrap();
+        assert_eq!(report.items_written, 1);
+        assert_eq!(report.items_skipped, 2);
+        assert_eq!(report.files_removed, 0);
+
+        assert!(storage.remove("third"));
+        let report = storage.flush().unwrap();
+        assert_eq!(report.items_written, 0);
+        assert_eq!(report.items_skipped, 2);
+        assert_eq!(report.files_removed, 1);
+
+        // a sync persists the data like a flush
+        assert!(storage.update_inner_object("first", &String::from("1st")));
+        storage.sync().unwrap();
+        assert_eq!(
+            storage.persisted_version("first"),
+            storage.get("first").map(|item| item.version)
+        );
+    }
+
+    #[test]
+    fn flush_if_dirty_test() {
+        let config = temp_storage_config("flush_if_dirty");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+        let storage_info_path = data_path.join(FILE_STORAGE_INFO);
+        let modified = || {
+            std::fs::metadata(&storage_info_path)
+                .unwrap()
+                .modified()
+                .unwrap()
+        };
+
+        let storage = Storage::open_with_config(config.clone());
+        assert!(!storage.is_dirty());
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
+        assert!(storage.is_dirty());
+        storage.flush().unwrap();
+        assert!(!storage.is_dirty());
+        let flushed_on = modified();
+
+        // reads do not modify the storage
+        thread::sleep(Duration::from_millis(10));
+        assert!(storage.get("my_string").is_some());
+        assert!(!storage.keys().is_empty());
+        assert!(!storage.remove("missing"));
+        assert!(matches!(storage.flush_if_dirty(), Ok(None)));
+        drop(storage);
+        assert_eq!(modified(), flushed_on);
+
+        // reopen and close without modifications
+        let storage = Storage::open_with_config(config.clone());
+        assert!(!storage.is_dirty());
+        drop(storage);
+        assert_eq!(modified(), flushed_on);
+
+        // a modification is persisted on close
+        let storage = Storage::open_with_config(config);
+        storage.clear();
+        drop(storage);
+        assert_ne!(modified(), flushed_on);
+    }
+
+    #[test]
+    fn flush_read_availability_test() {
+        use std::sync::mpsc::{channel, Sender};
+        use std::time::Instant;
+
+        const FLUSH_DURATION: Duration = Duration::from_millis(500);
+
+        /// in-memory backend with a slow flush
+        struct SlowBackend {
+            inner: MemBackend,
+            flush_started: Sender<()>,
+        }
+
+        struct SlowSnapshot {
+            flush_started: Sender<()>,
+        }
+
+        impl StorageSnapshot for SlowSnapshot {
+            fn flush(self: Box<Self>) -> Result<FlushReport, Error> {
+                self.flush_started.send(()).unwrap();
+                thread::sleep(FLUSH_DURATION);
+                Ok(FlushReport::default())
+            }
+        }
+
+        impl StorageBackend for SlowBackend {
+            fn get(&self, key: &str) -> Option<StorageItem> {
+                self.inner.get(key)
+            }
+            fn insert(&mut self, storage_item: StorageItem) {
+                self.inner.insert(storage_item)
+            }
+            fn remove(&mut self, key: &str) -> bool {
+                self.inner.remove(key)
+            }
+            fn clear(&mut self) {
+                self.inner.clear()
+            }
+            fn keys(&self) -> Vec<String> {
+                self.inner.keys()
+            }
+            fn load(&mut self) -> Result<(), Error> {
+                self.inner.load()
+            }
+            fn snapshot(&self) -> Box<dyn StorageSnapshot> {
+                Box::new(SlowSnapshot {
+                    flush_started: self.flush_started.clone(),
+                })
+            }
+        }
+
+        let (flush_started, flush_started_receiver) = channel();
+        let storage = Arc::new(Storage::with_backend(Box::new(SlowBackend {
+            inner: MemBackend::new(),
+            flush_started,
+        })));
+
+        let key = "my_string";
+        storage
+            .insert(StorageItem::new(key, &String::from("abc")).unwrap())
+            .unwrap();
+
+        let storage_clone = storage.clone();
+        let flush = thread::spawn(move || storage_clone.flush());
+        flush_started_receiver.recv().unwrap();
+
+        // reads are served while the flush is in progress
+        let started_on = Instant::now();
+        assert!(storage.get(key).is_some());
+        assert!(storage.contains_key(key));
+        assert_eq!(storage.keys().len(), 1);
+        assert!(started_on.elapsed() < FLUSH_DURATION / 2);
+
+        assert!(flush.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn report_test() {
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+
+        let mut item = StorageItem::with_type(
+            "my_string",
+            ItemType::Basic(BasicType::String),
+            &String::from("abc"),
+        )
+        .unwrap();
+        item.add_tag("tag1");
+        item.add_tag("tag2");
+        item.expires_on = Some(1_700_000_000);
+        storage.insert(item).unwrap();
+
+        let map: HashMap<String, String> = HashMap::new();
+        let item = StorageItem::with_type(
+            "my \"map\"",
+            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String)),
+            &map,
+        )
+        .unwrap();
+        storage.insert(item).unwrap();
+
+        let mut report = Vec::new();
+        storage.report(&mut report).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        let lines: Vec<_> = report.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "key,item_type,version,bytes,tags,expires_on");
+        assert_eq!(
+            lines[1],
+            "\"my \"\"map\"\"\",\"Complex(Map(String, String))\",0,1,,"
+        );
+        assert_eq!(lines[2], "my_string,Basic(String),0,4,tag1;tag2,1700000000");
+    }
+
+    #[test]
+    fn key_policy_test() {
+        let key_policy = KeyPolicy {
+            max_key_len: Some(16),
+            allowed_pattern: Some(regex::Regex::new("^[a-z0-9_]+$").unwrap()),
+            lowercase: true,
+        };
+        let storage =
+            Storage::with_backend(Box::new(MemBackend::new())).with_key_policy(key_policy);
+        let my_string = String::from("abc");
+
+        // too long key is rejected
+        let item = StorageItem::new("my_very_long_string_key", &my_string).unwrap();
+        assert!(storage.insert(item).unwrap_err().contains("maximum length"));
+
+        // disallowed characters are rejected
+        let item = StorageItem::new("my-string", &my_string).unwrap();
+        assert!(storage
+            .insert(item)
+            .unwrap_err()
+            .contains("allowed pattern"));
+        assert!(storage.keys().is_empty());
 
-        let storage_type =
-            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
-        let storage_item = StorageItem::with_type(key, storage_type, &my_map).unwrap();
+        // keys are normalized to lowercase on insert and get
+        let item = StorageItem::new("My_String", &my_string).unwrap();
+        storage.insert(item).unwrap();
+        assert_eq!(storage.keys(), vec!["my_string".to_string()]);
+        assert_eq!(storage.get("MY_STRING").unwrap().key, "my_string");
+        assert_eq!(
+            storage.get_inner_object::<String>("my_String").unwrap(),
+            my_string
+        );
+        assert!(storage.remove("My_String"));
+    }
 
-        storage.insert(storage_item);
+    #[test]
+    fn keys_paginated_test() {
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+        for index in 0..50 {
+            let key = format!("key_{}", index);
+            storage
+                .insert(StorageItem::new(&key, &index).unwrap())
+                .unwrap();
+        }
 
-        // get map entires in multiple threads
-        let mut threads = Vec::with_capacity(THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let storage_clone = storage.clone();
-            let entries_count = MAP_ENTRIES_PER_THREAD;
-            let handler = thread::spawn(move || {
-                let map: HashMap<String, String> = storage_clone.get_inner_object(key).unwrap();
-                for entry_number in 0..entries_count {
-                    let entry_key = format!("{}-{}", thread_number, entry_number);
-                    let entry_value = format!("{}", thread_number * entry_number);
-                    assert_eq!(map.get(&entry_key).unwrap(), &entry_value);
-                }
-                storage_clone.update_inner_object(key, &map);
-                thread::sleep(Duration::from_millis(1));
-            });
-            threads.push(handler);
+        let mut paged_keys = vec![];
+        for offset in (0..50).step_by(10) {
+            let (page, total) = storage.keys_paginated(offset, 10);
+            assert_eq!(page.len(), 10);
+            assert_eq!(total, 50);
+            paged_keys.extend(page);
         }
 
-        // wait until the finish of all the spawned threads
-        for handler in threads {
-            handler.join().unwrap();
+        // no overlaps or gaps
+        let mut keys = storage.keys();
+        keys.sort();
+        assert_eq!(paged_keys, keys);
+
+        // out of range
+        let (page, total) = storage.keys_paginated(50, 10);
+        assert!(page.is_empty());
+        assert_eq!(total, 50);
+    }
+
+    #[test]
+    fn keys_sorted_test() {
+        let mut keys = ["b", "a/2", "C", "a", "ab", "a/10", "_", "b0"].map(String::from);
+        let mut expected = keys.to_vec();
+        expected.sort();
+
+        // every rotation of the insertion order yields the same listing
+        for _ in 0..keys.len() {
+            keys.rotate_left(3);
+            let storage = Storage::with_backend(Box::new(MemBackend::new()));
+            for key in keys.iter().rev() {
+                storage
+                    .insert(StorageItem::new(key, &0_u8).unwrap())
+                    .unwrap();
+            }
+            assert_eq!(storage.keys_sorted(), expected);
         }
+        assert_eq!(
+            expected,
+            vec!["C", "_", "a", "a/10", "a/2", "ab", "b", "b0"]
+        );
+    }
 
-        // check entries count
-        let map = storage
-            .get_inner_object::<HashMap<String, String>>(key)
+    #[test]
+    fn accessors_test() {
+        let config = temp_storage_config("accessors");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        let storage = Storage::open_with_config(config.clone());
+        assert_eq!(storage.data_path(), data_path.as_path());
+        assert!(Arc::ptr_eq(storage.config(), &config));
+
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+        assert_eq!(storage.data_path(), Path::new(""));
+        assert!(storage.config().storage.is_none());
+    }
+
+    #[test]
+    fn missing_storage_config_test() {
+        let config = Arc::new(Config::default());
+
+        let result = Storage::try_open_with_config(config.clone());
+        assert!(result.err().unwrap().contains("`storage` section"));
+
+        let result = FileBackend::try_open(config);
+        assert!(result.err().unwrap().contains("`storage` section"));
+    }
+
+    #[test]
+    fn data_path_not_directory_test() {
+        let dir = temp_data_path("data_path_not_directory");
+        std::fs::create_dir_all(&dir).unwrap();
+        let open = |data_path: PathBuf| {
+            Storage::try_open_with_config(Arc::new(Config {
+                storage: Some(StorageConfig {
+                    data_path,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+        };
+
+        // the data path is a file
+        let data_path = dir.join("file");
+        std::fs::write(&data_path, b"not a directory").unwrap();
+        let err = open(data_path.clone()).err().unwrap();
+        assert_eq!(
+            err,
+            format!(
+                "The data path `{}` is not a directory",
+                data_path.to_string_lossy()
+            )
+        );
+
+        // the lock file cannot be created
+        let data_path = dir.join("storage");
+        std::fs::create_dir_all(data_path.join(FILE_STORAGE_LOCK)).unwrap();
+        let err = open(data_path).err().unwrap();
+        assert!(err.starts_with("Could not open the lock file"));
+    }
+
+    #[test]
+    fn merge_from_test() {
+        let source = Storage::with_backend(Box::new(MemBackend::new()));
+        let mut shared = StorageItem::new("shared", &String::from("source")).unwrap();
+        shared.update_object(&String::from("source"));
+        source.insert(shared.clone()).unwrap();
+        source
+            .insert(StorageItem::new("only_source", &String::from("source")).unwrap())
             .unwrap();
-        assert_eq!(map.keys().count(), THREADS_COUNT * MAP_ENTRIES_PER_THREAD);
 
-        // clean up the storage
-        storage.clear();
+        let destination = |version: u64| {
+            let storage = Storage::with_backend(Box::new(MemBackend::new()));
+            let mut item = StorageItem::new("shared", &String::from("destination")).unwrap();
+            item.version = version;
+            storage.insert(item).unwrap();
+            storage
+        };
+        let shared_value =
+            |storage: &Storage| storage.get_inner_object::<String>("shared").unwrap();
+
+        let storage = destination(0);
+        let merged = storage
+            .merge_from(&source, ConflictPolicy::Overwrite, true)
+            .unwrap();
+        assert_eq!(merged, 2);
+        assert_eq!(shared_value(&storage), "source");
+        assert_eq!(storage.get("shared").unwrap().id, shared.id);
+        assert!(storage.contains_key("only_source"));
+
+        let storage = destination(0);
+        let merged = storage
+            .merge_from(&source, ConflictPolicy::KeepExisting, true)
+            .unwrap();
+        assert_eq!(merged, 1);
+        assert_eq!(shared_value(&storage), "destination");
+        assert!(storage.contains_key("only_source"));
+
+        // the source item has the higher version
+        let storage = destination(0);
+        let merged = storage
+            .merge_from(&source, ConflictPolicy::HigherVersionWins, false)
+            .unwrap();
+        assert_eq!(merged, 2);
+        assert_eq!(shared_value(&storage), "source");
+        assert_ne!(storage.get("shared").unwrap().id, shared.id);
+
+        // the destination item has the higher version
+        let storage = destination(2);
+        let merged = storage
+            .merge_from(&source, ConflictPolicy::HigherVersionWins, false)
+            .unwrap();
+        assert_eq!(merged, 1);
+        assert_eq!(shared_value(&storage), "destination");
+
+        // the source storage is not modified
+        assert_eq!(shared_value(&source), "source");
+        assert_eq!(source.keys().len(), 2);
+
+        // nothing is merged if an item is rejected
+        let storage = destination(0).with_max_bytes(Some(12));
+        let result = storage.merge_from(&source, ConflictPolicy::Overwrite, true);
+        assert!(result.unwrap_err().contains("maximum"));
+        assert_eq!(shared_value(&storage), "destination");
+        assert!(!storage.contains_key("only_source"));
     }
 
     #[test]
-    fn multithread_map_remove_test() {
-        let storage = Arc::new(Storage::open());
+    fn watch_coalesced_test() {
+        let storage = Storage::open_in_memory();
+        let debounce = Duration::from_millis(100);
+        let receiver = storage.watch_coalesced("counter", debounce);
+
+        for i in 0..10_u32 {
+            storage
+                .insert(StorageItem::new("counter", &i).unwrap())
+                .unwrap();
+            // keys sharing the prefix are not watched
+            storage
+                .insert(StorageItem::new("counter:other", &i).unwrap())
+                .unwrap();
+        }
 
-        // clean up the storage
-        storage.clear();
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event.key, "counter");
+        assert_eq!(event.kind, ChangeKind::Set);
+        assert!(receiver.recv_timeout(debounce * 3).is_err());
+        assert_eq!(storage.get_inner_object::<u32>("counter"), Some(9));
 
-        // create a new map and insert entries
-        let key = "my_map";
-        let mut my_map = HashMap::<String, String>::new();
+        // the latest kind of change is delivered
+        storage
+            .insert(StorageItem::new("counter", &10_u32).unwrap())
+            .unwrap();
+        storage.remove("counter");
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event.kind, ChangeKind::Removed);
+    }
 
-        for thread_number in 0..THREADS_COUNT {
-            for entry_number in 0..MAP_ENTRIES_PER_THREAD {
-                let entry_key = format!("{}-{}", thread_number, entry_number);
-                let entry_value = format!("{}", thread_number * entry_number);
-                my_map.insert(entry_key, entry_value);
+    #[test]
+    fn filter_items_test() {
+        let storage = Storage::open_in_memory();
+        let insert = |key: &str, len: usize, tag: Option<&str>| {
+            let mut item = StorageItem::new(key, &vec![0_u8; len]).unwrap();
+            if let Some(tag) = tag {
+                item.add_tag(tag);
             }
-        }
+            storage.insert(item).unwrap();
+        };
+        insert("small", 10, Some("hot"));
+        insert("medium", 100, None);
+        insert("large", 1000, Some("hot"));
+        insert("tagged", 10, Some("cold"));
+
+        let keys = |items: Vec<StorageItem>| {
+            let mut keys: Vec<String> = items.into_iter().map(|item| item.key).collect();
+            keys.sort();
+            keys
+        };
+        let larger = storage.filter_items(|item| item.data.len() > 50);
+        assert_eq!(keys(larger), vec!["large", "medium"]);
 
-        let storage_type =
-            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
-        let storage_item = StorageItem::with_type(key, storage_type, &my_map).unwrap();
+        let hot = storage.filter_items(|item| {
+            item.tags
+                .as_ref()
+                .is_some_and(|tags| tags.iter().any(|tag| tag == "hot"))
+        });
+        assert_eq!(keys(hot), vec!["large", "small"]);
 
-        storage.insert(storage_item);
+        assert!(storage.filter_items(|_| false).is_empty());
+    }
 
-        // verify and remove map entires in multiple threads
-        let mut threads = Vec::with_capacity(THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let storage_clone = storage.clone();
-            let entries_count = MAP_ENTRIES_PER_THREAD;
-            let handler = thread::spawn(move || {
-                let mut global_lock = storage_clone.global_lock();
-                let mut map: HashMap<String, String> = storage_clone.get_inner_object(key).unwrap();
-                for entry_number in 0..entries_count {
-                    let entry_key = format!("{}-{}", thread_number, entry_number);
-                    let entry_value = format!("{}", thread_number * entry_number);
-                    assert_eq!(map.remove(&entry_key).unwrap(), entry_value);
-                }
-                storage_clone.update_inner_object(key, &map);
-                global_lock.unlock();
-                thread::sleep(Duration::from_millis(1));
-            });
-            threads.push(handler);
+    #[test]
+    fn keys_modified_since_test() {
+        let now = Arc::new(AtomicUsize::new(1_000));
+        let clock = now.clone();
+        let storage =
+            Storage::open_in_memory().with_clock(move || clock.load(Ordering::SeqCst) as u64);
+
+        let insert = |key: &str| {
+            storage
+                .insert(StorageItem::new(key, &String::from("abc")).unwrap())
+                .unwrap();
+        };
+        insert("old");
+        now.store(2_000, Ordering::SeqCst);
+        insert("cutoff");
+        now.store(3_000, Ordering::SeqCst);
+        insert("new");
+        assert_eq!(storage.get("old").unwrap().modified_at, 1_000);
+
+        let mut keys = storage.keys_modified_since(2_000);
+        keys.sort();
+        assert_eq!(keys, vec!["new".to_string()]);
+
+        // any mutation stamps the item
+        now.store(4_000, Ordering::SeqCst);
+        assert!(storage.update_inner_object("old", &String::from("def")));
+        assert!(storage.add_tag("cutoff", "important"));
+        let mut keys = storage.keys_modified_since(3_000);
+        keys.sort();
+        assert_eq!(keys, vec!["cutoff".to_string(), "old".to_string()]);
+        assert!(storage.keys_modified_since(4_000).is_empty());
+    }
+
+    #[test]
+    fn watch_removal_test() {
+        let storage = Storage::open_in_memory();
+        let keys = ["first", "second", "third"];
+        for key in keys {
+            storage
+                .insert(StorageItem::new(key, &String::from("abc")).unwrap())
+                .unwrap();
         }
+        let receivers: Vec<_> = keys.iter().map(|key| storage.watch(key)).collect();
 
-        // wait until the finish of all the spawned threads
-        for handler in threads {
-            handler.join().unwrap();
+        // removing a missing key is not a change
+        let missing = storage.watch("missing");
+        assert!(!storage.remove("missing"));
+        assert!(missing.try_recv().is_err());
+
+        storage.clear();
+        for (key, receiver) in keys.iter().zip(&receivers) {
+            let events: Vec<ChangeEvent> = receiver.try_iter().collect();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].key, *key);
+            assert_eq!(events[0].kind, ChangeKind::Removed);
         }
 
-        // ensure the map is empty
-        let map = storage
-            .get_inner_object::<HashMap<String, String>>(key)
+        // the watcher may call back into the storage, the lock is released before delivery
+        let storage = Arc::new(storage);
+        storage
+            .insert(StorageItem::new("first", &String::from("abc")).unwrap())
             .unwrap();
-        assert!(map.is_empty());
+        let receiver = storage.watch("first");
+        let storage_clone = storage.clone();
+        let watcher = thread::spawn(move || {
+            let event = receiver.recv().unwrap();
+            (event.kind, storage_clone.get(&event.key).is_none())
+        });
+        assert!(storage.remove("first"));
+        assert_eq!(watcher.join().unwrap(), (ChangeKind::Removed, true));
     }
 
     #[test]
-    fn multithread_multiobject_test() {
-        let storage = Arc::new(Storage::open());
-
-        // clean up the storage
+    fn watch_dir_test() {
+        let dir = temp_data_path("watch_dir");
+        std::fs::create_dir_all(dir.join("css")).unwrap();
+        std::fs::write(dir.join("index.html"), b"<p>first</p>").unwrap();
+        std::fs::write(dir.join("css").join("main.css"), b"p {}").unwrap();
+
+        let storage = Arc::new(Storage::open_in_memory());
+        assert_eq!(storage.import_dir(&dir, "site").unwrap(), 2);
+        assert_eq!(storage.import_dir(&dir, "site").unwrap(), 0);
+        assert_eq!(
+            storage.get_inner_object::<Vec<u8>>("site/css/main.css"),
+            Some(b"p {}".to_vec())
+        );
         storage.clear();
 
-        let key_prefix = "my_map";
+        let watcher = storage.watch_dir(&dir, "site").unwrap();
+        assert_eq!(watcher.prefix(), "site");
+        let content = |key: &str| storage.get_inner_object::<Vec<u8>>(key);
+        assert_eq!(content("site/index.html"), Some(b"<p>first</p>".to_vec()));
 
-        // creating and inserting map objects in multiple threads
-        let mut threads = Vec::with_capacity(THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let storage_clone = storage.clone();
-            let object_key = format!("{}-{}", key_prefix, thread_number);
-            let handler = thread::spawn(move || {
-                let map = HashMap::<String, String>::new();
-                let storage_type =
-                    ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
-                let storage_item = StorageItem::with_type(&object_key, storage_type, &map).unwrap();
+        // waits until the watched change settles, the file may be seen half written first
+        let receiver = storage.watch_prefix("site/");
+        let wait_for = |expected: &dyn Fn() -> bool| {
+            while !expected() {
+                receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            }
+        };
 
-                storage_clone.insert(storage_item);
-                thread::sleep(Duration::from_millis(1));
-            });
-            threads.push(handler);
-        }
+        std::fs::write(dir.join("index.html"), b"<p>second</p>").unwrap();
+        wait_for(&|| content("site/index.html") == Some(b"<p>second</p>".to_vec()));
 
-        // wait until the finish of all the spawned threads
-        for handler in threads {
-            handler.join().unwrap();
-        }
+        std::fs::write(dir.join("about.html"), b"<p>about</p>").unwrap();
+        wait_for(&|| content("site/about.html") == Some(b"<p>about</p>".to_vec()));
 
-        // verify inserted objects
-        let object_keys = storage.keys();
-        assert_eq!(object_keys.len(), THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let object_key = format!("{}-{}", key_prefix, thread_number);
-            assert!(object_keys.contains(&object_key));
-        }
+        std::fs::remove_file(dir.join("index.html")).unwrap();
+        wait_for(&|| !storage.contains_key("site/index.html"));
 
-        // inserting map entires in multiple threads
-        let mut threads = Vec::with_capacity(THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let storage_clone = storage.clone();
-            let object_key = format!("{}-{}", key_prefix, thread_number);
-            let entries_count = MAP_ENTRIES_PER_THREAD;
-            let handler = thread::spawn(move || {
-                let mut map: HashMap<String, String> =
-                    storage_clone.get_inner_object(&object_key).unwrap();
-                for entry_number in 0..entries_count {
-                    let entry_key = format!("{}-{}", thread_number, entry_number);
-                    let entry_value = format!("{}", thread_number * entry_number);
-                    map.insert(entry_key, entry_value);
-                }
-                storage_clone.update_inner_object(&object_key, &map);
-                thread::sleep(Duration::from_millis(1));
-            });
-            threads.push(handler);
-        }
+        std::fs::remove_dir_all(dir.join("css")).unwrap();
+        wait_for(&|| !storage.contains_key("site/css/main.css"));
+        assert_eq!(storage.keys(), vec!["site/about.html".to_string()]);
 
-        // wait until the finish of all the spawned threads
-        for handler in threads {
-            handler.join().unwrap();
-        }
+        // dropping the watcher stops the sync
+        drop(watcher);
+        while receiver.recv_timeout(Duration::from_millis(200)).is_ok() {}
+        std::fs::write(dir.join("contact.html"), b"<p>contact</p>").unwrap();
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+        assert!(!storage.contains_key("site/contact.html"));
+    }
 
-        // verify and remove map entires in multiple threads
-        let mut threads = Vec::with_capacity(THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let storage_clone = storage.clone();
-            let object_key = format!("{}-{}", key_prefix, thread_number);
-            let entries_count = MAP_ENTRIES_PER_THREAD;
-            let handler = thread::spawn(move || {
-                let mut map: HashMap<String, String> =
-                    storage_clone.get_inner_object(&object_key).unwrap();
-                for entry_number in 0..entries_count {
-                    let entry_key = format!("{}-{}", thread_number, entry_number);
-                    let entry_value = format!("{}", thread_number * entry_number);
-                    assert_eq!(map.remove(&entry_key).unwrap(), entry_value);
-                }
-                storage_clone.update_inner_object(&object_key, &map);
-                thread::sleep(Duration::from_millis(1));
-            });
-            threads.push(handler);
+    #[test]
+    fn try_get_inner_object_test() {
+        let storage = Storage::open_in_memory();
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
+
+        assert_eq!(
+            storage.try_get_inner_object::<String>("my_string"),
+            Ok(Some("abc".to_string()))
+        );
+        assert_eq!(storage.try_get_inner_object::<String>("missing"), Ok(None));
+
+        // the length prefix of the string decodes as a number, the rest is left over
+        assert_eq!(storage.get_inner_object::<u32>("my_string"), Some(3));
+        let result = storage.try_get_inner_object::<u32>("my_string");
+        assert!(matches!(result, Err(CodecError::Decode(_))));
+        let result = storage.try_get_inner_object::<bool>("my_string");
+        assert!(matches!(result, Err(CodecError::Decode(_))));
+    }
+
+    #[test]
+    fn subscribe_test() {
+        use storage_const::CHANGE_BROADCAST_CAPACITY;
+        use tokio::sync::broadcast::error::TryRecvError;
+
+        let storage = Storage::open_in_memory();
+        let mut receiver = storage.subscribe();
+        storage
+            .set("my_key", ItemType::Basic(BasicType::U32), &1_u32)
+            .unwrap();
+        assert!(storage.remove("my_key"));
+        let event = receiver.blocking_recv().unwrap();
+        assert_eq!(event.key, "my_key");
+        assert_eq!(event.kind, ChangeKind::Set);
+        assert_eq!(receiver.try_recv().unwrap().kind, ChangeKind::Removed);
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+        // a receiver falling behind misses the oldest changes
+        for i in 0..CHANGE_BROADCAST_CAPACITY + 10 {
+            storage
+                .set("counter", ItemType::Basic(BasicType::U64), &(i as u64))
+                .unwrap();
+        }
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Lagged(10)));
+        let mut received = 0;
+        while receiver.try_recv().is_ok() {
+            received += 1;
         }
+        assert_eq!(received, CHANGE_BROADCAST_CAPACITY);
 
-        // wait until the finish of all the spawned threads
-        for handler in threads {
-            handler.join().unwrap();
+        // a dropped receiver ends the subscription
+        drop(receiver);
+        assert!(storage.watchers.is_empty());
+    }
+
+    #[test]
+    fn watch_prefix_test() {
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+        let receiver = storage.watch_prefix("user:");
+
+        let insert = |key: &str| {
+            storage
+                .insert(StorageItem::new(key, &String::from("abc")).unwrap())
+                .unwrap();
+        };
+        insert("user:1");
+        insert("session:9");
+        insert("user:2");
+        assert!(storage.update_inner_object("user:1", &String::from("def")));
+        storage.remove("session:9");
+        storage.remove("user:2");
+
+        let events: Vec<ChangeEvent> = receiver.try_iter().collect();
+        let expected = [
+            ("user:1", ChangeKind::Set),
+            ("user:2", ChangeKind::Set),
+            ("user:1", ChangeKind::Set),
+            ("user:2", ChangeKind::Removed),
+        ];
+        assert_eq!(events.len(), expected.len());
+        for (event, (key, kind)) in events.iter().zip(expected) {
+            assert_eq!(event.key, key);
+            assert_eq!(event.kind, kind);
         }
 
-        // verify and remove storage items in multiple threads
-        let mut threads = Vec::with_capacity(THREADS_COUNT);
-        for thread_number in 0..THREADS_COUNT {
-            let storage_clone = storage.clone();
-            let object_key = format!("{}-{}", key_prefix, thread_number);
-            let handler = thread::spawn(move || {
-                let map: HashMap<String, String> =
-                    storage_clone.get_inner_object(&object_key).unwrap();
-                assert!(map.is_empty());
+        storage.clear();
+        let events: Vec<ChangeEvent> = receiver.try_iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].key, "user:1");
+        assert_eq!(events[0].kind, ChangeKind::Removed);
+    }
+
+    #[test]
+    fn len_bytes_test() {
+        let crossings = Arc::new(AtomicUsize::new(0));
+        let crossings_clone = crossings.clone();
+        let storage = Storage::with_backend(Box::new(MemBackend::new())).with_high_water_mark(
+            100,
+            move |_| {
+                crossings_clone.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+
+        let item = |key: &str, len: usize| StorageItem::new(key, &vec![0_u8; len]).unwrap();
+        let item1 = item("item1", 40);
+        let item1_len = item1.data.len();
+        let item2 = item("item2", 30);
+        let item2_len = item2.data.len();
+
+        storage.insert(item1).unwrap();
+        storage.insert(item2).unwrap();
+        assert_eq!(storage.len_bytes(), item1_len + item2_len);
+        assert_eq!(crossings.load(Ordering::SeqCst), 0);
+
+        // replacing an item accounts the size difference and crosses the mark
+        let item2 = item("item2", 70);
+        let item2_len = item2.data.len();
+        storage.insert(item2).unwrap();
+        assert_eq!(storage.len_bytes(), item1_len + item2_len);
+        assert_eq!(crossings.load(Ordering::SeqCst), 1);
+
+        // staying above the mark does not invoke the callback again
+        assert!(storage.update_inner_object("item1", &vec![0_u8; 50]));
+        let item1_len = storage.get("item1").unwrap().data.len();
+        assert_eq!(storage.len_bytes(), item1_len + item2_len);
+        assert_eq!(crossings.load(Ordering::SeqCst), 1);
+
+        assert!(storage.remove("item2"));
+        assert!(!storage.remove("item2"));
+        assert_eq!(storage.len_bytes(), item1_len);
+
+        storage.clear();
+        assert_eq!(storage.len_bytes(), 0);
+        assert_eq!(crossings.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn poisoned_lock_test() {
+        let storage = Arc::new(Storage::with_backend(Box::new(MemBackend::new())));
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
+
+        let storage_clone = storage.clone();
+        let result = thread::spawn(move || {
+            let _guard = storage_clone.lock();
+            panic!("panic while holding the storage lock");
+        })
+        .join();
+        assert!(result.is_err());
+
+        let value: String = storage.get_inner_object("my_string").unwrap();
+        assert_eq!(value, "abc");
+        storage
+            .insert(StorageItem::new("other", &1_u8).unwrap())
+            .unwrap();
+        assert!(storage.contains_key("other"));
+    }
+
+    #[test]
+    fn slow_lock_warn_test() {
+        let storage = Arc::new(
+            Storage::with_backend(Box::new(MemBackend::new()))
+                .with_slow_lock_warn(Some(Duration::from_millis(50))),
+        );
+        let subscriber = LogCollector::new(tracing::Level::WARN);
+        let messages = subscriber.messages.clone();
+
+        tracing::subscriber::with_default(subscriber, || {
+            // an uncontended lock does not warn
+            assert!(storage.keys().is_empty());
+            assert!(messages.lock().unwrap().is_empty());
 
-                // remove storage object
-                storage_clone.remove(&object_key);
-                thread::sleep(Duration::from_millis(1));
+            // hold the global lock from another thread longer than the threshold
+            let (locked_sender, locked_receiver) = std::sync::mpsc::channel();
+            let storage_clone = storage.clone();
+            let holder = thread::spawn(move || {
+                let _global_lock = storage_clone.global_lock();
+                locked_sender.send(thread::current().id()).unwrap();
+                thread::sleep(Duration::from_millis(200));
             });
-            threads.push(handler);
-        }
+            let holder_thread_id = locked_receiver.recv().unwrap();
 
-        // wait until the finish of all the spawned threads
-        for handler in threads {
-            handler.join().unwrap();
-        }
+            assert!(storage.keys().is_empty());
+            holder.join().unwrap();
 
-        // ensure empty storage
-        assert!(storage.keys().is_empty());
+            let messages = messages.lock().unwrap();
+            assert_eq!(messages.len(), 1);
+            assert!(messages[0].contains("for the storage lock"));
+            assert!(messages[0].contains(&format!("{:?}", holder_thread_id)));
+        });
     }
 
     #[test]
-    fn multithread_scoped_multiobject_test() {
-        let storage = Arc::new(Storage::open());
+    fn write_batch_test() {
+        /// in-memory backend counting the flushes
+        struct CountingBackend {
+            inner: MemBackend,
+            flushes: Arc<AtomicUsize>,
+        }
 
-        // clean up the storage
-        storage.clear();
+        impl StorageBackend for CountingBackend {
+            fn get(&self, key: &str) -> Option<StorageItem> {
+                self.inner.get(key)
+            }
+            fn insert(&mut self, storage_item: StorageItem) {
+                self.inner.insert(storage_item)
+            }
+            fn remove(&mut self, key: &str) -> bool {
+                self.inner.remove(key)
+            }
+            fn clear(&mut self) {
+                self.inner.clear()
+            }
+            fn keys(&self) -> Vec<String> {
+                self.inner.keys()
+            }
+            fn load(&mut self) -> Result<(), Error> {
+                self.inner.load()
+            }
+            fn snapshot(&self) -> Box<dyn StorageSnapshot> {
+                self.flushes.fetch_add(1, Ordering::SeqCst);
+                self.inner.snapshot()
+            }
+        }
 
-        let key_prefix = "my_map";
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let storage = Storage::with_backend(Box::new(CountingBackend {
+            inner: MemBackend::new(),
+            flushes: flushes.clone(),
+        }))
+        .with_flush_every_writes(Some(10));
 
-        // create and insert map objects into storage in multiple threads
-        thread::scope(|scope| {
-            for thread_number in 0..THREADS_COUNT {
-                let storage_clone = storage.clone();
-                scope.spawn(move || {
-                    let map = HashMap::<String, String>::new();
-                    let storage_type =
-                        ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
+        let mut batch = storage.batch();
+        for i in 0..1000 {
+            batch.insert(StorageItem::new(&format!("key_{}", i), &i).unwrap());
+        }
+        assert_eq!(batch.len(), 1000);
+        assert!(storage.keys().is_empty());
+        batch.commit().unwrap();
+
+        assert_eq!(storage.keys().len(), 1000);
+        assert_eq!(storage.get_inner_object::<i32>("key_999"), Some(999));
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+        assert!(!storage.is_dirty());
+
+        // an uncommitted batch is discarded
+        let mut batch = storage.batch();
+        batch
+            .insert(StorageItem::new("other", &1_u8).unwrap())
+            .remove("key_0");
+        drop(batch);
+        assert_eq!(storage.keys().len(), 1000);
+        assert!(storage.contains_key("key_0"));
+        assert!(!storage.contains_key("other"));
+        assert_eq!(flushes.load(Ordering::SeqCst), 1);
+    }
 
-                    let object_key = format!("{}-{}", key_prefix, thread_number);
-                    let storage_item =
-                        StorageItem::with_type(&object_key, storage_type, &map).unwrap();
+    #[test]
+    fn backend_for_each_test() {
+        /// in-memory backend counting the copies returned by `get`, visiting the items by reference if set
+        struct CountingBackend {
+            inner: MemBackend,
+            gets: Arc<AtomicUsize>,
+            visit_by_reference: bool,
+        }
 
-                    storage_clone.insert(storage_item);
-                });
+        impl StorageBackend for CountingBackend {
+            fn get(&self, key: &str) -> Option<StorageItem> {
+                self.gets.fetch_add(1, Ordering::SeqCst);
+                self.inner.get(key)
             }
-        });
-
-        // verify inserted objects
-        {
-            let object_keys = storage.keys();
-            assert_eq!(object_keys.len(), THREADS_COUNT);
-            for thread_number in 0..THREADS_COUNT {
-                let object_key = format!("{}-{}", key_prefix, thread_number);
-                assert!(object_keys.contains(&object_key));
+            fn insert(&mut self, storage_item: StorageItem) {
+                self.inner.insert(storage_item)
+            }
+            fn remove(&mut self, key: &str) -> bool {
+                self.inner.remove(key)
+            }
+            fn clear(&mut self) {
+                self.inner.clear()
+            }
+            fn keys(&self) -> Vec<String> {
+                self.inner.keys()
+            }
+            fn for_each(&self, f: &mut dyn FnMut(&StorageItem)) {
+                if self.visit_by_reference {
+                    self.inner.for_each(f)
+                } else {
+                    for key in self.keys() {
+                        if let Some(item) = self.get(&key) {
+                            f(&item);
+                        }
+                    }
+                }
+            }
+            fn load(&mut self) -> Result<(), Error> {
+                self.inner.load()
+            }
+            fn snapshot(&self) -> Box<dyn StorageSnapshot> {
+                self.inner.snapshot()
             }
         }
 
-        // inserting map entires in multiple threads
-        thread::scope(|scope| {
-            for thread_number in 0..THREADS_COUNT {
-                let storage_clone = storage.clone();
-                scope.spawn(move || {
-                    let object_key = format!("{}-{}", key_prefix, thread_number);
+        for visit_by_reference in [true, false] {
+            let gets = Arc::new(AtomicUsize::new(0));
+            let storage = Storage::with_backend(Box::new(CountingBackend {
+                inner: MemBackend::new(),
+                gets: gets.clone(),
+                visit_by_reference,
+            }));
+            storage
+                .set("number", ItemType::Basic(BasicType::U32), &1_u32)
+                .unwrap();
+            storage
+                .insert(StorageItem::new("text", &String::from("abc")).unwrap())
+                .unwrap();
+            gets.store(0, Ordering::SeqCst);
+
+            let keys = storage.keys_by_type(|item_type| matches!(item_type, ItemType::Basic(_)));
+            assert_eq!(keys, vec!["number".to_string()]);
+            let items = storage.filter_items(|item| item.key == "text");
+            assert_eq!(items.len(), 1);
+            let mut keys = storage.keys_modified_since(0);
+            keys.sort();
+            assert_eq!(keys, vec!["number".to_string(), "text".to_string()]);
+            let mut buf = vec![];
+            storage.export_archive(&mut buf).unwrap();
+            let destination = Storage::open_in_memory();
+            assert_eq!(destination.import_archive(buf.as_slice()), Ok(2));
+
+            // the scans copy no item unless the backend visits copies
+            assert_eq!(gets.load(Ordering::SeqCst) == 0, visit_by_reference);
+        }
+    }
 
-                    let mut map: HashMap<String, String> =
-                        storage_clone.get_inner_object(&object_key).unwrap();
+    #[test]
+    fn clone_item_test() {
+        let storage = Storage::with_backend(Box::new(MemBackend::new()));
+        let mut item = StorageItem::builder("original")
+            .description("my item")
+            .tag("my_tag")
+            .build(&String::from("abc"))
+            .unwrap();
+        item.update_object(&String::from("abc"));
+        storage.insert(item).unwrap();
+
+        storage.clone_item("original", "copy").unwrap();
+        let original = storage.get("original").unwrap();
+        let copy = storage.get("copy").unwrap();
+        assert_ne!(copy.id, original.id);
+        assert_eq!(copy.version, 0);
+        assert_eq!(copy.description, original.description);
+        assert_eq!(copy.tags, original.tags);
+        assert_eq!(copy.data, original.data);
+
+        // mutating the copy does not affect the original
+        assert!(storage.update_inner_object("copy", &String::from("def")));
+        let value: String = storage.get_inner_object("original").unwrap();
+        assert_eq!(value, "abc");
+        let value: String = storage.get_inner_object("copy").unwrap();
+        assert_eq!(value, "def");
+        assert_eq!(storage.get("original").unwrap().version, original.version);
 
-                    for entry_number in 0..MAP_ENTRIES_PER_THREAD {
-                        let entry_key = format!("{}-{}", thread_number, entry_number);
-                        let entry_value = format!("{}", thread_number * entry_number);
-                        map.insert(entry_key, entry_value);
-                    }
+        assert_eq!(
+            storage.clone_item("missing", "other"),
+            Err(CloneError::SourceNotFound("missing".into()))
+        );
+        assert_eq!(
+            storage.clone_item("original", "copy"),
+            Err(CloneError::TargetExists("copy".into()))
+        );
+    }
 
-                    storage_clone.update_inner_object(&object_key, &map);
-                });
-            }
-        });
+    #[test]
+    fn metadata_limits_test() {
+        let storage = Storage::open_in_memory().with_metadata_limits(Some(3), Some(2));
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
 
-        // verify and remove map entires in multiple threads
-        thread::scope(|scope| {
-            for thread_number in 0..THREADS_COUNT {
-                let storage_clone = storage.clone();
-                scope.spawn(move || {
-                    let object_key = format!("{}-{}", key_prefix, thread_number);
-                    let mut map: HashMap<String, String> =
-                        storage_clone.get_inner_object(&object_key).unwrap();
+        for tag in ["a", "b", "c"] {
+            assert!(storage.add_tag("my_string", tag));
+        }
+        assert!(!storage.add_tag("my_string", "d"));
+        let item = storage.get("my_string").unwrap();
+        assert_eq!(item.tags, Some(vec!["a".into(), "b".into(), "c".into()]));
+
+        assert!(storage.set_metafield("my_string", "owner", "alice"));
+        assert!(storage.set_metafield("my_string", "team", "core"));
+        assert!(!storage.set_metafield("my_string", "region", "eu"));
+        // overwriting an existing metafield does not add one
+        assert!(storage.set_metafield("my_string", "owner", "bob"));
+        let metafields = storage.get("my_string").unwrap().metafields.unwrap();
+        assert_eq!(metafields.len(), 2);
+        assert_eq!(metafields["owner"], "bob");
+        assert!(!metafields.contains_key("region"));
+
+        let mut item = StorageItem::new("my_tagged", &String::from("abc")).unwrap();
+        for tag in ["a", "b", "c", "d"] {
+            item.add_tag(tag);
+        }
+        assert!(storage.insert(item).is_err());
+        assert!(storage.get("my_tagged").is_none());
+    }
 
-                    for entry_number in 0..MAP_ENTRIES_PER_THREAD {
-                        let entry_key = format!("{}-{}", thread_number, entry_number);
-                        let entry_value = format!("{}", thread_number * entry_number);
-                        assert_eq!(map.remove(&entry_key).unwrap(), entry_value);
-                    }
+    #[test]
+    fn update_item_metadata_test() {
+        let storage = Storage::open_in_memory();
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
+        let original = storage.get("my_string").unwrap();
+
+        assert!(storage.add_tag("my_string", "important"));
+        assert!(storage.add_tag("my_string", "draft"));
+        assert!(storage.set_metafield("my_string", "owner", "alice"));
+        assert!(storage.set_description("my_string", "sample"));
+        let item = storage.get("my_string").unwrap();
+        assert_eq!(item.data, original.data);
+        assert_eq!(item.id, original.id);
+        assert_eq!(item.version, original.version + 4);
+        assert_eq!(item.tags, Some(vec!["important".into(), "draft".into()]));
+        assert_eq!(item.metafields.unwrap()["owner"], "alice");
+        assert_eq!(item.description.as_deref(), Some("sample"));
+
+        assert!(storage.remove_tag("my_string", "draft"));
+        let item = storage.get("my_string").unwrap();
+        assert_eq!(item.tags, Some(vec!["important".into()]));
+        assert_eq!(item.version, original.version + 5);
+
+        // nothing to modify, the version is kept
+        assert!(!storage.remove_tag("my_string", "draft"));
+        assert_eq!(
+            storage.get("my_string").unwrap().version,
+            original.version + 5
+        );
 
-                    storage_clone.update_inner_object(&object_key, &map);
-                });
-            }
-        });
+        assert!(!storage.add_tag("missing", "important"));
+        assert!(!storage.set_metafield("missing", "owner", "alice"));
+        let value: String = storage.get_inner_object("my_string").unwrap();
+        assert_eq!(value, "abc");
+    }
 
-        // verify and remove storage items in multiple threads
-        thread::scope(|scope| {
-            for thread_number in 0..THREADS_COUNT {
-                let storage_clone = storage.clone();
-                scope.spawn(move || {
-                    let object_key = format!("{}-{}", key_prefix, thread_number);
-                    let map: HashMap<String, String> =
-                        storage_clone.get_inner_object(&object_key).unwrap();
-                    assert!(map.is_empty());
+    #[test]
+    fn set_persistence_test() {
+        let config = temp_storage_config("set_persistence");
+
+        let storage = Storage::open_with_config(config.clone());
+        for key in ["cached", "durable"] {
+            storage
+                .insert(StorageItem::new(key, &key.to_string()).unwrap())
+                .unwrap();
+        }
+        let original = storage.get("durable").unwrap();
+        assert!(matches!(original.persistence, StoragePersistence::Memory));
+
+        // promote
+        assert!(storage.set_persistence("durable", StoragePersistence::Disk));
+        let item = storage.get("durable").unwrap();
+        assert!(matches!(item.persistence, StoragePersistence::Disk));
+        assert_eq!(item.version, original.version + 1);
+        assert_eq!(item.data, original.data);
+        assert!(!storage.set_persistence("missing", StoragePersistence::Disk));
+        storage.flush().unwrap();
+        drop(storage);
+
+        // the mode survives a reopen, then demote
+        let storage = Storage::open_with_config(config.clone());
+        let item = storage.get("durable").unwrap();
+        assert!(matches!(item.persistence, StoragePersistence::Disk));
+        assert!(matches!(
+            storage.get("cached").unwrap().persistence,
+            StoragePersistence::Memory
+        ));
+        assert!(storage.set_persistence("durable", StoragePersistence::Memory));
+        storage.flush().unwrap();
+        drop(storage);
+
+        let storage = Storage::open_with_config(config);
+        let item = storage.get("durable").unwrap();
+        assert!(matches!(item.persistence, StoragePersistence::Memory));
+        assert_eq!(item.version, original.version + 2);
+    }
 
-                    // remove storage object
-                    storage_clone.remove(&object_key);
-                });
-            }
-        });
+    #[test]
+    fn keys_by_type_test() {
+        let storage = Storage::open_in_memory();
+        storage
+            .insert(StorageItem::new("custom", &String::from("abc")).unwrap())
+            .unwrap();
+        storage
+            .insert(
+                StorageItem::with_type(
+                    "name",
+                    ItemType::Basic(BasicType::String),
+                    &String::from("abc"),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        storage
+            .insert(
+                StorageItem::with_type("count", ItemType::Basic(BasicType::U32), &7_u32).unwrap(),
+            )
+            .unwrap();
+        let blob_type = ItemType::Complex(ComplexType::Blob);
+        for key in ["report", "image"] {
+            storage
+                .insert(StorageItem::with_type(key, blob_type.clone(), &vec![1_u8, 2, 3]).unwrap())
+                .unwrap();
+        }
+        storage
+            .insert(
+                StorageItem::with_type(
+                    "config",
+                    ItemType::Complex(ComplexType::Json),
+                    &String::from("{}"),
+                )
+                .unwrap(),
+            )
+            .unwrap();
 
-        // ensure empty storage
-        assert!(storage.keys().is_empty());
+        let mut blobs = storage
+            .keys_by_type(|item_type| matches!(item_type, ItemType::Complex(ComplexType::Blob)));
+        blobs.sort();
+        assert_eq!(blobs, vec!["image", "report"]);
+
+        let json = storage
+            .keys_by_type(|item_type| matches!(item_type, ItemType::Complex(ComplexType::Json)));
+        assert_eq!(json, vec!["config"]);
+
+        let mut basic = storage.keys_by_type(|item_type| matches!(item_type, ItemType::Basic(_)));
+        basic.sort();
+        assert_eq!(basic, vec!["count", "name"]);
+
+        let custom = storage.keys_by_type(|item_type| matches!(item_type, ItemType::Custom));
+        assert_eq!(custom, vec!["custom"]);
     }
 
     #[test]
-    fn storage_flush_load_test() {
-        use std::fs;
-        use std::path::Path;
+    fn changes_since_test() {
+        let storage = Storage::open_in_memory();
+        assert_eq!(storage.change_seq(), 0);
 
-        let mut storage = Storage::open();
+        storage
+            .insert(StorageItem::new("first", &1_u8).unwrap())
+            .unwrap();
+        storage
+            .insert(StorageItem::new("second", &2_u8).unwrap())
+            .unwrap();
+        let seq = storage.change_seq();
+        assert_eq!(seq, 2);
 
-        // clean up the storage
-        storage.clear();
+        storage
+            .insert(StorageItem::new("third", &3_u8).unwrap())
+            .unwrap();
+        assert!(storage.update_inner_object("first", &10_u8));
+        assert!(storage.remove("second"));
+        // a missing item is not a change
+        assert!(!storage.remove("missing"));
+
+        let (changes, current_seq) = storage.changes_since(seq);
+        assert_eq!(current_seq, 5);
+        let changes: Vec<(u64, &str, ChangeKind)> = changes
+            .iter()
+            .map(|change| (change.seq, change.key.as_str(), change.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (3, "third", ChangeKind::Set),
+                (4, "first", ChangeKind::Set),
+                (5, "second", ChangeKind::Removed),
+            ]
+        );
 
-        assert_eq!(storage.flush(), Ok(()));
+        let (changes, current_seq) = storage.changes_since(current_seq);
+        assert!(changes.is_empty());
+        assert_eq!(current_seq, 5);
+    }
 
-        // check the storage info is empty
-        let result = storage.load_storage_info();
-        assert!(result.is_ok());
-        assert!(result.unwrap().is_empty());
+    #[test]
+    fn get_many_objects_test() {
+        let storage = Storage::open_in_memory();
+        for (key, value) in [("first", "one"), ("second", "two"), ("third", "three")] {
+            storage
+                .insert(StorageItem::new(key, &value.to_string()).unwrap())
+                .unwrap();
+        }
+        // the encoded map does not decode as a string, the key byte 255 is not valid UTF-8
+        let map = HashMap::from([(255_u8, 1_u64)]);
+        storage
+            .insert(StorageItem::new("map", &map).unwrap())
+            .unwrap();
 
-        let storage_data_path = storage.get_storage_data_path();
+        let keys: Vec<String> = ["first", "second", "third", "map", "missing"]
+            .iter()
+            .map(|key| key.to_string())
+            .collect();
+        let objects = storage.get_many_objects::<String>(&keys);
+        assert_eq!(objects.len(), 3);
+        assert_eq!(objects["first"], "one");
+        assert_eq!(objects["second"], "two");
+        assert_eq!(objects["third"], "three");
+    }
 
-        // check the storage blob directory exists
-        assert!(Path::new(&storage_data_path).exists());
+    #[test]
+    fn get_or_default_test() {
+        let storage = Storage::open_in_memory();
+        storage
+            .insert(StorageItem::new("retries", &5_u32).unwrap())
+            .unwrap();
+        storage
+            .insert(StorageItem::new("name", &"anor".to_string()).unwrap())
+            .unwrap();
 
-        // check the storage blob directory is empty
-        let paths = fs::read_dir(&storage_data_path).unwrap();
-        assert_eq!(paths.count(), 0);
+        assert_eq!(storage.get_or_default::<u32>("retries"), 5);
+        assert_eq!(storage.get_or_default::<u32>("missing"), 0);
+        assert_eq!(storage.get_or_default::<String>("name"), "anor");
+        assert_eq!(storage.get_or_default::<String>("missing"), "");
 
-        let key = "my_map1";
-        let mut my_map1 = HashMap::<String, String>::new();
-        my_map1.insert("1".into(), "One".into());
-        my_map1.insert("2".into(), "Two".into());
-        my_map1.insert("3".into(), "Three".into());
+        // the encoded map does not decode as a string, the key byte 255 is not valid UTF-8
+        let map = HashMap::from([(255_u8, 1_u64)]);
+        storage
+            .insert(StorageItem::new("map", &map).unwrap())
+            .unwrap();
+        assert_eq!(storage.get_or_default::<String>("map"), "");
+    }
 
-        // insert the map into storage
-        let storage_type =
-            ItemType::Complex(ComplexType::Map(BasicType::String, BasicType::String));
-        let storage_item = StorageItem::with_type(key, storage_type, &my_map1).unwrap();
-        storage.insert(storage_item);
+    #[test]
+    fn open_in_memory_test() {
+        let mut storage = Storage::open_in_memory();
+        assert!(storage.keys().is_empty());
+        assert_eq!(storage.data_path(), Path::new(""));
 
-        // persist the storage
-        assert_eq!(storage.flush(), Ok(()));
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
+        let value: String = storage.get_inner_object("my_string").unwrap();
+        assert_eq!(value, "abc");
 
-        // check the storage info has the map
-        let result = storage.load_storage_info();
-        assert!(result.is_ok());
+        storage
+            .update(StorageItem::new("my_string", &String::from("def")).unwrap())
+            .unwrap();
+        let value: String = storage.get_inner_object("my_string").unwrap();
+        assert_eq!(value, "def");
 
-        let storage_info = result.unwrap();
-        assert!(storage_info.contains_key(key));
+        // flush and load do not touch the disk, the items are kept
+        storage.flush().unwrap();
+        storage.load().unwrap();
+        assert!(storage.contains_key("my_string"));
 
-        // check the storage blob directory exists
-        assert!(Path::new(&storage_data_path).exists());
+        assert!(storage.remove("my_string"));
+        assert!(storage.keys().is_empty());
+    }
 
-        // check the storage blob directory has a single entry
-        let paths = fs::read_dir(&storage_data_path).unwrap();
-        let entries: Vec<_> = paths.flatten().map(|v| v.file_name()).collect();
-        assert_eq!(entries.len(), 1);
+    #[test]
+    fn flush_every_writes_test() {
+        let data_path = temp_data_path("flush_every_writes");
+        let config = Arc::new(Config {
+            storage: Some(StorageConfig {
+                data_path: data_path.clone(),
+                flush_every_writes: Some(5),
+                ..Default::default()
+            }),
+            api: None,
+            http: None,
+            remote: None,
+        });
+        let persisted_keys = || {
+            let info: Result<HashMap<String, (String, u64)>, Error> =
+                decode_from_file(data_path.join(FILE_STORAGE_INFO));
+            info.map_or(0, |info| info.len())
+        };
 
-        // check the entry id
-        let item_id = storage_info.get(key).unwrap().0.to_ascii_lowercase();
-        assert_eq!(entries[0].to_string_lossy().to_ascii_lowercase(), item_id);
+        let storage = Storage::open_with_config(config);
+        for i in 0..4 {
+            let item = StorageItem::new(&format!("key_{}", i), &i).unwrap();
+            storage.insert(item).unwrap();
+        }
+        assert_eq!(persisted_keys(), 0);
+        assert!(storage.is_dirty());
 
-        // clean up the storage
-        storage.clear();
+        // the fifth write triggers the flush
+        storage
+            .insert(StorageItem::new("key_4", &4).unwrap())
+            .unwrap();
+        assert_eq!(persisted_keys(), 5);
+        assert!(!storage.is_dirty());
 
-        let object_keys = storage.keys();
-        assert!(object_keys.is_empty());
+        // the counter restarts after the flush
+        storage.remove("key_0");
+        assert_eq!(persisted_keys(), 5);
+        assert!(storage.is_dirty());
+    }
 
-        // load storage
-        assert_eq!(storage.load(), Ok(()));
+    #[test]
+    fn duplicate_id_test() {
+        let config = temp_storage_config("duplicate_id");
+        let data_path = config.storage.as_ref().unwrap().data_path.clone();
+
+        let storage = Storage::open_with_config(config);
+        let mut first = StorageItem::new("first", &1_u8).unwrap();
+        first.id = "forced".to_string();
+        let mut second = StorageItem::new("second", &2_u8).unwrap();
+        second.id = "FORCED".to_string();
+        storage.insert(first).unwrap();
+        storage.insert(second).unwrap();
+
+        // neither blob file is written
+        let err = storage.flush().unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::DuplicateId { key, other_key, .. } if key == "first" && other_key == "second"
+        ));
+        assert!(storage.is_dirty());
+        assert!(!data_path.join(DIR_STORAGE_DATA).join("forced").exists());
+
+        // the flush succeeds once the collision is resolved
+        storage.remove("second");
+        storage.flush().unwrap();
+        assert!(data_path.join(DIR_STORAGE_DATA).join("forced").exists());
+    }
 
-        // verify loaded storage
-        let object_keys = storage.keys();
-        assert_eq!(object_keys.len(), 1);
-        assert_eq!(object_keys[0], key);
+    /// Returns a fresh directory under `target/tmp` for the files of a test
+    fn temp_data_path(name: &str) -> PathBuf {
+        let data_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("tmp")
+            .join(name);
+        _ = std::fs::remove_dir_all(&data_path);
+        data_path
+    }
 
-        let map: HashMap<String, String> = storage.get_inner_object(key).unwrap();
-        assert_eq!(my_map1, map);
+    /// Returns the configuration of a storage persisted in a fresh directory under `target/tmp`
+    fn temp_storage_config(name: &str) -> Arc<Config> {
+        Arc::new(Config {
+            storage: Some(StorageConfig {
+                data_path: temp_data_path(name),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
 
-        // clean up the storage
-        storage.clear();
+    fn storage_path() -> PathBuf {
+        let config = config::load();
+        config.storage.as_ref().unwrap().data_path.clone()
+    }
+
+    fn load_storage_info() -> Result<HashMap<String, (String, u64)>, Error> {
+        decode_from_file(storage_path().join(FILE_STORAGE_INFO))
     }
 }
@@ -0,0 +1,158 @@
+//! Single-file archive of the storage items
+//! The archive is a stream of item packets closed by a trailer packet holding the number of items,
+//! so a truncated archive is detected on import
+
+use super::{storage_codec::*, storage_item::StorageItem, storage_packet::*};
+use std::io::{self, Read, Write};
+
+/// Size of the packet length prefix in bytes
+const PACKET_LENGTH_SIZE: usize = 8;
+
+/// Writes the items and the trailer into the archive stream
+/// Returns the number of bytes written
+pub fn write_archive<W: Write>(w: &mut W, items: &[StorageItem]) -> io::Result<usize> {
+    let encoded_items = items
+        .iter()
+        .map(encode_archive_item)
+        .collect::<io::Result<Vec<_>>>()?;
+    write_encoded_archive(w, &encoded_items)
+}
+
+/// Encodes the item as an archive packet, e.g. to encode the items while the storage is locked
+/// and write them into the stream afterwards
+pub fn encode_archive_item(item: &StorageItem) -> io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    write_packet(&mut buf, item, StroragePacketType::StrorageItem)?;
+    Ok(buf)
+}
+
+/// Writes the items encoded by [`encode_archive_item`] and the trailer into the archive stream
+/// Returns the number of bytes written
+pub fn write_encoded_archive<W: Write>(w: &mut W, encoded_items: &[Vec<u8>]) -> io::Result<usize> {
+    let mut bytes_written = 0;
+    for encoded_item in encoded_items {
+        w.write_all(encoded_item)?;
+        bytes_written += encoded_item.len();
+    }
+    let item_count = encoded_items.len() as u64;
+    bytes_written += write_packet(w, &item_count, StroragePacketType::StrorageArchiveTrailer)?;
+    w.flush()?;
+    Ok(bytes_written)
+}
+
+/// Reads the items of the archive stream up to the trailer
+/// Returns an `InvalidData` error if a packet is malformed or the item count does not match the trailer
+pub fn read_archive<R: Read>(r: &mut R) -> io::Result<Vec<StorageItem>> {
+    let mut items = vec![];
+    loop {
+        let packet = read_packet(r)?;
+        match packet.header.packet_type {
+            StroragePacketType::StrorageItem => items.push(decode_packet(&packet)?),
+            StroragePacketType::StrorageArchiveTrailer => {
+                let item_count: u64 = decode_packet(&packet)?;
+                if item_count != items.len() as u64 {
+                    return Err(invalid_data(format!(
+                        "Archive item count mismatch, expected: {}, found: {}",
+                        item_count,
+                        items.len()
+                    )));
+                }
+                return Ok(items);
+            }
+            packet_type => {
+                return Err(invalid_data(format!(
+                    "Unexpected packet in archive: {:?}",
+                    packet_type
+                )))
+            }
+        }
+    }
+}
+
+fn write_packet<W: Write, T: bincode::Encode>(
+    w: &mut W,
+    obj: &T,
+    packet_type: StroragePacketType,
+) -> io::Result<usize> {
+    let codec_type = StrorageCodecType::default();
+    let buf = match encode_to_binary(obj, codec_type) {
+        Some(buf) => buf,
+        None => return Err(invalid_data(format!("Could not encode {:?}", packet_type))),
+    };
+    let packet = build_storage_packet(buf, packet_type, codec_type);
+    let header = packet.header.to_vec();
+    w.write_all(&header)?;
+    w.write_all(&packet.data)?;
+    Ok(header.len() + packet.data.len())
+}
+
+fn read_packet<R: Read>(r: &mut R) -> io::Result<StroragePacket> {
+    let mut buf = vec![0_u8; PACKET_LENGTH_SIZE];
+    r.read_exact(&mut buf)?;
+    let mut packet_length_arr = [0_u8; PACKET_LENGTH_SIZE];
+    packet_length_arr.copy_from_slice(&buf);
+    let packet_length = u64::from_be_bytes(packet_length_arr);
+
+    // the rest is read without trusting the length for an allocation up front
+    let rest_length = packet_length.saturating_sub(PACKET_LENGTH_SIZE as u64);
+    r.take(rest_length).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < packet_length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Archive is truncated",
+        ));
+    }
+    parse_packet(buf).map_err(invalid_data)
+}
+
+fn decode_packet<T: bincode::Decode>(packet: &StroragePacket) -> io::Result<T> {
+    let header = &packet.header;
+    if !codec_enabled(header.codec_type)
+        || header.codec_config_version != codec_config_version(header.codec_type)
+    {
+        return Err(invalid_data(format!(
+            "Unsupported codec {:?} of config version {}",
+            header.codec_type, header.codec_config_version
+        )));
+    }
+    match decode_from_binary(&packet.data, header.codec_type) {
+        Some(obj) => Ok(obj),
+        None => Err(invalid_data(format!(
+            "Could not decode {:?}",
+            header.packet_type
+        ))),
+    }
+}
+
+fn invalid_data(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_archive_test() {
+        let items = vec![
+            StorageItem::new("first", &1_u32).unwrap(),
+            StorageItem::new("second", &2_u32).unwrap(),
+        ];
+        let mut buf = vec![];
+        let bytes_written = write_archive(&mut buf, &items).unwrap();
+        assert_eq!(bytes_written, buf.len());
+        assert_eq!(read_archive(&mut buf.as_slice()).unwrap().len(), 2);
+
+        // the trailer is missing
+        let truncated = &buf[..buf.len() - 1];
+        let err = read_archive(&mut &truncated[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // an item packet is missing
+        let mut buf = vec![];
+        write_packet(&mut buf, &items[0], StroragePacketType::StrorageItem).unwrap();
+        write_packet(&mut buf, &2_u64, StroragePacketType::StrorageArchiveTrailer).unwrap();
+        let err = read_archive(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Mutation recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOp {
+    Insert,
+    Update,
+    Remove,
+    Clear,
+}
+
+impl AuditOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditOp::Insert => "insert",
+            AuditOp::Update => "update",
+            AuditOp::Remove => "remove",
+            AuditOp::Clear => "clear",
+        }
+    }
+}
+
+/// Append-only log of the storage mutations for forensics, one JSON object per line
+/// Unlike persisted data, the log is never read back by the storage
+pub struct AuditLog {
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    /// Opens the audit log for appending, the file is created if not exists
+    pub fn open(filepath: &Path) -> Result<Self, String> {
+        match OpenOptions::new().create(true).append(true).open(filepath) {
+            Ok(file) => Ok(AuditLog {
+                file: Mutex::new(file),
+            }),
+            Err(err) => {
+                let err = format!(
+                    "Could not open the audit log: `{}`, Error Message: {}",
+                    filepath.to_string_lossy(),
+                    err
+                );
+                tracing::error!("{}", err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Appends a record of the mutation
+    /// `ts` is the timestamp in milliseconds, `key` and `principal` are `null` if not applicable
+    pub fn record(&self, op: AuditOp, key: Option<&str>, principal: Option<&str>) {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+        let line = format!(
+            "{{\"ts\":{},\"op\":\"{}\",\"key\":{},\"principal\":{}}}\n",
+            ts,
+            op.name(),
+            json_value(key),
+            json_value(principal)
+        );
+
+        let mut file = match self.file.lock() {
+            Ok(file) => file,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            tracing::error!("Could not write the audit log: {}", err);
+        }
+    }
+}
+
+/// Formats an optional string as a JSON value
+fn json_value(value: Option<&str>) -> String {
+    let value = match value {
+        Some(value) => value,
+        None => return "null".to_string(),
+    };
+
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_value_test() {
+        assert_eq!(json_value(None), "null");
+        assert_eq!(json_value(Some("user:1")), "\"user:1\"");
+        assert_eq!(
+            json_value(Some("a\"b\\c\nd\u{1}")),
+            "\"a\\\"b\\\\c\\nd\\u0001\""
+        );
+    }
+}
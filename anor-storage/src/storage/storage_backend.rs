@@ -0,0 +1,74 @@
+use super::{storage_error::Error, storage_item::StorageItem};
+
+/// Storage Backend
+/// Defines the engine that keeps the storage items and persists them
+pub trait StorageBackend: Send {
+    /// Gets an item corresponding to the key
+    fn get(&self, key: &str) -> Option<StorageItem>;
+
+    /// Returns whether an item with the key is present
+    fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the payload size in bytes of the item corresponding to the key
+    fn item_len(&self, key: &str) -> Option<usize> {
+        self.get(key).map(|item| item.data.len())
+    }
+
+    /// Inserts an item, replacing an item with the same key if present
+    fn insert(&mut self, storage_item: StorageItem);
+
+    /// Removes an item, returns whether an item with the key was present
+    fn remove(&mut self, key: &str) -> bool;
+
+    /// Removes all items
+    fn clear(&mut self);
+
+    /// Reserves capacity for at least `additional` more items, a hint backends may ignore
+    fn reserve(&mut self, _additional: usize) {}
+
+    /// Returns the keys of the items
+    fn keys(&self) -> Vec<String>;
+
+    /// Visits the items by reference, so scans do not copy every item
+    /// The default implementation visits copies, backends keeping the items in memory avoid them
+    fn for_each(&self, f: &mut dyn FnMut(&StorageItem)) {
+        for key in self.keys() {
+            if let Some(item) = self.get(&key) {
+                f(&item);
+            }
+        }
+    }
+
+    /// Loads persisted items, replacing the current items
+    fn load(&mut self) -> Result<(), Error>;
+
+    /// Returns a snapshot of the items to persist
+    fn snapshot(&self) -> Box<dyn StorageSnapshot>;
+
+    /// Persists the items
+    fn flush(&mut self) -> Result<FlushReport, Error> {
+        self.snapshot().flush()
+    }
+}
+
+/// Storage Snapshot
+/// Point-in-time copy of the items, persisted without holding the backend
+pub trait StorageSnapshot: Send {
+    /// Persists the items of the snapshot
+    fn flush(self: Box<Self>) -> Result<FlushReport, Error>;
+}
+
+/// Summary of the work done by a flush
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushReport {
+    /// number of items persisted as new or changed
+    pub items_written: usize,
+    /// number of items unchanged since the previous flush
+    pub items_skipped: usize,
+    /// number of blob files of removed items deleted
+    pub files_removed: usize,
+    /// number of bytes written, including the storage info
+    pub bytes_written: u64,
+}
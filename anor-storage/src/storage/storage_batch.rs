@@ -0,0 +1,53 @@
+use super::storage_item::StorageItem;
+use super::Storage;
+
+/// Operation accumulated by a write batch
+pub(crate) enum BatchOp {
+    Insert(StorageItem),
+    Remove(String),
+}
+
+/// Inserts and removals applied to the storage at once on commit
+/// A batch dropped without commit discards its operations
+pub struct WriteBatch<'a> {
+    storage: &'a Storage,
+    ops: Vec<BatchOp>,
+}
+
+impl<'a> WriteBatch<'a> {
+    pub(crate) fn new(storage: &'a Storage) -> Self {
+        WriteBatch {
+            storage,
+            ops: vec![],
+        }
+    }
+
+    /// Adds an insert of the item, replacing an item with the same key on commit
+    pub fn insert(&mut self, storage_item: StorageItem) -> &mut Self {
+        self.ops.push(BatchOp::Insert(storage_item));
+        self
+    }
+
+    /// Adds a removal of the item corresponding to the key
+    pub fn remove(&mut self, key: &str) -> &mut Self {
+        self.ops.push(BatchOp::Remove(key.to_string()));
+        self
+    }
+
+    /// Returns the number of accumulated operations
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns whether the batch has no operations
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// Applies the operations under the global lock and flushes the storage once
+    /// Nothing is applied if a key does not comply with the key policy, the storage is read-only
+    /// or the batch would exceed the maximum payload size of the storage
+    pub fn commit(self) -> Result<(), String> {
+        self.storage.commit_batch(self.ops)
+    }
+}
@@ -0,0 +1,94 @@
+use super::storage_watch::ChangeKind;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Change of a storage item along with its sequence number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeRecord {
+    pub seq: u64,
+    pub key: String,
+    pub kind: ChangeKind,
+}
+
+/// Bounded log of the latest changes, numbered by a monotonically increasing sequence
+pub struct ChangeLog {
+    capacity: usize,
+    inner: Mutex<ChangeLogInner>,
+}
+
+#[derive(Default)]
+struct ChangeLogInner {
+    seq: u64,
+    records: VecDeque<ChangeRecord>,
+}
+
+impl ChangeLog {
+    pub fn with_capacity(capacity: usize) -> Self {
+        ChangeLog {
+            capacity,
+            inner: Mutex::new(ChangeLogInner::default()),
+        }
+    }
+
+    /// Records the change under the next sequence number, dropping the oldest record if full
+    pub fn record(&self, key: &str, kind: ChangeKind) {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        inner.seq += 1;
+        let seq = inner.seq;
+        if inner.records.len() >= self.capacity {
+            inner.records.pop_front();
+        }
+        inner.records.push_back(ChangeRecord {
+            seq,
+            key: key.to_string(),
+            kind,
+        });
+    }
+
+    /// Returns the current sequence number, zero if nothing changed yet
+    pub fn seq(&self) -> u64 {
+        match self.inner.lock() {
+            Ok(inner) => inner.seq,
+            Err(poisoned) => poisoned.into_inner().seq,
+        }
+    }
+
+    /// Returns the changes with a sequence number above `seq` and the current sequence number
+    pub fn changes_since(&self, seq: u64) -> (Vec<ChangeRecord>, u64) {
+        let inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let changes = inner
+            .records
+            .iter()
+            .filter(|record| record.seq > seq)
+            .cloned()
+            .collect();
+        (changes, inner.seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_test() {
+        let change_log = ChangeLog::with_capacity(2);
+        change_log.record("a", ChangeKind::Set);
+        change_log.record("b", ChangeKind::Set);
+        change_log.record("a", ChangeKind::Removed);
+
+        // the oldest change is dropped, the sequence keeps counting
+        let (changes, seq) = change_log.changes_since(0);
+        assert_eq!(seq, 3);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].seq, 2);
+        assert_eq!(changes[1].key, "a");
+        assert_eq!(changes[1].kind, ChangeKind::Removed);
+    }
+}
@@ -1,26 +1,65 @@
-use super::storage_packet::*;
+use super::{storage_const::TEMP_FILE_EXTENSION, storage_error::Error, storage_packet::*};
 use std::{
     fs::File,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
+/// Version of the bincode configuration, recorded in the packet header
+/// Must be incremented whenever [`bincode_config`] changes, so persisted data encoded with another configuration is detected
+pub const BINCODE_CONFIG_VERSION: u8 = 1;
+
+/// Returns the bincode configuration used to encode and decode objects
+pub fn bincode_config() -> bincode::config::Configuration {
+    bincode::config::standard()
+}
+
+/// Returns the configuration version of the codec, `0` for codecs not supported yet
+pub fn codec_config_version(codec_type: StrorageCodecType) -> u8 {
+    match codec_type {
+        StrorageCodecType::Bincode => BINCODE_CONFIG_VERSION,
+        _ => 0,
+    }
+}
+
+/// Returns whether the codec is enabled in this build
+pub fn codec_enabled(codec_type: StrorageCodecType) -> bool {
+    matches!(codec_type, StrorageCodecType::Bincode)
+}
+
+/// Error of decoding an inner object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    /// the data does not decode as the requested type
+    Decode(String),
+    /// the codec is not supported in this build
+    Unsupported(String),
+}
+
+impl std::fmt::Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Decode(reason) => write!(f, "Could not decode the object: {}", reason),
+            CodecError::Unsupported(codec) => write!(f, "Codec {} not supported yet", codec),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
 /// encode object into binary array `[u8]`
 pub fn encode_to_binary<T: bincode::Encode>(
     obj: &T,
     codec_type: StrorageCodecType,
 ) -> Option<Vec<u8>> {
     match codec_type {
-        StrorageCodecType::Bincode => {
-            let bincode_config = bincode::config::standard();
-            match bincode::encode_to_vec(obj, bincode_config) {
-                Ok(arr) => Some(arr),
-                Err(msg) => {
-                    tracing::error!("Object to Binary encode error: {}", msg.to_string());
-                    None
-                }
+        StrorageCodecType::Bincode => match bincode::encode_to_vec(obj, bincode_config()) {
+            Ok(arr) => Some(arr),
+            Err(msg) => {
+                tracing::error!("Object to Binary encode error: {}", msg.to_string());
+                None
             }
-        }
+        },
         _ => {
             tracing::error!("Codec {:?} not supported yet", codec_type);
             None
@@ -34,19 +73,16 @@ pub fn decode_from_binary<T: bincode::Decode>(
     codec_type: StrorageCodecType,
 ) -> Option<T> {
     match codec_type {
-        StrorageCodecType::Bincode => {
-            let bincode_config = bincode::config::standard();
-            match bincode::decode_from_slice(encoded, bincode_config) {
-                Ok(r) => {
-                    let (decoded, _len): (T, usize) = r;
-                    Some(decoded)
-                }
-                Err(msg) => {
-                    tracing::error!("Binary to Object decode error: {}", msg.to_string());
-                    None
-                }
+        StrorageCodecType::Bincode => match bincode::decode_from_slice(encoded, bincode_config()) {
+            Ok(r) => {
+                let (decoded, _len): (T, usize) = r;
+                Some(decoded)
             }
-        }
+            Err(msg) => {
+                tracing::error!("Binary to Object decode error: {}", msg.to_string());
+                None
+            }
+        },
         _ => {
             tracing::error!("Codec {:?} not supported yet", codec_type);
             None
@@ -54,77 +90,263 @@ pub fn decode_from_binary<T: bincode::Decode>(
     }
 }
 
+/// Decodes object from binary array slice `[u8]`, the whole data needs to be one encoded object
+/// Unlike [`decode_from_binary`], data of another type is reported even if its leading bytes decode as the object
+pub fn try_decode_from_binary<T: bincode::Decode>(
+    encoded: &[u8],
+    codec_type: StrorageCodecType,
+) -> Result<T, CodecError> {
+    match codec_type {
+        StrorageCodecType::Bincode => match bincode::decode_from_slice(encoded, bincode_config()) {
+            Ok((decoded, len)) if len == encoded.len() => Ok(decoded),
+            Ok((_, len)) => Err(CodecError::Decode(format!(
+                "{} trailing bytes left",
+                encoded.len() - len
+            ))),
+            Err(err) => Err(CodecError::Decode(err.to_string())),
+        },
+        _ => Err(CodecError::Unsupported(format!("{:?}", codec_type))),
+    }
+}
+
 /// Encodes the object and persists in file
+/// Returns the number of bytes written
 pub fn encode_to_file<T: bincode::Encode>(
     filepath: PathBuf,
     obj: &T,
     packet_type: StroragePacketType,
-) -> Result<(), String> {
+) -> Result<usize, Error> {
     let codec_type = StrorageCodecType::default();
-    if let Some(buf) = encode_to_binary(obj, codec_type) {
-        match File::create(&filepath) {
-            Ok(mut file) => {
-                // build packet
-                let packet = build_storage_packet(buf, packet_type, codec_type);
-
-                // write packet header
-                if let Err(err) = file.write_all(&packet.header.to_vec()) {
-                    return Err(format!(
-                        "Could not write into file: `{}`, Error Message: {}",
-                        filepath.to_string_lossy(),
-                        err
-                    ));
-                }
-
-                // write packet data
-                if let Err(err) = file.write_all(&packet.data) {
-                    return Err(format!(
-                        "Could not write into file: `{}`, Error Message: {}",
-                        filepath.to_string_lossy(),
-                        err
-                    ));
-                }
-            }
-            Err(err) => {
-                return Err(format!(
-                    "Could not create file: `{}`, Error Message: {}",
-                    filepath.to_string_lossy(),
-                    err
-                ));
-            }
+    let buf = match encode_to_binary(obj, codec_type) {
+        Some(buf) => buf,
+        None => return Err(Error::Encode(filepath)),
+    };
+    let mut file = match File::create(&filepath) {
+        Ok(file) => file,
+        Err(err) => return Err(Error::io(filepath, err)),
+    };
+
+    // build packet
+    let packet = build_storage_packet(buf, packet_type, codec_type);
+    let header = packet.header.to_vec();
+
+    // write packet header
+    if let Err(err) = file.write_all(&header) {
+        return Err(Error::io(filepath, err));
+    }
+
+    // write packet data
+    if let Err(err) = file.write_all(&packet.data) {
+        return Err(Error::io(filepath, err));
+    }
+
+    Ok(header.len() + packet.data.len())
+}
+
+/// Encodes object into a temporary file of the temp path and renames it to the file path
+/// The file path either keeps its previous content or gets the complete new content
+/// The temp path needs to be on the same file system as the file path
+pub fn encode_to_file_atomic<T: bincode::Encode>(
+    filepath: PathBuf,
+    temp_path: &Path,
+    obj: &T,
+    packet_type: StroragePacketType,
+) -> Result<usize, Error> {
+    let filename = match filepath.file_name() {
+        Some(filename) => filename.to_string_lossy(),
+        None => {
+            return Err(Error::Other(format!(
+                "Not a file: `{}`",
+                filepath.to_string_lossy()
+            )))
         }
-    } else {
-        return Err("Could not encode object!".to_string());
+    };
+    let temp_filepath = temp_path.join(format!("{}.{}", filename, TEMP_FILE_EXTENSION));
+
+    let bytes_written = encode_to_file(temp_filepath.clone(), obj, packet_type)?;
+    if let Err(err) = std::fs::rename(&temp_filepath, &filepath) {
+        _ = std::fs::remove_file(&temp_filepath);
+        return Err(Error::io(filepath, err));
     }
-    Ok(())
+    Ok(bytes_written)
 }
 
 /// Loads and decodes object from file
-pub fn decode_from_file<T: bincode::Decode>(filepath: PathBuf) -> Result<T, String> {
-    if let Ok(mut file) = File::open(&filepath) {
-        let mut buf = vec![];
-        match file.read_to_end(&mut buf) {
-            Ok(_) => match parse_packet(buf) {
-                Ok(packet) => {
-                    if let Some(obj) = decode_from_binary(&packet.data, packet.header.codec_type) {
-                        return Ok(obj);
-                    }
-                }
-                Err(err) => {
-                    return Err(err);
-                }
-            },
-            Err(err) => {
-                return Err(format!(
-                    "Could not read file: `{}`, Error Message: {}",
-                    filepath.to_string_lossy(),
-                    err
-                ));
+pub fn decode_from_file<T: bincode::Decode>(filepath: PathBuf) -> Result<T, Error> {
+    let mut file = match File::open(&filepath) {
+        Ok(file) => file,
+        Err(err) => return Err(Error::io(filepath, err)),
+    };
+    let mut buf = vec![];
+    if let Err(err) = file.read_to_end(&mut buf) {
+        return Err(Error::io(filepath, err));
+    }
+
+    let packet = match parse_packet(buf) {
+        Ok(packet) => packet,
+        Err(reason) => {
+            return Err(Error::Decode {
+                path: filepath,
+                reason,
+            })
+        }
+    };
+    let header = &packet.header;
+    if !codec_enabled(header.codec_type) {
+        let reason = format!(
+            "requires codec {:?} which is not enabled in this build",
+            header.codec_type
+        );
+        return Err(Error::Decode {
+            path: filepath,
+            reason,
+        });
+    }
+    let expected_version = codec_config_version(header.codec_type);
+    if header.codec_config_version != expected_version {
+        let reason = format!(
+            "Unsupported codec config version {} of {:?}, expected version: {}",
+            header.codec_config_version, header.codec_type, expected_version
+        );
+        return Err(Error::Decode {
+            path: filepath,
+            reason,
+        });
+    }
+    match decode_from_binary(&packet.data, header.codec_type) {
+        Some(obj) => Ok(obj),
+        None => {
+            let reason = "the data does not decode into the object".to_string();
+            Err(Error::Decode {
+                path: filepath,
+                reason,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codec_config_version_test() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("tmp")
+            .join("codec_config_version");
+        std::fs::create_dir_all(&dir).unwrap();
+        let filepath = dir.join("object");
+
+        let obj = String::from("abc");
+        encode_to_file(
+            filepath.clone(),
+            &obj,
+            StroragePacketType::StrorageItemObject,
+        )
+        .unwrap();
+
+        let mut buf = std::fs::read(&filepath).unwrap();
+        let header = parse_packet_header(&buf).unwrap();
+        assert_eq!(header.codec_config_version, BINCODE_CONFIG_VERSION);
+        let decoded: String = decode_from_file(filepath.clone()).unwrap();
+        assert_eq!(decoded, obj);
+
+        // unknown codec config version
+        buf[11] = BINCODE_CONFIG_VERSION + 1;
+        std::fs::write(&filepath, &buf).unwrap();
+        let result: Result<String, Error> = decode_from_file(filepath.clone());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unsupported codec config version"));
+
+        // packets of version 1 are decoded with the initial codec config
+        let data = encode_to_binary(&obj, StrorageCodecType::Bincode).unwrap();
+        let mut buf = ((data.len() + 11) as u64).to_be_bytes().to_vec();
+        buf.extend_from_slice(&[StroragePacketType::StrorageItemObject as u8, 1, 1]);
+        buf.extend_from_slice(&data);
+        std::fs::write(&filepath, &buf).unwrap();
+        let decoded: String = decode_from_file(filepath).unwrap();
+        assert_eq!(decoded, obj);
+    }
+
+    #[test]
+    fn encode_to_file_atomic_test() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("tmp")
+            .join("encode_to_file_atomic");
+        _ = std::fs::remove_dir_all(&dir);
+        let temp_path = dir.join("temp");
+        std::fs::create_dir_all(&temp_path).unwrap();
+        let filepath = dir.join("object");
+
+        let obj = String::from("abc");
+        encode_to_file_atomic(
+            filepath.clone(),
+            &temp_path,
+            &obj,
+            StroragePacketType::StrorageItemObject,
+        )
+        .unwrap();
+        let decoded: String = decode_from_file(filepath.clone()).unwrap();
+        assert_eq!(decoded, obj);
+        assert_eq!(std::fs::read_dir(&temp_path).unwrap().count(), 0);
+
+        // the write goes through the temp path, the previous content is kept on failure
+        let missing_temp_path = dir.join("missing");
+        let result = encode_to_file_atomic(
+            filepath.clone(),
+            &missing_temp_path,
+            &String::from("xyz"),
+            StroragePacketType::StrorageItemObject,
+        );
+        assert!(matches!(result, Err(Error::NotFound(_))));
+        let decoded: String = decode_from_file(filepath).unwrap();
+        assert_eq!(decoded, obj);
+    }
+
+    #[test]
+    fn error_variants_test() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("tmp")
+            .join("codec_error_variants");
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // missing file
+        let missing = dir.join("missing");
+        let result: Result<String, Error> = decode_from_file(missing.clone());
+        match result {
+            Err(Error::NotFound(path)) => assert_eq!(path, missing),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // decode failure
+        let garbage = dir.join("garbage");
+        std::fs::write(&garbage, b"not a packet").unwrap();
+        let result: Result<String, Error> = decode_from_file(garbage.clone());
+        match result {
+            Err(Error::Decode { path, .. }) => assert_eq!(path, garbage),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // I/O error, a directory cannot be read as a file
+        let result: Result<String, Error> = decode_from_file(dir.clone());
+        match result {
+            Err(err @ Error::Io { .. }) => {
+                assert!(std::error::Error::source(&err).is_some());
+                assert!(err.to_string().contains("codec_error_variants"));
             }
+            other => panic!("unexpected result: {:?}", other),
         }
+        let result = encode_to_file(
+            dir.clone(),
+            &String::from("abc"),
+            StroragePacketType::StrorageItemObject,
+        );
+        assert!(matches!(result, Err(Error::Io { .. })));
     }
-    Err(format!(
-        "Could not open file: {}",
-        filepath.to_string_lossy()
-    ))
 }
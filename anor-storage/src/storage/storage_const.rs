@@ -1,6 +1,23 @@
-// lock attempt timout for opening a new storage instance
-pub const INSTANCE_LOCK_TIMEOUT_MILLISECONDS: u32 = 5000; 
+// lock attempt interval for opening a new storage instance
+pub const INSTANCE_LOCK_POLL_INTERVAL_MILLISECONDS: u64 = 50;
+
+// progress logging interval while waiting for the lock held by another instance
+pub const INSTANCE_LOCK_LOG_INTERVAL_MILLISECONDS: u64 = 1000;
 
 pub const FILE_STORAGE_INFO: &str = "storage-info";
 pub const FILE_STORAGE_LOCK: &str = "storage-lock";
-pub const DIR_STORAGE_DATA: &str = "storage";
\ No newline at end of file
+pub const FILE_STORAGE_AUDIT_LOG: &str = "audit.log";
+pub const DIR_STORAGE_DATA: &str = "storage";
+pub const DIR_STORAGE_QUARANTINE: &str = "quarantine";
+
+// extension of the temporary files of atomic writes, such files are never taken as item files
+pub const TEMP_FILE_EXTENSION: &str = "tmp";
+
+// number of the latest changes kept to answer `Storage::changes_since`
+pub const CHANGE_LOG_CAPACITY: usize = 4096;
+
+// number of changes buffered for an async subscriber, a subscriber falling further behind lags
+pub const CHANGE_BROADCAST_CAPACITY: usize = 1024;
+
+// upper bound of the replications of an item in the cluster
+pub const MAX_ITEM_REDUNDANCY: u8 = 16;
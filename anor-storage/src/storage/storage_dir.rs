@@ -0,0 +1,92 @@
+//! Import of directory trees into the storage
+//! Files are stored as blob items keyed by the prefix and their `/`-separated path relative to the directory
+
+use notify::RecommendedWatcher;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Watches a directory imported by [`Storage::watch_dir`](crate::Storage::watch_dir),
+/// keeping the storage in sync with the files on disk until dropped
+pub struct DirWatcher {
+    pub(crate) dir: PathBuf,
+    pub(crate) prefix: String,
+    pub(crate) _watcher: RecommendedWatcher,
+}
+
+impl DirWatcher {
+    /// Returns the watched directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the prefix of the keys of the imported files
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+}
+
+/// Returns the key of the file or directory path within the directory
+/// Returns `None` if the path is not below the directory or not valid unicode
+pub fn dir_item_key(dir: &Path, path: &Path, prefix: &str) -> Option<String> {
+    let relative = path.strip_prefix(dir).ok()?;
+    let mut key = prefix.to_string();
+    let mut separated = prefix.is_empty() || prefix.ends_with('/');
+    for component in relative.components() {
+        match component {
+            Component::Normal(name) => {
+                if !separated {
+                    key.push('/');
+                }
+                key.push_str(name.to_str()?);
+                separated = false;
+            }
+            _ => return None,
+        }
+    }
+    Some(key)
+}
+
+/// Returns the paths of the files of the directory tree, sorted
+pub fn dir_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                dirs.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_item_key_test() {
+        let dir = Path::new("/srv/site");
+        let path = Path::new("/srv/site/css/main.css");
+        assert_eq!(
+            dir_item_key(dir, path, "site"),
+            Some("site/css/main.css".to_string())
+        );
+        assert_eq!(
+            dir_item_key(dir, path, "site/"),
+            Some("site/css/main.css".to_string())
+        );
+        assert_eq!(
+            dir_item_key(dir, path, ""),
+            Some("css/main.css".to_string())
+        );
+        assert_eq!(dir_item_key(dir, dir, "site"), Some("site".to_string()));
+        assert_eq!(dir_item_key(dir, Path::new("/srv/other"), "site"), None);
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Storage Error
+/// Failure of loading, persisting, encoding or decoding storage data
+#[derive(Debug)]
+pub enum Error {
+    /// the file does not exist
+    NotFound(PathBuf),
+    /// reading or writing the file failed
+    Io { path: PathBuf, source: io::Error },
+    /// the file is not a valid packet or its data could not be decoded
+    Decode { path: PathBuf, reason: String },
+    /// the object could not be encoded into the file
+    Encode(PathBuf),
+    /// the storage is opened read-only
+    ReadOnly,
+    /// another flush of the storage is in progress
+    FlushInProgress,
+    /// two items of different keys share the id, so they would be persisted into the same file
+    DuplicateId {
+        id: String,
+        key: String,
+        other_key: String,
+    },
+    /// any other failure, e.g. of a custom backend
+    Other(String),
+}
+
+impl Error {
+    /// Returns the error of an I/O operation on the file, a missing file is reported as [`Error::NotFound`]
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        let path = path.into();
+        if source.kind() == io::ErrorKind::NotFound {
+            Error::NotFound(path)
+        } else {
+            Error::Io { path, source }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(path) => write!(f, "File not found: `{}`", path.to_string_lossy()),
+            Error::Io { path, source } => write!(
+                f,
+                "I/O error on file: `{}`, Error Message: {}",
+                path.to_string_lossy(),
+                source
+            ),
+            Error::Decode { path, reason } => write!(
+                f,
+                "Could not decode file: `{}`, {}",
+                path.to_string_lossy(),
+                reason
+            ),
+            Error::Encode(path) => write!(
+                f,
+                "Could not encode object into file: `{}`",
+                path.to_string_lossy()
+            ),
+            Error::ReadOnly => write!(f, "The storage is opened read-only!"),
+            Error::FlushInProgress => write!(f, "Another flush of the storage is in progress!"),
+            Error::DuplicateId { id, key, other_key } => write!(
+                f,
+                "Items `{}` and `{}` share the id `{}`",
+                key, other_key, id
+            ),
+            Error::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message)
+    }
+}
+
+/// Bridge for the callers reporting errors as strings
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.to_string()
+    }
+}
@@ -0,0 +1,647 @@
+use super::storage_backend::{FlushReport, StorageBackend, StorageSnapshot};
+use super::{
+    storage_codec::*, storage_const::*, storage_error::Error, storage_item::*, storage_packet::*,
+};
+use anor_utils::config::{Config, StorageConfig};
+use fs2::FileExt;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, FileType},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+type StorageMap = HashMap<String, StorageItem>;
+type StorageInfo = HashMap<String, (String, u64)>;
+
+/// Storage backend keeping items in memory and persisting them in local files
+pub struct FileBackend {
+    storage_map: StorageMap,
+    storage_path: PathBuf,
+    temp_path: PathBuf,
+    instance_lock: File,
+    read_only: bool,
+    durable: bool,
+}
+
+impl Drop for FileBackend {
+    fn drop(&mut self) {
+        self.unlock();
+    }
+}
+
+impl FileBackend {
+    /// Opens the local storage path for exclusive access
+    /// Persisted data is not loaded, see [`StorageBackend::load`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage could not be opened, see [`FileBackend::try_open`] for a non-panicking variant.
+    pub fn open(config: Arc<Config>) -> Self {
+        match Self::try_open(config) {
+            Ok(backend) => backend,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Opens the local storage path for exclusive access
+    /// If another instance holds the storage, waits up to `lock_wait` of the storage config for its release
+    pub fn try_open(config: Arc<Config>) -> Result<Self, String> {
+        Self::try_open_with_mode(config, false)
+    }
+
+    /// Opens the local storage path for shared read-only access
+    /// Read-only instances coexist, while an instance with exclusive access waits for them to close
+    /// The storage path is expected to be created by an instance with exclusive access already
+    pub fn try_open_read_only(config: Arc<Config>) -> Result<Self, String> {
+        Self::try_open_with_mode(config, true)
+    }
+
+    fn try_open_with_mode(config: Arc<Config>, read_only: bool) -> Result<Self, String> {
+        let storage_config = get_storage_config(&config)?;
+        let storage_path = storage_config.data_path.as_path();
+        let temp_path = storage_config.temp_path();
+
+        // an existing data path needs to be a directory, e.g. not a file pointed at by mistake
+        if storage_path.exists() && !storage_path.is_dir() {
+            let err = format!(
+                "The data path `{}` is not a directory",
+                storage_path.to_string_lossy()
+            );
+            tracing::error!("{}", err);
+            return Err(err);
+        }
+
+        // create storage_path and temp_path if not exists
+        if !read_only {
+            for path in [storage_path, temp_path] {
+                if let Err(err) = std::fs::create_dir_all(path) {
+                    tracing::error!("{}", err);
+                    return Err(err.to_string());
+                };
+            }
+        }
+
+        // try to lock the local storage for exclusive access
+        // that prevents access to the stored data from other instances to ensure data consistency
+        // read-only instances share the lock, as they do not modify the stored data
+        let lock_filepath = storage_path.join(FILE_STORAGE_LOCK);
+        let instance_lock = match fs::OpenOptions::new()
+            .read(true)
+            .write(!read_only)
+            .create(!read_only)
+            .truncate(false)
+            .open(&lock_filepath)
+        {
+            Ok(file) => file,
+            Err(err) => {
+                let err = format!(
+                    "Could not open the lock file `{}`: {}",
+                    lock_filepath.to_string_lossy(),
+                    err
+                );
+                tracing::error!("{}", err);
+                return Err(err);
+            }
+        };
+
+        let lock_wait = storage_config.lock_wait;
+        let lock_try_duration = Duration::from_millis(INSTANCE_LOCK_POLL_INTERVAL_MILLISECONDS);
+        let log_interval = Duration::from_millis(INSTANCE_LOCK_LOG_INTERVAL_MILLISECONDS);
+        let started_on = Instant::now();
+        let mut next_log = Duration::ZERO;
+
+        let try_lock = |file: &File| {
+            if read_only {
+                FileExt::try_lock_shared(file)
+            } else {
+                FileExt::try_lock_exclusive(file)
+            }
+        };
+
+        while let Err(err) = try_lock(&instance_lock) {
+            let elapsed = started_on.elapsed();
+            if elapsed >= lock_wait {
+                // the holder of the lock records itself in the lock file
+                let lock_owner = fs::read_to_string(&lock_filepath)
+                    .unwrap_or_default()
+                    .lines()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let error_message = format!(
+                    "Could not obtain a lock `{}` to open the local storage! Lock owner: {}. Error Message: {}",
+                    lock_filepath.to_string_lossy(),
+                    if lock_owner.is_empty() { "unknown" } else { &lock_owner },
+                    err
+                );
+                tracing::error!("{}", error_message);
+                return Err(error_message);
+            }
+            if elapsed >= next_log {
+                tracing::info!(
+                    "Waiting for the lock `{}` held by another instance, {} of {} ms elapsed",
+                    lock_filepath.to_string_lossy(),
+                    elapsed.as_millis(),
+                    lock_wait.as_millis()
+                );
+                next_log = elapsed + log_interval;
+            }
+            thread::sleep(lock_try_duration.min(lock_wait - elapsed));
+        }
+
+        if next_log > Duration::ZERO {
+            tracing::info!(
+                "Obtained the lock `{}` after {} ms",
+                lock_filepath.to_string_lossy(),
+                started_on.elapsed().as_millis()
+            );
+        }
+
+        if !read_only {
+            if let Err(err) = write_lock_owner(&instance_lock) {
+                tracing::error!("Could not write the lock owner: {}", err);
+            }
+        }
+
+        let initial_capacity = storage_config.initial_capacity.unwrap_or_default();
+        Ok(FileBackend {
+            storage_map: HashMap::with_capacity(initial_capacity),
+            storage_path: storage_path.to_path_buf(),
+            temp_path: temp_path.to_path_buf(),
+            instance_lock,
+            read_only,
+            durable: storage_config.durable,
+        })
+    }
+
+    fn storage_path(&self) -> &Path {
+        self.storage_path.as_path()
+    }
+
+    fn load_item(&self, item_id: String) -> Result<StorageItem, Error> {
+        let storage_data_path = get_storage_data_path(self.storage_path());
+        let filepath = storage_data_path.join(item_id);
+        decode_from_file(filepath)
+    }
+
+    /// Returns the codec of the item file if it is not enabled in this build
+    fn missing_codec(&self, item_id: &str) -> Option<StrorageCodecType> {
+        let filepath = get_storage_data_path(self.storage_path()).join(item_id);
+        let buf = fs::read(filepath).ok()?;
+        let header = parse_packet_header(&buf).ok()?;
+        Some(header.codec_type).filter(|codec_type| !codec_enabled(*codec_type))
+    }
+
+    /// Moves the item file into the quarantine folder, so it is kept but not loaded
+    /// A read-only storage leaves the file in place
+    fn quarantine_item(&self, item_id: &str) {
+        if !self.read_only {
+            quarantine_item_file(self.storage_path(), item_id);
+        }
+    }
+
+    /// Unlocks the local storage
+    fn unlock(&mut self) {
+        if !self.read_only {
+            if let Err(err) = self.instance_lock.set_len(0) {
+                tracing::error!("{}", err);
+            }
+        }
+        if let Err(err) = self.instance_lock.unlock() {
+            tracing::error!("{}", err);
+        }
+    }
+}
+
+impl StorageBackend for FileBackend {
+    fn get(&self, key: &str) -> Option<StorageItem> {
+        self.storage_map.get(key).cloned()
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.storage_map.contains_key(key)
+    }
+
+    fn item_len(&self, key: &str) -> Option<usize> {
+        self.storage_map.get(key).map(|item| item.data.len())
+    }
+
+    fn insert(&mut self, storage_item: StorageItem) {
+        self.storage_map
+            .insert(storage_item.key.clone(), storage_item);
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        self.storage_map.remove(key).is_some()
+    }
+
+    fn clear(&mut self) {
+        self.storage_map.clear();
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.storage_map.reserve(additional);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.storage_map.keys().cloned().collect()
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(&StorageItem)) {
+        for item in self.storage_map.values() {
+            f(item);
+        }
+    }
+
+    fn load(&mut self) -> Result<(), Error> {
+        self.clear();
+
+        // load storage info
+        match load_storage_info(self.storage_path()) {
+            Ok(storage_info) => {
+                // the persisted item count is known up front, so the map is not rehashed while loading
+                self.reserve(storage_info.len());
+
+                // load items
+                for (item_key, (item_id, _)) in &storage_info {
+                    match self.load_item(item_id.clone()) {
+                        Ok(storage_item) => {
+                            // an inconsistent item is kept aside instead of failing the load
+                            if let Err(err) = storage_item.validate() {
+                                tracing::error!(
+                                    "Item `{}` is invalid: {}, the item is quarantined",
+                                    item_key,
+                                    err
+                                );
+                                self.quarantine_item(item_id);
+                                continue;
+                            }
+
+                            // insert loaded item into storage
+                            self.insert(storage_item)
+                        }
+                        Err(err) => {
+                            // an item encoded with a codec missing in this build is kept aside instead of failing the load
+                            if let Some(codec_type) = self.missing_codec(item_id) {
+                                tracing::error!(
+                                    "Item `{}` requires codec {:?} which is not enabled in this build, the item is quarantined",
+                                    item_key,
+                                    codec_type
+                                );
+                                self.quarantine_item(item_id);
+                                continue;
+                            }
+                            tracing::error!("{}", err);
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                tracing::error!("{}", err);
+            }
+        };
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Box<dyn StorageSnapshot> {
+        Box::new(FileSnapshot {
+            storage_map: self.storage_map.clone(),
+            storage_path: self.storage_path().to_path_buf(),
+            temp_path: self.temp_path.clone(),
+            durable: self.durable,
+        })
+    }
+}
+
+/// Copy of the items persisted in local files
+struct FileSnapshot {
+    storage_map: StorageMap,
+    storage_path: PathBuf,
+    temp_path: PathBuf,
+    durable: bool,
+}
+
+impl StorageSnapshot for FileSnapshot {
+    fn flush(self: Box<Self>) -> Result<FlushReport, Error> {
+        // items sharing an id would overwrite each other's blob file
+        if let Err(err) = check_duplicate_ids(&self.storage_map) {
+            tracing::error!("{}", err);
+            return Err(err);
+        }
+
+        // load locally persisted storage info
+        let persisted_info = match load_storage_info(&self.storage_path) {
+            Ok(objects) => Some(objects),
+            Err(err) => {
+                tracing::error!("{}", err);
+                None
+            }
+        };
+
+        let mut info_to_persist: StorageInfo = HashMap::new();
+        for (key, item) in &self.storage_map {
+            info_to_persist.insert(key.clone(), (item.id.clone(), item.version));
+        }
+
+        let mut report = FlushReport::default();
+
+        // persist the storage info
+        match persist_storage_info(&self.storage_path, &self.temp_path, &info_to_persist) {
+            Ok(bytes_written) => report.bytes_written += bytes_written as u64,
+            Err(err) => {
+                tracing::error!("{}", err);
+                return Err(err);
+            }
+        }
+        let mut written = vec![self.storage_path.join(FILE_STORAGE_INFO)];
+
+        // create storage_data_path if not exists
+        let storage_data_path = get_storage_data_path(&self.storage_path);
+        if let Err(err) = std::fs::create_dir_all(&storage_data_path) {
+            let err = Error::io(storage_data_path, err);
+            tracing::error!("{}", err);
+            return Err(err);
+        };
+
+        // analyze existing blob files
+        let item_ids: HashSet<_> = info_to_persist
+            .values()
+            .map(|v| v.0.to_ascii_lowercase())
+            .collect();
+        let mut to_remove = vec![];
+        if let Ok(entries) = std::fs::read_dir(&storage_data_path) {
+            for entry in entries.flatten() {
+                if let Ok(file_type) = entry.file_type() {
+                    if FileType::is_file(&file_type) {
+                        let filename = entry.file_name().to_string_lossy().to_ascii_lowercase();
+                        // temporary files may belong to a write in progress
+                        if !item_ids.contains(&filename) && !is_temp_file(&filename) {
+                            to_remove.push(entry.path());
+                        }
+                    }
+                }
+            }
+        }
+
+        // remove blob files corresponding to removed items
+        for path in to_remove {
+            match std::fs::remove_file(path) {
+                Ok(()) => report.files_removed += 1,
+                Err(err) => tracing::error!("Could not remove unused item blob file: {}", err),
+            }
+        }
+
+        for (item_key, (item_id, item_version)) in info_to_persist {
+            if let Some(item) = self.storage_map.get(&item_key) {
+                // check if item is replaced or updated
+                let needs_persist = if let Some(prev) = &persisted_info {
+                    if let Some((prev_id, prev_version)) = prev.get(&item.key) {
+                        // need to check the id first as the item can be removed and a new item with the same key is created then
+                        (item_id != *prev_id) || (item_version > *prev_version)
+                    } else {
+                        // new item needs persist
+                        true
+                    }
+                } else {
+                    // initial storage needs persist
+                    true
+                };
+
+                if needs_persist {
+                    match persist_item(&self.storage_path, &self.temp_path, item) {
+                        Ok(bytes_written) => {
+                            report.items_written += 1;
+                            report.bytes_written += bytes_written as u64;
+                            written.push(storage_data_path.join(&item.id));
+                        }
+                        Err(err) => {
+                            tracing::error!("{}", err);
+                            return Err(err);
+                        }
+                    }
+                } else {
+                    report.items_skipped += 1;
+                }
+            }
+        }
+
+        if self.durable {
+            // the renamed files are durable once their directories are synced as well
+            written.push(storage_data_path);
+            written.push(self.storage_path.clone());
+            for path in written {
+                if let Err(err) = sync_to_disk(&path) {
+                    tracing::error!("{}", err);
+                    return Err(err);
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Syncs the content of the file or directory to disk
+fn sync_to_disk(path: &Path) -> Result<(), Error> {
+    // directories can not be opened for syncing on every platform
+    if path.is_dir() && !cfg!(unix) {
+        return Ok(());
+    }
+    File::open(path)
+        .and_then(|file| file.sync_all())
+        .map_err(|err| Error::io(path.to_path_buf(), err))
+}
+
+/// Records the current process as the owner of the obtained lock
+fn write_lock_owner(mut instance_lock: &File) -> std::io::Result<()> {
+    let hostname = std::env::var("HOSTNAME")
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|hostname| hostname.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string());
+    let opened_on = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    instance_lock.set_len(0)?;
+    writeln!(instance_lock, "pid: {}", std::process::id())?;
+    writeln!(instance_lock, "hostname: {}", hostname)?;
+    writeln!(instance_lock, "opened_on: {}", opened_on)?;
+    instance_lock.flush()
+}
+
+/// Returns the storage section of the configuration
+/// Returns an error if the configuration has no storage section
+pub(crate) fn get_storage_config(config: &Config) -> Result<&StorageConfig, String> {
+    match config.storage.as_ref() {
+        Some(storage_config) => Ok(storage_config),
+        None => {
+            let err = "The storage configuration is missing! The `storage` section is required to open a storage".to_string();
+            tracing::error!("{}", err);
+            Err(err)
+        }
+    }
+}
+
+/// Returns the version of the item persisted in the storage info of the storage path
+pub(crate) fn persisted_item_version(storage_path: &Path, key: &str) -> Result<Option<u64>, Error> {
+    let storage_info = load_storage_info(storage_path)?;
+    Ok(storage_info.get(key).map(|(_, version)| *version))
+}
+
+/// Loads the persisted storage info
+/// A missing storage info file, as of a fresh data path, is an empty storage
+fn load_storage_info(storage_path: &Path) -> Result<StorageInfo, Error> {
+    let filepath = storage_path.join(FILE_STORAGE_INFO);
+    if matches!(filepath.try_exists(), Ok(false)) {
+        tracing::debug!(
+            "No storage info at `{}`, starting with an empty storage",
+            filepath.to_string_lossy()
+        );
+        return Ok(HashMap::new());
+    }
+    decode_from_file(filepath)
+}
+
+fn persist_storage_info(
+    storage_path: &Path,
+    temp_path: &Path,
+    storage_info: &StorageInfo,
+) -> Result<usize, Error> {
+    let filepath = storage_path.join(FILE_STORAGE_INFO);
+    encode_to_file_atomic(
+        filepath,
+        temp_path,
+        storage_info,
+        StroragePacketType::StrorageInfo,
+    )
+}
+
+/// Returns whether the file is a temporary file of an atomic write
+fn is_temp_file(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(TEMP_FILE_EXTENSION))
+}
+
+/// Checks that no two items share an id, ids are compared case-insensitively like the blob filenames
+fn check_duplicate_ids(storage_map: &StorageMap) -> Result<(), Error> {
+    let mut keys_by_id: HashMap<String, &str> = HashMap::with_capacity(storage_map.len());
+    for (key, item) in storage_map {
+        if let Some(other_key) = keys_by_id.insert(item.id.to_ascii_lowercase(), key) {
+            let (key, other_key) = if key.as_str() < other_key {
+                (key.as_str(), other_key)
+            } else {
+                (other_key, key.as_str())
+            };
+            return Err(Error::DuplicateId {
+                id: item.id.clone(),
+                key: key.to_string(),
+                other_key: other_key.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn get_storage_data_path(storage_path: &Path) -> PathBuf {
+    storage_path.join(DIR_STORAGE_DATA)
+}
+
+/// Moves the item file into the quarantine folder of the storage path
+fn quarantine_item_file(storage_path: &Path, item_id: &str) {
+    let quarantine_path = storage_path.join(DIR_STORAGE_QUARANTINE);
+    if let Err(err) = fs::create_dir_all(&quarantine_path) {
+        tracing::error!("{}", err);
+        return;
+    }
+    let filepath = get_storage_data_path(storage_path).join(item_id);
+    if let Err(err) = fs::rename(&filepath, quarantine_path.join(item_id)) {
+        tracing::error!("Could not quarantine item file: {}", err);
+    }
+}
+
+/// Decodes the item files of the storage path and persists the storage info of the decoded items
+/// Item files that do not decode are quarantined, of several files with the same key the highest version is kept
+/// Returns the decoded items
+pub(crate) fn rebuild_storage_info(
+    storage_path: &Path,
+    temp_path: &Path,
+) -> Result<Vec<StorageItem>, String> {
+    let storage_data_path = get_storage_data_path(storage_path);
+    let entries = match fs::read_dir(&storage_data_path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            let err = format!(
+                "Could not read the storage data path `{}`: {}",
+                storage_data_path.to_string_lossy(),
+                err
+            );
+            tracing::error!("{}", err);
+            return Err(err);
+        }
+    };
+
+    let mut storage_map = StorageMap::new();
+    for entry in entries.flatten() {
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if is_temp_file(&filename) {
+            continue;
+        }
+        let item = match decode_from_file::<StorageItem>(entry.path()) {
+            Ok(item) => item,
+            Err(err) => {
+                tracing::error!("{}, the file is quarantined", err);
+                quarantine_item_file(storage_path, &filename);
+                continue;
+            }
+        };
+        if let Err(err) = item.validate() {
+            tracing::error!(
+                "Item file `{}` is invalid: {}, the file is quarantined",
+                filename,
+                err
+            );
+            quarantine_item_file(storage_path, &filename);
+            continue;
+        }
+        // the item file is named after the item id
+        if !item.id.eq_ignore_ascii_case(&filename) {
+            tracing::error!(
+                "Item file `{}` does not match the item id `{}`, the file is quarantined",
+                filename,
+                item.id
+            );
+            quarantine_item_file(storage_path, &filename);
+            continue;
+        }
+        let newer = storage_map
+            .get(&item.key)
+            .is_none_or(|prev| item.version > prev.version);
+        if newer {
+            storage_map.insert(item.key.clone(), item);
+        }
+    }
+
+    let storage_info: StorageInfo = storage_map
+        .iter()
+        .map(|(key, item)| (key.clone(), (item.id.clone(), item.version)))
+        .collect();
+    if let Err(err) = persist_storage_info(storage_path, temp_path, &storage_info) {
+        tracing::error!("{}", err);
+        return Err(err.to_string());
+    }
+    Ok(storage_map.into_values().collect())
+}
+
+fn persist_item(storage_path: &Path, temp_path: &Path, item: &StorageItem) -> Result<usize, Error> {
+    let storage_data_path = get_storage_data_path(storage_path);
+    let filepath = storage_data_path.join(&item.id);
+    encode_to_file_atomic(filepath, temp_path, item, StroragePacketType::StrorageItem)
+}
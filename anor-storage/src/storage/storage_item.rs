@@ -1,4 +1,7 @@
-use super::{storage_codec::*, storage_persistence::*, storage_packet::*};
+use super::{
+    storage_codec::*, storage_const::MAX_ITEM_REDUNDANCY, storage_packet::*, storage_persistence::*,
+};
+use anor_utils::config::IdScheme;
 use std::collections::HashMap;
 use uuid::Uuid;
 
@@ -16,7 +19,7 @@ pub enum ItemType {
 }
 
 /// Basic Type
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bincode::Encode, bincode::Decode)]
 pub enum BasicType {
     Bool,
     I8,
@@ -35,6 +38,34 @@ pub enum BasicType {
     String,
 }
 
+impl BasicType {
+    /// Returns whether the data is exactly one encoded value of the type
+    fn matches(&self, data: &[u8]) -> bool {
+        fn decodes_as<T: bincode::Decode>(data: &[u8]) -> bool {
+            bincode::decode_from_slice::<T, _>(data, bincode_config())
+                .is_ok_and(|(_, len)| len == data.len())
+        }
+
+        match self {
+            BasicType::Bool => decodes_as::<bool>(data),
+            BasicType::I8 => decodes_as::<i8>(data),
+            BasicType::I16 => decodes_as::<i16>(data),
+            BasicType::I32 => decodes_as::<i32>(data),
+            BasicType::I64 => decodes_as::<i64>(data),
+            BasicType::I128 => decodes_as::<i128>(data),
+            BasicType::U8 => decodes_as::<u8>(data),
+            BasicType::U16 => decodes_as::<u16>(data),
+            BasicType::U32 => decodes_as::<u32>(data),
+            BasicType::U64 => decodes_as::<u64>(data),
+            BasicType::U128 => decodes_as::<u128>(data),
+            BasicType::F32 => decodes_as::<f32>(data),
+            BasicType::F64 => decodes_as::<f64>(data),
+            BasicType::Char => decodes_as::<char>(data),
+            BasicType::String => decodes_as::<String>(data),
+        }
+    }
+}
+
 /// Complex Type
 #[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
 pub enum ComplexType {
@@ -47,10 +78,44 @@ pub enum ComplexType {
     File,
     Folder,
     Path,
+    /// points of `(timestamp, f64)` sorted by timestamp
+    TimeSeries,
+}
+
+/// Error of [`StorageItem::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// the item has an empty id
+    EmptyId,
+    /// the item has an empty key
+    EmptyKey,
+    /// the data does not decode as the basic type of the item
+    DataMismatch(BasicType),
+    /// the redundancy exceeds the maximum number of replications
+    Redundancy(u8),
 }
 
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::EmptyId => write!(f, "The item id is empty"),
+            ValidationError::EmptyKey => write!(f, "The item key is empty"),
+            ValidationError::DataMismatch(basic_type) => {
+                write!(f, "The item data is not a valid {:?}", basic_type)
+            }
+            ValidationError::Redundancy(redundancy) => write!(
+                f,
+                "The item redundancy {} exceeds the maximum of {}",
+                redundancy, MAX_ITEM_REDUNDANCY
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
 /// Storage Item
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, bincode::Encode)]
 pub struct StorageItem {
     pub id: String,
     pub key: String,
@@ -67,12 +132,55 @@ pub struct StorageItem {
 
     /// defines the number of required replications in the cluster
     pub redundancy: u8,
+
+    /// `modified_at` - timestamp in epoch seconds, set by the storage on every mutation
+    pub modified_at: u64,
+}
+
+impl bincode::Decode for StorageItem {
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(StorageItem {
+            id: bincode::Decode::decode(decoder)?,
+            key: bincode::Decode::decode(decoder)?,
+            version: bincode::Decode::decode(decoder)?,
+            data: bincode::Decode::decode(decoder)?,
+            item_type: bincode::Decode::decode(decoder)?,
+            description: bincode::Decode::decode(decoder)?,
+            tags: bincode::Decode::decode(decoder)?,
+            metafields: bincode::Decode::decode(decoder)?,
+            expires_on: bincode::Decode::decode(decoder)?,
+            persistence: bincode::Decode::decode(decoder)?,
+            redundancy: bincode::Decode::decode(decoder)?,
+            // items persisted before the timestamp was recorded end here
+            modified_at: match bincode::Decode::decode(decoder) {
+                Err(bincode::error::DecodeError::UnexpectedEnd { .. }) => 0,
+                result => result?,
+            },
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(StorageItem);
+
+/// Returns a new item id of the scheme
+pub fn new_item_id(id_scheme: IdScheme) -> String {
+    match id_scheme {
+        IdScheme::Uuid4 => Uuid::new_v4().to_string(),
+        IdScheme::Uuid7 => Uuid::now_v7().to_string(),
+    }
 }
 
 impl StorageItem {
+    /// Returns a builder to construct an item with optional fields
+    pub fn builder(key: &str) -> StorageItemBuilder {
+        StorageItemBuilder::new(key)
+    }
+
     pub fn new<T: bincode::Encode>(key: &str, obj: &T) -> Option<Self> {
         encode_to_binary(obj, StrorageCodecType::default()).map(|data| StorageItem {
-            id: Uuid::new_v4().to_string(),
+            id: new_item_id(IdScheme::default()),
             key: key.to_owned(),
             version: 0,
             description: None,
@@ -83,6 +191,7 @@ impl StorageItem {
             metafields: None,
             expires_on: None,
             redundancy: 0,
+            modified_at: 0,
         })
     }
 
@@ -92,7 +201,7 @@ impl StorageItem {
         obj: &T,
     ) -> Option<Self> {
         encode_to_binary(obj, StrorageCodecType::default()).map(|data| StorageItem {
-            id: Uuid::new_v4().to_string(),
+            id: new_item_id(IdScheme::default()),
             key: key.to_owned(),
             version: 0,
             description: None,
@@ -103,12 +212,15 @@ impl StorageItem {
             metafields: None,
             expires_on: None,
             redundancy: 0,
+            modified_at: 0,
         })
     }
 
+    /// Replaces the inner object and increments the item version
     pub fn update_object<T: bincode::Encode>(&mut self, obj: &T) -> bool {
         if let Some(encoded) = encode_to_binary(obj, StrorageCodecType::default()) {
             self.data = encoded;
+            self.version += 1;
             return true;
         }
         false
@@ -118,6 +230,31 @@ impl StorageItem {
         decode_from_binary(&self.data, StrorageCodecType::default())
     }
 
+    /// Returns the inner object, or the reason the data does not decode as `T`
+    pub fn try_get_object<T: bincode::Decode>(&self) -> Result<T, CodecError> {
+        try_decode_from_binary(&self.data, StrorageCodecType::default())
+    }
+
+    /// Checks the consistency of a decoded item
+    /// The data of a basic type item needs to decode exactly as that type
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.id.is_empty() {
+            return Err(ValidationError::EmptyId);
+        }
+        if self.key.is_empty() {
+            return Err(ValidationError::EmptyKey);
+        }
+        if let ItemType::Basic(basic_type) = &self.item_type {
+            if !basic_type.matches(&self.data) {
+                return Err(ValidationError::DataMismatch(*basic_type));
+            }
+        }
+        if self.redundancy > MAX_ITEM_REDUNDANCY {
+            return Err(ValidationError::Redundancy(self.redundancy));
+        }
+        Ok(())
+    }
+
     pub fn set_description(&mut self, description: &str) {
         self.description = Some(description.to_string());
     }
@@ -133,6 +270,18 @@ impl StorageItem {
         }
     }
 
+    /// Removes the tag, returns whether the item had the tag
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        match self.tags.as_mut() {
+            Some(tags) => {
+                let len = tags.len();
+                tags.retain(|item_tag| item_tag != tag);
+                tags.len() != len
+            }
+            None => false,
+        }
+    }
+
     pub fn add_metafield(&mut self, key: &str, value: &str) {
         match self.metafields.as_mut() {
             Some(metafields) => {
@@ -146,3 +295,187 @@ impl StorageItem {
         };
     }
 }
+
+/// Storage Item Builder
+/// Collects the optional fields of an item, the object is encoded on `build`
+#[derive(Debug, Clone)]
+pub struct StorageItemBuilder {
+    key: String,
+    item_type: ItemType,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+    metafields: Option<HashMap<String, String>>,
+    expires_on: Option<u64>,
+    persistence: StoragePersistence,
+    redundancy: u8,
+    id_scheme: IdScheme,
+}
+
+impl StorageItemBuilder {
+    pub fn new(key: &str) -> Self {
+        StorageItemBuilder {
+            key: key.to_owned(),
+            item_type: ItemType::Custom,
+            description: None,
+            tags: None,
+            metafields: None,
+            expires_on: None,
+            persistence: StoragePersistence::Memory,
+            redundancy: 0,
+            id_scheme: IdScheme::default(),
+        }
+    }
+
+    pub fn item_type(mut self, item_type: ItemType) -> Self {
+        self.item_type = item_type;
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.get_or_insert_with(Vec::new).push(tag.into());
+        self
+    }
+
+    pub fn metafield(mut self, key: &str, value: &str) -> Self {
+        self.metafields
+            .get_or_insert_with(HashMap::new)
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// `expires_on` - timestamp, defines expiry datetime
+    pub fn expires_on(mut self, expires_on: u64) -> Self {
+        self.expires_on = Some(expires_on);
+        self
+    }
+
+    pub fn persistence(mut self, persistence: StoragePersistence) -> Self {
+        self.persistence = persistence;
+        self
+    }
+
+    /// defines the number of required replications in the cluster
+    pub fn redundancy(mut self, redundancy: u8) -> Self {
+        self.redundancy = redundancy;
+        self
+    }
+
+    /// defines the scheme of the item id, see [`Storage::new_item`](crate::storage::Storage::new_item) for the scheme of the storage
+    pub fn id_scheme(mut self, id_scheme: IdScheme) -> Self {
+        self.id_scheme = id_scheme;
+        self
+    }
+
+    /// Encodes the object and builds the item
+    /// Returns `None` if the object could not be encoded
+    pub fn build<T: bincode::Encode>(self, obj: &T) -> Option<StorageItem> {
+        let mut item = StorageItem::with_type(&self.key, self.item_type, obj)?;
+        item.id = new_item_id(self.id_scheme);
+        item.description = self.description;
+        item.tags = self.tags;
+        item.metafields = self.metafields;
+        item.expires_on = self.expires_on;
+        item.persistence = self.persistence;
+        item.redundancy = self.redundancy;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_item_builder_test() {
+        let my_string = String::from("abc");
+        let item = StorageItem::builder("my_item")
+            .item_type(ItemType::Basic(BasicType::String))
+            .description("my description")
+            .tag("tag1")
+            .tag("tag2")
+            .metafield("owner", "anor")
+            .expires_on(1_700_000_000)
+            .persistence(StoragePersistence::Disk)
+            .redundancy(2)
+            .build(&my_string)
+            .unwrap();
+
+        assert_eq!(item.key, "my_item");
+        assert!(matches!(item.item_type, ItemType::Basic(BasicType::String)));
+        assert_eq!(item.description, Some("my description".to_string()));
+        assert_eq!(
+            item.tags,
+            Some(vec!["tag1".to_string(), "tag2".to_string()])
+        );
+        assert_eq!(
+            item.metafields.as_ref().unwrap().get("owner"),
+            Some(&"anor".to_string())
+        );
+        assert_eq!(item.expires_on, Some(1_700_000_000));
+        assert!(matches!(item.persistence, StoragePersistence::Disk));
+        assert_eq!(item.redundancy, 2);
+        assert_eq!(item.get_object::<String>(), Some(my_string));
+    }
+
+    #[test]
+    fn validate_test() {
+        let item = StorageItem::with_type(
+            "my_item",
+            ItemType::Basic(BasicType::String),
+            &String::from("abc"),
+        )
+        .unwrap();
+        assert_eq!(item.validate(), Ok(()));
+
+        let mut empty_key = item.clone();
+        empty_key.key = String::new();
+        assert_eq!(empty_key.validate(), Err(ValidationError::EmptyKey));
+
+        // a string is not a bool, and an u64 is not a single u8
+        let mut mismatched = item.clone();
+        mismatched.item_type = ItemType::Basic(BasicType::Bool);
+        assert_eq!(
+            mismatched.validate(),
+            Err(ValidationError::DataMismatch(BasicType::Bool))
+        );
+        let mut mismatched =
+            StorageItem::with_type("my_item", ItemType::Basic(BasicType::U8), &u64::MAX).unwrap();
+        assert_eq!(
+            mismatched.validate(),
+            Err(ValidationError::DataMismatch(BasicType::U8))
+        );
+        mismatched.update_object(&7_u8);
+        assert_eq!(mismatched.validate(), Ok(()));
+
+        let mut redundant = item;
+        redundant.redundancy = MAX_ITEM_REDUNDANCY + 1;
+        assert_eq!(
+            redundant.validate(),
+            Err(ValidationError::Redundancy(MAX_ITEM_REDUNDANCY + 1))
+        );
+    }
+
+    #[test]
+    fn decode_without_modified_at_test() {
+        let mut item = StorageItem::new("my_item", &String::from("abc")).unwrap();
+        item.modified_at = 1_700_000_000;
+        let encoded = encode_to_binary(&item, StrorageCodecType::default()).unwrap();
+        let decoded: StorageItem =
+            decode_from_binary(&encoded, StrorageCodecType::default()).unwrap();
+        assert_eq!(decoded.modified_at, 1_700_000_000);
+
+        // the varint of a timestamp below 251 is a single byte
+        item.modified_at = 0;
+        let encoded = encode_to_binary(&item, StrorageCodecType::default()).unwrap();
+        let legacy = &encoded[..encoded.len() - 1];
+        let decoded: StorageItem =
+            decode_from_binary(legacy, StrorageCodecType::default()).unwrap();
+        assert_eq!(decoded.key, "my_item");
+        assert_eq!(decoded.modified_at, 0);
+    }
+}
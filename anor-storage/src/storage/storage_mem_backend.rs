@@ -0,0 +1,73 @@
+use super::storage_backend::{FlushReport, StorageBackend, StorageSnapshot};
+use super::{storage_error::Error, storage_item::StorageItem};
+use std::collections::HashMap;
+
+/// In-memory only storage backend, nothing is persisted
+#[derive(Default)]
+pub struct MemBackend {
+    storage_map: HashMap<String, StorageItem>,
+}
+
+impl MemBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for MemBackend {
+    fn get(&self, key: &str) -> Option<StorageItem> {
+        self.storage_map.get(key).cloned()
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.storage_map.contains_key(key)
+    }
+
+    fn item_len(&self, key: &str) -> Option<usize> {
+        self.storage_map.get(key).map(|item| item.data.len())
+    }
+
+    fn insert(&mut self, storage_item: StorageItem) {
+        self.storage_map
+            .insert(storage_item.key.clone(), storage_item);
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        self.storage_map.remove(key).is_some()
+    }
+
+    fn clear(&mut self) {
+        self.storage_map.clear();
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.storage_map.reserve(additional);
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.storage_map.keys().cloned().collect()
+    }
+
+    fn for_each(&self, f: &mut dyn FnMut(&StorageItem)) {
+        for item in self.storage_map.values() {
+            f(item);
+        }
+    }
+
+    fn load(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Box<dyn StorageSnapshot> {
+        Box::new(MemSnapshot)
+    }
+}
+
+/// Nothing to persist for the in-memory backend
+struct MemSnapshot;
+
+impl StorageSnapshot for MemSnapshot {
+    fn flush(self: Box<Self>) -> Result<FlushReport, Error> {
+        Ok(FlushReport::default())
+    }
+}
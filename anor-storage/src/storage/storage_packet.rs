@@ -1,5 +1,11 @@
-const STORAGE_PACKET_HEADER_SIZE: usize = 11;
-const STORAGE_PACKET_VERSION: u8 = 1;
+use super::storage_codec::codec_config_version;
+
+const STORAGE_PACKET_HEADER_SIZE: usize = 12;
+const STORAGE_PACKET_VERSION: u8 = 2;
+
+/// packets of version 1 have no codec config version in the header
+const STORAGE_PACKET_V1_HEADER_SIZE: usize = 11;
+const STORAGE_PACKET_V1_CODEC_CONFIG_VERSION: u8 = 1;
 
 /// StoragePacketMetaFields
 pub type StoragePacketFields = Vec<(String, String)>;
@@ -10,15 +16,30 @@ pub enum StroragePacketType {
     StrorageInfo = 1,
     StrorageItem = 2,
     StrorageItemObject = 3,
+    StrorageArchiveTrailer = 4,
+}
+
+impl StroragePacketType {
+    /// Returns the packet type of the value, `None` if the value is unknown
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(StroragePacketType::StrorageInfo),
+            2 => Some(StroragePacketType::StrorageItem),
+            3 => Some(StroragePacketType::StrorageItemObject),
+            4 => Some(StroragePacketType::StrorageArchiveTrailer),
+            _ => None,
+        }
+    }
 }
 
+/// # Panics
+///
+/// Panics if the value is unknown, see [`StroragePacketType::from_u8`] for a non-panicking variant.
 impl From<u8> for StroragePacketType {
     fn from(v: u8) -> Self {
-        match v {
-            1 => StroragePacketType::StrorageInfo,
-            2 => StroragePacketType::StrorageItem,
-            3 => StroragePacketType::StrorageItemObject,
-            _ => panic!("Unmatched StroragePacketType value {}", v),
+        match Self::from_u8(v) {
+            Some(packet_type) => packet_type,
+            None => panic!("Unmatched StroragePacketType value {}", v),
         }
     }
 }
@@ -43,15 +64,28 @@ pub enum StrorageCodecType {
     CapnProto = 5,
 }
 
+impl StrorageCodecType {
+    /// Returns the codec type of the value, `None` if the value is unknown
+    pub fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            1 => Some(StrorageCodecType::Bincode),
+            2 => Some(StrorageCodecType::ProtocolBuffers),
+            3 => Some(StrorageCodecType::FlatBuffers),
+            4 => Some(StrorageCodecType::MessagePack),
+            5 => Some(StrorageCodecType::CapnProto),
+            _ => None,
+        }
+    }
+}
+
+/// # Panics
+///
+/// Panics if the value is unknown, see [`StrorageCodecType::from_u8`] for a non-panicking variant.
 impl From<u8> for StrorageCodecType {
     fn from(v: u8) -> Self {
-        match v {
-            1 => StrorageCodecType::Bincode,
-            2 => StrorageCodecType::ProtocolBuffers,
-            3 => StrorageCodecType::FlatBuffers,
-            4 => StrorageCodecType::MessagePack,
-            5 => StrorageCodecType::CapnProto,
-            _ => panic!("Unmatched CodecType value {}", v),
+        match Self::from_u8(v) {
+            Some(codec_type) => codec_type,
+            None => panic!("Unmatched CodecType value {}", v),
         }
     }
 }
@@ -69,19 +103,34 @@ pub struct StroragePacketHeader {
     pub packet_type: StroragePacketType,
     pub packet_version: u8,
     pub codec_type: StrorageCodecType,
+    /// version of the codec configuration the data was encoded with
+    pub codec_config_version: u8,
 }
 
 impl StroragePacketHeader {
     pub fn to_vec(&self) -> Vec<u8> {
-        let mut header: Vec<u8> = Vec::with_capacity(STORAGE_PACKET_HEADER_SIZE);
+        let header_size = packet_header_size(self.packet_version);
+        let mut header: Vec<u8> = Vec::with_capacity(header_size);
         header.extend_from_slice(&(self.packet_length.to_be_bytes()));
         header.push(self.packet_type as u8);
         header.push(self.packet_version);
         header.push(self.codec_type as u8);
+        if header_size > STORAGE_PACKET_V1_HEADER_SIZE {
+            header.push(self.codec_config_version);
+        }
         header
     }
 }
 
+/// Returns the header size of the packet version
+fn packet_header_size(packet_version: u8) -> usize {
+    if packet_version < 2 {
+        STORAGE_PACKET_V1_HEADER_SIZE
+    } else {
+        STORAGE_PACKET_HEADER_SIZE
+    }
+}
+
 /// builds a storage packet
 pub fn build_storage_packet(
     buf: Vec<u8>,
@@ -94,12 +143,12 @@ pub fn build_storage_packet(
 
 /// parses a buffer into storage packet
 pub fn parse_packet(buf: Vec<u8>) -> Result<StroragePacket, String> {
-    // parse header
+    // parse header, the buffer is checked to hold at least the header
     let header = parse_packet_header(&buf)?;
 
     // convert the buf into data part
     let mut data = buf;
-    data.drain(0..STORAGE_PACKET_HEADER_SIZE);
+    data.drain(..packet_header_size(header.packet_version));
 
     Ok(StroragePacket { header, data })
 }
@@ -115,13 +164,19 @@ pub fn build_packet_header(
         packet_type,
         packet_version: STORAGE_PACKET_VERSION,
         codec_type,
+        codec_config_version: codec_config_version(codec_type),
     }
 }
 
 /// parses storage packet header
 pub fn parse_packet_header(buf: &[u8]) -> Result<StroragePacketHeader, String> {
     let buf_len = buf.len();
-    if buf_len < STORAGE_PACKET_HEADER_SIZE {
+    if buf_len == 0 {
+        return Err("Cannot parse packet header, the buffer is empty".to_string());
+    }
+
+    // the size is checked before any field is read, the header size depends on the packet version
+    if buf_len < STORAGE_PACKET_V1_HEADER_SIZE || buf_len < packet_header_size(buf[9]) {
         return Err(format!(
             "Cannot parse packet header, invalid buffer size: {}",
             buf_len
@@ -139,11 +194,29 @@ pub fn parse_packet_header(buf: &[u8]) -> Result<StroragePacketHeader, String> {
         ));
     }
 
+    let packet_version = buf[9];
+    let codec_config_version = if packet_version < 2 {
+        STORAGE_PACKET_V1_CODEC_CONFIG_VERSION
+    } else {
+        buf[11]
+    };
+
+    // the type bytes of a corrupted packet are rejected rather than trusted
+    let packet_type = match StroragePacketType::from_u8(buf[8]) {
+        Some(packet_type) => packet_type,
+        None => return Err(format!("Unknown packet type: {}", buf[8])),
+    };
+    let codec_type = match StrorageCodecType::from_u8(buf[10]) {
+        Some(codec_type) => codec_type,
+        None => return Err(format!("Unknown codec type: {}", buf[10])),
+    };
+
     let header = StroragePacketHeader {
         packet_length,
-        packet_type: buf[8].into(),
-        packet_version: buf[9],
-        codec_type: buf[10].into(),
+        packet_type,
+        packet_version,
+        codec_type,
+        codec_config_version,
     };
 
     Ok(header)
@@ -155,9 +228,10 @@ pub fn packet_metafields(
 ) -> (StoragePacketFields, StoragePacketFields) {
     let header = [
         ("packet_length", "u64"),
-        ("packet_type", "StroragePacketType{StrorageInfo=1,StrorageItem=2,StrorageItemObject=3}"),
+        ("packet_type", "StroragePacketType{StrorageInfo=1,StrorageItem=2,StrorageItemObject=3,StrorageArchiveTrailer=4}"),
         ("packet_version", "u8"),
         ("codec_type", "StrorageCodecType{Bincode=1,ProtocolBuffers=2,FlatBuffers=3,MessagePack=4,CapnProto=5}"),
+        ("codec_config_version", "u8"),
     ];
 
     let object = match packet_type {
@@ -176,9 +250,11 @@ pub fn packet_metafields(
             ("expires_on", "Option<u64>"),
             ("storage_locations", "Vec<StorageLocation>"),
             ("redundancy", "u8"),
+            ("modified_at", "u64"),
         ]
         .to_vec(),
         StroragePacketType::StrorageItemObject => [("StrorageItemObject", "Vec[u8]")].to_vec(),
+        StroragePacketType::StrorageArchiveTrailer => [("item_count", "u64")].to_vec(),
     };
 
     (
@@ -192,3 +268,66 @@ pub fn packet_metafields(
             .collect::<Vec<_>>(),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_short_packet_test() {
+        let result = parse_packet(vec![]);
+        assert!(result.err().unwrap().contains("empty"));
+
+        let result = parse_packet(vec![1, 2, 3]);
+        assert!(result.err().unwrap().contains("invalid buffer size: 3"));
+
+        // the declared length matches the buffer, which is still shorter than the header
+        let mut buf = 10_u64.to_be_bytes().to_vec();
+        buf.extend_from_slice(&[
+            StroragePacketType::StrorageItem as u8,
+            STORAGE_PACKET_VERSION,
+        ]);
+        assert!(parse_packet(buf).is_err());
+
+        // a version 2 packet needs one more header byte than a version 1 packet
+        let mut buf = 11_u64.to_be_bytes().to_vec();
+        buf.extend_from_slice(&[
+            StroragePacketType::StrorageItem as u8,
+            STORAGE_PACKET_VERSION,
+            1,
+        ]);
+        assert!(parse_packet(buf).is_err());
+
+        // a packet without data
+        let packet = build_storage_packet(
+            vec![],
+            StroragePacketType::StrorageItem,
+            StrorageCodecType::Bincode,
+        );
+        let packet = parse_packet(packet.header.to_vec()).unwrap();
+        assert_eq!(
+            packet.header.packet_length,
+            STORAGE_PACKET_HEADER_SIZE as u64
+        );
+        assert!(packet.data.is_empty());
+    }
+
+    #[test]
+    fn parse_unknown_type_test() {
+        let packet = build_storage_packet(
+            vec![],
+            StroragePacketType::StrorageItem,
+            StrorageCodecType::Bincode,
+        );
+
+        let mut buf = packet.header.to_vec();
+        buf[8] = 0;
+        let result = parse_packet(buf);
+        assert!(result.err().unwrap().contains("Unknown packet type: 0"));
+
+        let mut buf = packet.header.to_vec();
+        buf[10] = 255;
+        let result = parse_packet(buf);
+        assert!(result.err().unwrap().contains("Unknown codec type: 255"));
+    }
+}
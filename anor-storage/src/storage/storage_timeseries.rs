@@ -0,0 +1,60 @@
+use super::storage_item::{ComplexType, ItemType, StorageItem};
+
+/// Point of a time series, a timestamp and its value
+pub type TimeSeriesPoint = (u64, f64);
+
+/// Returns the points of the time series item
+/// Fails if the item is not a time series or its data does not decode
+pub(crate) fn time_series_points(item: &StorageItem) -> Result<Vec<TimeSeriesPoint>, String> {
+    if !matches!(item.item_type, ItemType::Complex(ComplexType::TimeSeries)) {
+        return Err(format!("Item `{}` is not a time series", item.key));
+    }
+    item.get_object()
+        .ok_or_else(|| format!("Time series `{}` could not be decoded", item.key))
+}
+
+/// Inserts the point keeping the points sorted by timestamp
+/// A point with a timestamp already present goes after the present ones
+pub(crate) fn insert_point(points: &mut Vec<TimeSeriesPoint>, point: TimeSeriesPoint) {
+    let index = points.partition_point(|(ts, _)| *ts <= point.0);
+    points.insert(index, point);
+}
+
+/// Returns the points with a timestamp within `from..=to`
+pub(crate) fn points_in_range(
+    points: &[TimeSeriesPoint],
+    from: u64,
+    to: u64,
+) -> &[TimeSeriesPoint] {
+    if from > to {
+        return &[];
+    }
+    let start = points.partition_point(|(ts, _)| *ts < from);
+    let end = points.partition_point(|(ts, _)| *ts <= to);
+    &points[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_test() {
+        let mut points = vec![];
+        for point in [(30, 3.0), (10, 1.0), (20, 2.0), (20, 2.5), (0, 0.0)] {
+            insert_point(&mut points, point);
+        }
+        assert_eq!(
+            points,
+            vec![(0, 0.0), (10, 1.0), (20, 2.0), (20, 2.5), (30, 3.0)]
+        );
+
+        assert_eq!(
+            points_in_range(&points, 10, 20),
+            &[(10, 1.0), (20, 2.0), (20, 2.5)]
+        );
+        assert_eq!(points_in_range(&points, 11, 19), &[]);
+        assert_eq!(points_in_range(&points, 30, 10), &[]);
+        assert_eq!(points_in_range(&points, 0, u64::MAX), points.as_slice());
+    }
+}
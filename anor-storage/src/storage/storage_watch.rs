@@ -0,0 +1,181 @@
+use super::storage_const::CHANGE_BROADCAST_CAPACITY;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Kind of a change made to a storage item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// the item was inserted or updated
+    Set,
+    /// the item was removed
+    Removed,
+}
+
+/// Change of a storage item delivered to the watchers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub key: String,
+    pub kind: ChangeKind,
+}
+
+/// Keys a subscription is notified of
+enum WatchFilter {
+    /// the keys starting with the prefix
+    Prefix(String),
+    /// the key only
+    Key(String),
+}
+
+impl WatchFilter {
+    fn matches(&self, key: &str) -> bool {
+        match self {
+            WatchFilter::Prefix(prefix) => key.starts_with(prefix.as_str()),
+            WatchFilter::Key(watched) => key == watched,
+        }
+    }
+}
+
+/// Subscriptions to the changes of the keys starting with a prefix or of single keys
+#[derive(Default)]
+pub struct Watchers {
+    subscriptions: Mutex<Vec<(WatchFilter, Sender<ChangeEvent>)>>,
+    /// async subscriptions to the changes of all keys, created on the first one
+    broadcast: OnceLock<broadcast::Sender<ChangeEvent>>,
+}
+
+impl Watchers {
+    /// Subscribes to the changes of the keys starting with the prefix
+    pub fn subscribe(&self, prefix: &str) -> Receiver<ChangeEvent> {
+        self.subscribe_filter(WatchFilter::Prefix(prefix.to_string()))
+    }
+
+    /// Subscribes to the changes of the key
+    pub fn subscribe_key(&self, key: &str) -> Receiver<ChangeEvent> {
+        self.subscribe_filter(WatchFilter::Key(key.to_string()))
+    }
+
+    /// Subscribes to the changes of all keys through an async channel
+    pub fn subscribe_async(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.broadcast
+            .get_or_init(|| broadcast::channel(CHANGE_BROADCAST_CAPACITY).0)
+            .subscribe()
+    }
+
+    fn subscribe_filter(&self, filter: WatchFilter) -> Receiver<ChangeEvent> {
+        let (sender, receiver) = channel();
+        if let Ok(mut subscriptions) = self.subscriptions.lock() {
+            subscriptions.push((filter, sender));
+        }
+        receiver
+    }
+
+    /// Returns whether there are no subscriptions
+    pub fn is_empty(&self) -> bool {
+        let no_async = self
+            .broadcast
+            .get()
+            .is_none_or(|sender| sender.receiver_count() == 0);
+        match self.subscriptions.lock() {
+            Ok(subscriptions) => subscriptions.is_empty() && no_async,
+            Err(_) => no_async,
+        }
+    }
+
+    /// Delivers the change to the subscriptions matching the key and to the async subscriptions
+    /// Subscriptions with a dropped receiver are pruned once a change fails to deliver
+    pub fn notify(&self, key: &str, kind: ChangeKind) {
+        if let Some(sender) = self.broadcast.get() {
+            // fails only if there are no async subscriptions
+            _ = sender.send(ChangeEvent {
+                key: key.to_string(),
+                kind,
+            });
+        }
+        if let Ok(mut subscriptions) = self.subscriptions.lock() {
+            subscriptions.retain(|(filter, sender)| {
+                if !filter.matches(key) {
+                    return true;
+                }
+                let event = ChangeEvent {
+                    key: key.to_string(),
+                    kind,
+                };
+                sender.send(event).is_ok()
+            });
+        }
+    }
+}
+
+/// Collapses the successive changes of the key into a single event delivered once no change
+/// follows within the debounce window, the event carries the latest kind of change
+/// The coalescing thread ends when either receiver is disconnected
+pub fn coalesce(
+    receiver: Receiver<ChangeEvent>,
+    key: String,
+    debounce: Duration,
+) -> Receiver<ChangeEvent> {
+    let (sender, coalesced) = channel();
+    thread::spawn(move || {
+        let matches = |event: &ChangeEvent| event.key == key;
+        while let Ok(event) = receiver.recv() {
+            if !matches(&event) {
+                continue;
+            }
+            let mut latest = event;
+            let disconnected = loop {
+                match receiver.recv_timeout(debounce) {
+                    Ok(event) if matches(&event) => latest = event,
+                    Ok(_) => continue,
+                    Err(RecvTimeoutError::Timeout) => break false,
+                    Err(RecvTimeoutError::Disconnected) => break true,
+                }
+            };
+            if sender.send(latest).is_err() || disconnected {
+                return;
+            }
+        }
+    });
+    coalesced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_test() {
+        let watchers = Watchers::default();
+        let receiver = watchers.subscribe("user:");
+        let dropped = watchers.subscribe("user:");
+        drop(dropped);
+
+        // non-matching keys do not prune the subscription
+        watchers.notify("session:9", ChangeKind::Set);
+        assert_eq!(watchers.subscriptions.lock().unwrap().len(), 2);
+
+        watchers.notify("user:1", ChangeKind::Removed);
+        assert_eq!(watchers.subscriptions.lock().unwrap().len(), 1);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            ChangeEvent {
+                key: "user:1".into(),
+                kind: ChangeKind::Removed
+            }
+        );
+
+        drop(receiver);
+        watchers.notify("user:2", ChangeKind::Set);
+        assert!(watchers.is_empty());
+
+        // a key subscription is not notified of the keys it is a prefix of
+        let receiver = watchers.subscribe_key("user:1");
+        watchers.notify("user:10", ChangeKind::Set);
+        watchers.notify("user:1", ChangeKind::Set);
+        let events: Vec<ChangeEvent> = receiver.try_iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].key, "user:1");
+    }
+}
@@ -0,0 +1,65 @@
+#[test]
+fn audit_log() {
+    use anor_storage::{Storage, StorageItem};
+    use anor_utils::config::{Config, StorageConfig};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[derive(serde::Deserialize)]
+    struct AuditRecord {
+        ts: u64,
+        op: String,
+        key: Option<String>,
+        principal: Option<String>,
+    }
+
+    let data_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("tmp")
+        .join("audit_log");
+    _ = std::fs::remove_dir_all(&data_path);
+
+    let config = Arc::new(Config {
+        storage: Some(StorageConfig {
+            data_path: data_path.clone(),
+            audit: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    let storage = Storage::open_with_config(config);
+    storage
+        .insert(StorageItem::new("user:1", &String::from("abc")).unwrap())
+        .unwrap();
+    assert!(storage.update_inner_object("user:1", &String::from("def")));
+    assert!(!storage.remove("missing"));
+    assert!(storage.remove("user:1"));
+    let mut batch = storage.batch();
+    batch.insert(StorageItem::new("user:\"2\"", &1_u8).unwrap());
+    batch.commit().unwrap();
+    storage.clear();
+    drop(storage);
+
+    let log = std::fs::read_to_string(data_path.join("audit.log")).unwrap();
+    let records: Vec<AuditRecord> = log
+        .lines()
+        .map(|line| serde_yaml::from_str(line).unwrap())
+        .collect();
+
+    let expected = [
+        ("insert", Some("user:1")),
+        ("update", Some("user:1")),
+        ("remove", Some("user:1")),
+        ("insert", Some("user:\"2\"")),
+        ("clear", None),
+    ];
+    assert_eq!(records.len(), expected.len());
+    for (record, (op, key)) in records.iter().zip(expected) {
+        assert_eq!(record.op, op);
+        assert_eq!(record.key.as_deref(), key);
+        assert!(record.principal.is_none());
+        assert!(record.ts > 0);
+    }
+    assert!(records.windows(2).all(|pair| pair[0].ts <= pair[1].ts));
+}
@@ -0,0 +1,88 @@
+#[test]
+fn lock_handoff() {
+    use anor_storage::Storage;
+    use anor_utils::config::{Config, StorageConfig};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    let data_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("tmp")
+        .join("lock_handoff");
+
+    let config_with_wait = |lock_wait: Duration| {
+        Arc::new(Config {
+            storage: Some(StorageConfig {
+                data_path: data_path.clone(),
+                lock_wait,
+                ..Default::default()
+            }),
+            api: None,
+            http: None,
+            remote: None,
+        })
+    };
+
+    let storage = Storage::open_with_config(config_with_wait(Duration::ZERO));
+
+    // the lock is not released within the wait duration
+    let result = Storage::try_open_with_config(config_with_wait(Duration::from_millis(100)));
+    let err = result.err().unwrap();
+    assert!(err.contains("Could not obtain a lock"));
+    assert!(err.contains(&format!("pid: {}", std::process::id())));
+
+    // the previous instance is closed on a timer
+    let handoff_delay = Duration::from_millis(300);
+    let closing = thread::spawn(move || {
+        thread::sleep(handoff_delay);
+        drop(storage);
+    });
+
+    // the second instance waits for the handoff
+    let started_on = Instant::now();
+    let result = Storage::try_open_with_config(config_with_wait(Duration::from_secs(5)));
+    assert!(result.is_ok());
+    assert!(started_on.elapsed() >= handoff_delay / 2);
+
+    closing.join().unwrap();
+}
+
+#[test]
+fn lock_owner() {
+    use anor_storage::Storage;
+    use anor_utils::config::{Config, StorageConfig};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    let data_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("tmp")
+        .join("lock_owner");
+
+    let config = Arc::new(Config {
+        storage: Some(StorageConfig {
+            data_path: data_path.clone(),
+            ..Default::default()
+        }),
+        api: None,
+        http: None,
+        remote: None,
+    });
+
+    let storage = Storage::open_with_config(config);
+    let lock_file = std::fs::read_to_string(data_path.join("storage-lock")).unwrap();
+    let pid = lock_file
+        .lines()
+        .find_map(|line| line.strip_prefix("pid: "))
+        .unwrap();
+    assert_eq!(pid, std::process::id().to_string());
+    assert!(lock_file.contains("hostname: "));
+    assert!(lock_file.contains("opened_on: "));
+
+    // the owner is cleared on close
+    drop(storage);
+    let lock_file = std::fs::read_to_string(data_path.join("storage-lock")).unwrap();
+    assert!(lock_file.is_empty());
+}
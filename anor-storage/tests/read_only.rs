@@ -0,0 +1,58 @@
+#[test]
+fn read_only() {
+    use anor_storage::{Storage, StorageItem};
+    use anor_utils::config::{Config, StorageConfig};
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let data_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("tmp")
+        .join("read_only");
+
+    let config = Arc::new(Config {
+        storage: Some(StorageConfig {
+            data_path,
+            lock_wait: Duration::from_millis(100),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    // prepare the stored data
+    let storage = Storage::open_with_config(config.clone());
+    storage.clear();
+    storage
+        .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+        .unwrap();
+    drop(storage);
+
+    // two read-only instances share the lock
+    let reader1 = Storage::try_open_read_only_with_config(config.clone()).unwrap();
+    let reader2 = Storage::try_open_read_only_with_config(config.clone()).unwrap();
+    assert!(reader1.is_read_only());
+    for reader in [&reader1, &reader2] {
+        let value: String = reader.get_inner_object("my_string").unwrap();
+        assert_eq!(value, "abc");
+    }
+
+    // write attempts fail and do not modify the storage
+    let result = reader1.insert(StorageItem::new("other", &1_u8).unwrap());
+    assert!(result.unwrap_err().contains("read-only"));
+    assert!(!reader1.remove("my_string"));
+    assert!(!reader1.update_inner_object("my_string", &String::from("def")));
+    reader1.clear();
+    assert!(reader1.flush().is_err());
+    assert_eq!(reader1.keys(), vec!["my_string".to_string()]);
+
+    // exclusive access waits for the read-only instances
+    assert!(Storage::try_open_with_config(config.clone()).is_err());
+
+    drop(reader1);
+    drop(reader2);
+
+    // nothing was flushed on close
+    let storage = Storage::open_with_config(config);
+    assert_eq!(storage.keys(), vec!["my_string".to_string()]);
+}
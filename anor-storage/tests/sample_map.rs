@@ -25,7 +25,7 @@ fn sample_map() {
         storage_item.add_metafield("language", "en");
 
         // insert item into storage
-        storage.insert(storage_item);
+        storage.insert(storage_item).unwrap();
 
         // get the map from the storage by key
         let mut map: HashMap<u8, String> = storage.get_inner_object(key).unwrap();
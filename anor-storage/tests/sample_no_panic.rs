@@ -0,0 +1,59 @@
+#[test]
+fn sample_no_panic() {
+    use anor_storage::{Storage, StorageItem};
+    use anor_utils::config::{Config, StorageConfig};
+    use std::fs;
+    use std::panic;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    let tmp_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("tmp")
+        .join("sample_no_panic");
+    let data_path = tmp_path.join("data");
+    let _ = fs::remove_dir_all(&tmp_path);
+    fs::create_dir_all(&tmp_path).unwrap();
+
+    let config = Arc::new(Config {
+        storage: Some(StorageConfig {
+            data_path: data_path.clone(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+
+    let result = panic::catch_unwind(|| {
+        // the data path is a file, the storage is not opened
+        fs::write(&data_path, b"not a directory").unwrap();
+        assert!(Storage::try_open_with_config(config.clone()).is_err());
+        fs::remove_file(&data_path).unwrap();
+
+        // the storage is opened and flushed
+        let mut storage = Storage::try_open_with_config(config.clone()).unwrap();
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
+        storage.try_flush().unwrap();
+
+        // the item file is corrupted with an unknown packet type
+        let item_id = storage.get("my_string").unwrap().id;
+        let item_path = data_path.join("storage").join(item_id);
+        let mut corrupted = 12_u64.to_be_bytes().to_vec();
+        corrupted.extend_from_slice(&[0, 2, 1, 1]);
+        fs::write(item_path, corrupted).unwrap();
+        assert!(storage.load().is_err());
+
+        // the data path is replaced by a file
+        fs::remove_dir_all(&data_path).unwrap();
+        fs::write(&data_path, b"not a directory").unwrap();
+        storage
+            .insert(StorageItem::new("my_string", &String::from("abc")).unwrap())
+            .unwrap();
+        assert!(storage.try_flush().is_err());
+        assert!(storage.try_close().is_err());
+    });
+    assert!(result.is_ok());
+
+    fs::remove_dir_all(&tmp_path).unwrap();
+}
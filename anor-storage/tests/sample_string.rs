@@ -14,7 +14,7 @@ fn sample_string() {
         let storage_item = StorageItem::new(key, &sample_string).unwrap();
 
         // insert item into storage
-        storage.insert(storage_item);
+        storage.insert(storage_item).unwrap();
 
         // get the string from the storage by key
         let mut string_value: String = storage.get_inner_object(key).unwrap();
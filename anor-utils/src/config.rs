@@ -1,8 +1,14 @@
 use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::io;
 use std::io::Read;
-use std::net::{IpAddr, SocketAddr};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::net::{Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use regex::Regex;
 
 use super::{cargo_profile, envsubst};
 
@@ -11,18 +17,29 @@ const DEFAULT_CONFIG_FILENAME_DEBUG: &str = "anor-config.debug";
 const DEFAULT_CONFIG_FILENAME_TEST: &str = "anor-config.test";
 
 const DEFAULT_STORAGE_DATA_PATH: &str = "/var/anor";
+const DEFAULT_STORAGE_LOCK_WAIT_MILLISECONDS: u64 = 5000;
+
+/// reason of rejecting a zero value of a key expecting a positive number
+const NOT_POSITIVE: &str = "expected a number greater than 0";
 
 const DEFAULT_API_SERVICE_LISTEN_ADDRESS: &str = "127.0.0.1";
 const DEFAULT_API_SERVICE_LISTEN_PORT: u16 = 7311;
 const DEFAULT_API_SERVICE_ENABLED: bool = false;
+const DEFAULT_API_SERVICE_IO_TIMEOUT_MILLISECONDS: u64 = 30000;
+const DEFAULT_API_SERVICE_KEEPALIVE_IDLE_MILLISECONDS: u64 = 60000;
+const DEFAULT_API_SERVICE_KEEPALIVE_INTERVAL_MILLISECONDS: u64 = 10000;
+const DEFAULT_API_SERVICE_MAX_FRAME_BYTES: u64 = 64 * 1024 * 1024;
 
 const DEFAULT_HTTP_SERVICE_LISTEN_ADDRESS: &str = "127.0.0.1";
 const DEFAULT_HTTP_SERVICE_LISTEN_PORT: u16 = 8181;
 const DEFAULT_HTTP_SERVICE_ENABLED: bool = false;
+const DEFAULT_HTTP_SERVICE_MAX_BODY_BYTES: u64 = 1024 * 1024;
+const DEFAULT_HTTP_CORS_ALLOW_HEADERS: &str = "Content-Type";
 
 const DEFAULT_REMOTE_NODE: &str = "127.0.0.1:9191";
+const DEFAULT_REMOTE_NODE_PRIORITY: u32 = 0;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Config {
     pub storage: Option<StorageConfig>,
     pub api: Option<ApiConfig>,
@@ -33,41 +50,556 @@ pub struct Config {
 #[derive(Debug)]
 pub struct StorageConfig {
     pub data_path: PathBuf,
+    pub key_policy: KeyPolicy,
+    /// time to wait for the instance lock of the data path held by another instance
+    pub lock_wait: Duration,
+    /// number of writes after which the storage is flushed automatically
+    pub flush_every_writes: Option<usize>,
+    /// whether mutations are recorded in the audit log of the data path
+    pub audit: bool,
+    /// lock wait duration above which a warning is logged
+    pub slow_lock_warn: Option<Duration>,
+    /// directory of the temporary files of atomic writes, the data path if not set
+    /// needs to be on the same file system as the data path
+    pub temp_dir: Option<PathBuf>,
+    /// maximum number of tags per item, unbounded if not set
+    pub max_tags: Option<usize>,
+    /// maximum number of metafields per item, unbounded if not set
+    pub max_metafields: Option<usize>,
+    /// whether items already expired are dropped on load and their files removed
+    pub evict_expired_on_load: bool,
+    /// whether the storage is opened read-only, e.g. by replicas, mutations are rejected by the storage and the services
+    pub read_only: bool,
+    /// scheme of the ids generated for new items
+    pub id_scheme: IdScheme,
+    /// number of items the storage is sized for at open, the persisted item count is reserved on load anyway
+    pub initial_capacity: Option<usize>,
+    /// interval of the automatic flush of a modified storage by the server, not flushed periodically if not set
+    pub flush_interval: Option<Duration>,
+    /// whether the files written by a flush are synced to disk before the flush completes
+    pub durable: bool,
+    /// maximum total size of the item data in bytes, inserts beyond are rejected, unbounded if not set
+    pub max_bytes: Option<usize>,
+    /// time to live of the items inserted without an expiry, by key prefix, the longest matching prefix applies
+    pub ttl_rules: Vec<(String, Duration)>,
+}
+
+impl StorageConfig {
+    /// Returns the directory of the temporary files, the data path by default
+    pub fn temp_path(&self) -> &Path {
+        self.temp_dir.as_deref().unwrap_or(&self.data_path)
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            data_path: PathBuf::from(DEFAULT_STORAGE_DATA_PATH),
+            key_policy: KeyPolicy::default(),
+            lock_wait: Duration::from_millis(DEFAULT_STORAGE_LOCK_WAIT_MILLISECONDS),
+            flush_every_writes: None,
+            audit: false,
+            slow_lock_warn: None,
+            temp_dir: None,
+            max_tags: None,
+            max_metafields: None,
+            evict_expired_on_load: true,
+            read_only: false,
+            id_scheme: IdScheme::default(),
+            initial_capacity: None,
+            flush_interval: None,
+            durable: false,
+            max_bytes: None,
+            ttl_rules: vec![],
+        }
+    }
+}
+
+/// Scheme of the generated item ids
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdScheme {
+    /// random UUID v4
+    #[default]
+    Uuid4,
+    /// time-ordered UUID v7, ids of later items sort after the ids of earlier ones
+    Uuid7,
+}
+
+impl FromStr for IdScheme {
+    type Err = String;
+
+    /// Parses `uuid4` or `uuid7`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "uuid4" => Ok(IdScheme::Uuid4),
+            "uuid7" => Ok(IdScheme::Uuid7),
+            _ => Err(format!("Unknown id scheme: `{}`", s)),
+        }
+    }
+}
+
+impl Display for IdScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdScheme::Uuid4 => write!(f, "uuid4"),
+            IdScheme::Uuid7 => write!(f, "uuid7"),
+        }
+    }
+}
+
+/// Rules for the keys of stored items
+#[derive(Debug, Clone, Default)]
+pub struct KeyPolicy {
+    /// maximum key length in bytes
+    pub max_key_len: Option<usize>,
+    /// pattern the whole key needs to match
+    pub allowed_pattern: Option<Regex>,
+    /// keys are converted to lowercase
+    pub lowercase: bool,
 }
 
 #[derive(Debug)]
 pub struct ApiConfig {
-    pub listen_on: Vec<SocketAddr>,
+    pub listen_on: Vec<NetworkAddress>,
     pub enabled: bool,
+    /// time to wait for a socket read or write before the connection is closed, zero disables the timeout
+    pub io_timeout: Duration,
+    /// time to wait for the next request before the connection is closed, `io_timeout` if not set
+    pub idle_timeout: Option<Duration>,
+    /// TCP keepalive of the connections, disabled if not set
+    pub keepalive: Option<KeepaliveConfig>,
+    /// maximum number of simultaneous connections from a client IP, unlimited if not set
+    pub max_conns_per_ip: Option<usize>,
+    /// number of retries with backoff if the listen address cannot be bound at start
+    pub bind_retries: u32,
+    /// maximum size of a received frame, the connection sending a larger frame is closed
+    pub max_frame_bytes: u64,
+}
+
+/// TCP keepalive parameters of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// idle time before the first keepalive probe is sent
+    pub idle: Duration,
+    /// time between unanswered keepalive probes
+    pub interval: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        KeepaliveConfig {
+            idle: Duration::from_millis(DEFAULT_API_SERVICE_KEEPALIVE_IDLE_MILLISECONDS),
+            interval: Duration::from_millis(DEFAULT_API_SERVICE_KEEPALIVE_INTERVAL_MILLISECONDS),
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            listen_on: vec![NetworkAddress {
+                host: DEFAULT_API_SERVICE_LISTEN_ADDRESS.to_string(),
+                port: DEFAULT_API_SERVICE_LISTEN_PORT,
+            }],
+            enabled: DEFAULT_API_SERVICE_ENABLED,
+            io_timeout: Duration::from_millis(DEFAULT_API_SERVICE_IO_TIMEOUT_MILLISECONDS),
+            idle_timeout: None,
+            keepalive: Some(KeepaliveConfig::default()),
+            max_conns_per_ip: None,
+            bind_retries: 0,
+            max_frame_bytes: DEFAULT_API_SERVICE_MAX_FRAME_BYTES,
+        }
+    }
+}
+
+impl ApiConfig {
+    /// Returns the socket read and write timeout, `None` if disabled
+    pub fn socket_timeout(&self) -> Option<Duration> {
+        if self.io_timeout.is_zero() {
+            None
+        } else {
+            Some(self.io_timeout)
+        }
+    }
+
+    /// Returns the time to wait for the next request on a connection, `None` if disabled
+    pub fn connection_idle_timeout(&self) -> Option<Duration> {
+        match self.idle_timeout {
+            Some(idle_timeout) if idle_timeout.is_zero() => None,
+            Some(idle_timeout) => Some(idle_timeout),
+            None => self.socket_timeout(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct HttpConfig {
-    pub listen_on: Vec<SocketAddr>,
+    pub listen_on: Vec<NetworkAddress>,
     pub enabled: bool,
+    /// maximum size of a request body, larger requests are rejected with `413 Payload Too Large`
+    pub max_body_bytes: u64,
+    /// whether every handled request is logged at info level
+    pub access_log: bool,
+    /// time to handle a request, longer requests are answered with `503 Service Unavailable`, unbounded if not set
+    pub request_timeout: Option<Duration>,
+    /// number of retries with backoff if the listen address cannot be bound at start
+    pub bind_retries: u32,
+    /// cross-origin access of browser clients, disabled if not set
+    pub cors: Option<CorsConfig>,
+    /// bearer token required to write or remove the storage items, not required if not set
+    pub access_token: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            listen_on: vec![NetworkAddress {
+                host: DEFAULT_HTTP_SERVICE_LISTEN_ADDRESS.to_string(),
+                port: DEFAULT_HTTP_SERVICE_LISTEN_PORT,
+            }],
+            enabled: DEFAULT_HTTP_SERVICE_ENABLED,
+            max_body_bytes: DEFAULT_HTTP_SERVICE_MAX_BODY_BYTES,
+            access_log: false,
+            request_timeout: None,
+            bind_retries: 0,
+            cors: None,
+            access_token: None,
+        }
+    }
+}
+
+/// Cross-Origin Resource Sharing parameters of the http service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorsConfig {
+    /// origin allowed to access the service, `*` allows any origin
+    pub allow_origin: String,
+    /// request headers allowed in cross-origin requests
+    pub allow_headers: Vec<String>,
 }
 
 #[derive(Debug)]
 pub struct RemoteConfig {
-    pub nodes: Vec<SocketAddr>,
+    pub nodes: Vec<RemoteNode>,
+}
+
+impl RemoteConfig {
+    /// Returns the nodes ordered by priority, higher priorities first
+    /// Nodes with the same priority keep the configured order
+    pub fn nodes_by_priority(&self) -> Vec<&RemoteNode> {
+        let mut nodes: Vec<_> = self.nodes.iter().collect();
+        nodes.sort_by_key(|node| std::cmp::Reverse(node.priority));
+        nodes
+    }
+}
+
+/// Remote node address with a priority for the client node selection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteNode {
+    pub address: NetworkAddress,
+    pub priority: u32,
+}
+
+impl From<NetworkAddress> for RemoteNode {
+    fn from(address: NetworkAddress) -> Self {
+        RemoteNode {
+            address,
+            priority: DEFAULT_REMOTE_NODE_PRIORITY,
+        }
+    }
+}
+
+impl FromStr for RemoteNode {
+    type Err = String;
+
+    /// Parses `host:port` or `host:port#priority`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (address, priority) = match s.split_once('#') {
+            Some((address, priority)) => {
+                let priority = priority
+                    .trim()
+                    .parse()
+                    .map_err(|err| format!("Invalid priority in node `{}`: {}", s, err))?;
+                (address, priority)
+            }
+            None => (s, DEFAULT_REMOTE_NODE_PRIORITY),
+        };
+
+        Ok(RemoteNode {
+            address: address.parse()?,
+            priority,
+        })
+    }
+}
+
+impl Display for RemoteNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.priority == DEFAULT_REMOTE_NODE_PRIORITY {
+            write!(f, "{}", self.address)
+        } else {
+            write!(f, "{}#{}", self.address, self.priority)
+        }
+    }
+}
+
+/// Network address given by a host name or an IP address, and a port
+/// The host is resolved on use (bind or connect), so DNS changes are picked up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkAddress {
+    pub host: String,
+    pub port: u16,
+}
+
+impl NetworkAddress {
+    pub fn new(host: &str, port: u16) -> Result<Self, String> {
+        let host = host.trim();
+        let host = match host.strip_prefix('[') {
+            Some(bracketed) => match bracketed.strip_suffix(']') {
+                Some(ipv6) => ipv6,
+                None => return Err(format!("Unclosed bracket in host: `{}`", host)),
+            },
+            None => host,
+        };
+
+        if host.contains(':') {
+            if let Err(err) = host.parse::<Ipv6Addr>() {
+                return Err(format!("Invalid IPv6 address `{}`: {}", host, err));
+            }
+        } else if host.is_empty()
+            || !host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+        {
+            return Err(format!("Invalid host name: `{}`", host));
+        }
+
+        Ok(NetworkAddress {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl FromStr for NetworkAddress {
+    type Err = String;
+
+    /// Parses `host:port`, `ip:port` or `[ipv6]:port`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (host, port) = match s.rsplit_once(':') {
+            Some(parts) => parts,
+            None => return Err(format!("Missing port in address: `{}`", s)),
+        };
+
+        if host.contains(':') && !host.starts_with('[') {
+            return Err(format!(
+                "IPv6 address must be enclosed in brackets, e.g. `[::1]:9191`: `{}`",
+                s
+            ));
+        }
+
+        let port = port
+            .parse()
+            .map_err(|err| format!("Invalid port in address `{}`: {}", s, err))?;
+
+        Self::new(host, port)
+    }
+}
+
+impl Display for NetworkAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+impl ToSocketAddrs for NetworkAddress {
+    type Iter = std::vec::IntoIter<SocketAddr>;
+
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        (self.host.as_str(), self.port).to_socket_addrs()
+    }
+}
+
+impl Display for Config {
+    /// Formats a normalized summary of the resolved configuration
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(storage) = &self.storage {
+            writeln!(f, "storage:")?;
+            writeln!(f, "  data_path: {}", storage.data_path.to_string_lossy())?;
+            let key_policy = &storage.key_policy;
+            if let Some(max_key_len) = key_policy.max_key_len {
+                writeln!(f, "  max_key_len: {}", max_key_len)?;
+            }
+            if let Some(allowed_pattern) = &key_policy.allowed_pattern {
+                writeln!(f, "  allowed_pattern: {}", allowed_pattern)?;
+            }
+            writeln!(f, "  lowercase: {}", key_policy.lowercase)?;
+            writeln!(f, "  lock_wait_ms: {}", storage.lock_wait.as_millis())?;
+            if let Some(flush_every_writes) = storage.flush_every_writes {
+                writeln!(f, "  flush_every_writes: {}", flush_every_writes)?;
+            }
+            writeln!(f, "  audit: {}", storage.audit)?;
+            if let Some(slow_lock_warn) = storage.slow_lock_warn {
+                writeln!(f, "  slow_lock_warn_ms: {}", slow_lock_warn.as_millis())?;
+            }
+            if let Some(temp_dir) = &storage.temp_dir {
+                writeln!(f, "  temp_dir: {}", temp_dir.to_string_lossy())?;
+            }
+            if let Some(max_tags) = storage.max_tags {
+                writeln!(f, "  max_tags: {}", max_tags)?;
+            }
+            if let Some(max_metafields) = storage.max_metafields {
+                writeln!(f, "  max_metafields: {}", max_metafields)?;
+            }
+            writeln!(
+                f,
+                "  evict_expired_on_load: {}",
+                storage.evict_expired_on_load
+            )?;
+            writeln!(f, "  read_only: {}", storage.read_only)?;
+            writeln!(f, "  id_scheme: {}", storage.id_scheme)?;
+            if let Some(initial_capacity) = storage.initial_capacity {
+                writeln!(f, "  initial_capacity: {}", initial_capacity)?;
+            }
+            if let Some(flush_interval) = storage.flush_interval {
+                writeln!(f, "  flush_interval_ms: {}", flush_interval.as_millis())?;
+            }
+            writeln!(f, "  durable: {}", storage.durable)?;
+            if let Some(max_bytes) = storage.max_bytes {
+                writeln!(f, "  max_bytes: {}", max_bytes)?;
+            }
+            if !storage.ttl_rules.is_empty() {
+                let ttl_rules: Vec<String> = storage
+                    .ttl_rules
+                    .iter()
+                    .map(|(prefix, ttl)| format!("{}={}", prefix, ttl.as_secs()))
+                    .collect();
+                writeln!(f, "  ttl_rules: {}", ttl_rules.join(", "))?;
+            }
+        }
+        if let Some(api) = &self.api {
+            writeln!(f, "api:")?;
+            writeln!(f, "  listen_on: {}", join_display(&api.listen_on))?;
+            writeln!(f, "  enabled: {}", api.enabled)?;
+            writeln!(f, "  io_timeout_ms: {}", api.io_timeout.as_millis())?;
+            if let Some(idle_timeout) = api.idle_timeout {
+                writeln!(f, "  idle_timeout_ms: {}", idle_timeout.as_millis())?;
+            }
+            match &api.keepalive {
+                Some(keepalive) => {
+                    writeln!(f, "  keepalive_idle_ms: {}", keepalive.idle.as_millis())?;
+                    writeln!(
+                        f,
+                        "  keepalive_interval_ms: {}",
+                        keepalive.interval.as_millis()
+                    )?;
+                }
+                None => writeln!(f, "  keepalive: false")?,
+            }
+            if let Some(max_conns_per_ip) = api.max_conns_per_ip {
+                writeln!(f, "  max_conns_per_ip: {}", max_conns_per_ip)?;
+            }
+            writeln!(f, "  bind_retries: {}", api.bind_retries)?;
+            writeln!(f, "  max_frame_bytes: {}", api.max_frame_bytes)?;
+        }
+        if let Some(http) = &self.http {
+            writeln!(f, "http:")?;
+            writeln!(f, "  listen_on: {}", join_display(&http.listen_on))?;
+            writeln!(f, "  enabled: {}", http.enabled)?;
+            writeln!(f, "  max_body_bytes: {}", http.max_body_bytes)?;
+            writeln!(f, "  access_log: {}", http.access_log)?;
+            if let Some(request_timeout) = http.request_timeout {
+                writeln!(f, "  request_timeout_ms: {}", request_timeout.as_millis())?;
+            }
+            writeln!(f, "  bind_retries: {}", http.bind_retries)?;
+            if let Some(cors) = &http.cors {
+                writeln!(f, "  cors_allow_origin: {}", cors.allow_origin)?;
+                writeln!(f, "  cors_allow_headers: {}", cors.allow_headers.join(", "))?;
+            }
+            if http.access_token.is_some() {
+                // the token is a secret, only its presence is shown
+                writeln!(f, "  access_token: <set>")?;
+            }
+        }
+        if let Some(remote) = &self.remote {
+            writeln!(f, "remote:")?;
+            writeln!(f, "  nodes: {}", join_display(&remote.nodes))?;
+        }
+        Ok(())
+    }
+}
+
+fn join_display<T: Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
+/// Loads the configuration file according to the build profile
+/// The file is parsed once, repeated calls return the same configuration, see [`try_load`]
+///
+/// # Panics
+///
+/// Panics if the configuration could not be loaded, see [`try_load`] for a non-panicking variant.
 pub fn load() -> Arc<Config> {
-    let config_filename = get_config_filename();
-    let mut config_file = std::fs::File::open(config_filename)
-        .unwrap_or_else(|_| panic!("Could not open {} file.", config_filename));
+    match try_load() {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("{}", err);
+            panic!("{}", err);
+        }
+    }
+}
+
+/// Loads and validates the configuration file according to the build profile
+/// The loaded configuration is kept for the process, repeated calls return the same `Arc` without reading the file again
+/// A failed load is not kept, the next call reads the file again
+pub fn try_load() -> Result<Arc<Config>, String> {
+    static CONFIG: OnceLock<Arc<Config>> = OnceLock::new();
+    if let Some(config) = CONFIG.get() {
+        return Ok(config.clone());
+    }
+    let config = try_load_from(get_config_filename())?;
+    // a concurrent first load may have won, all callers get the kept configuration
+    Ok(CONFIG.get_or_init(|| config).clone())
+}
+
+/// Loads and validates the specified configuration file
+pub fn try_load_from<P: AsRef<Path>>(config_filename: P) -> Result<Arc<Config>, String> {
+    let config_filename = config_filename.as_ref();
+    let mut config_file = std::fs::File::open(config_filename).map_err(|err| {
+        format!(
+            "Could not open {} file. Error Message: {}",
+            config_filename.to_string_lossy(),
+            err
+        )
+    })?;
 
     let mut config_content = String::new();
     if let Err(err) = config_file.read_to_string(&mut config_content) {
-        tracing::error!("{}", err);
-        panic!("{}", err);
+        return Err(format!(
+            "Could not read {} file. Error Message: {}",
+            config_filename.to_string_lossy(),
+            err
+        ));
     }
 
     let config_substituted = envsubst::dollar_curly(&config_content);
 
     let config_map: HashMap<String, HashMap<String, String>> =
-        serde_yaml::from_str(&config_substituted)
-            .unwrap_or_else(|_| panic!("Could not parse {} file.", config_filename));
+        serde_yaml::from_str(&config_substituted).map_err(|err| {
+            format!(
+                "Could not parse {} file. Error Message: {}",
+                config_filename.to_string_lossy(),
+                err
+            )
+        })?;
 
     tracing::trace!("loaded config:\n{:#?}", config_map);
 
@@ -81,51 +613,83 @@ pub fn load() -> Arc<Config> {
     let map_key = "storage";
     if config_map.contains_key(map_key) {
         let config_node = &config_map[map_key];
-        let data_path = parse_storage_path(config_node);
-        config.storage = Some(StorageConfig { data_path });
+        config.storage = Some(parse_storage(map_key, config_node)?);
     }
 
     let map_key = "api";
     if config_map.contains_key(map_key) {
         let config_node = &config_map[map_key];
         let listen_on = parse_listen_on(
+            map_key,
             config_node,
             DEFAULT_API_SERVICE_LISTEN_ADDRESS,
             DEFAULT_API_SERVICE_LISTEN_PORT,
-        );
-        let enabled = parse_enabled(config_node).unwrap_or(DEFAULT_API_SERVICE_ENABLED);
-        config.api = Some(ApiConfig { listen_on, enabled });
+        )?;
+        let enabled = parse_enabled(map_key, config_node)?.unwrap_or(DEFAULT_API_SERVICE_ENABLED);
+        let io_timeout = parse_io_timeout(map_key, config_node)?;
+        let idle_timeout = parse_idle_timeout(map_key, config_node)?;
+        let keepalive = parse_keepalive(map_key, config_node)?;
+        let max_conns_per_ip = parse_max_conns_per_ip(map_key, config_node)?;
+        let bind_retries = parse_bind_retries(map_key, config_node)?;
+        let max_frame_bytes = parse_max_frame_bytes(map_key, config_node)?;
+        config.api = Some(ApiConfig {
+            listen_on,
+            enabled,
+            io_timeout,
+            idle_timeout,
+            keepalive,
+            max_conns_per_ip,
+            bind_retries,
+            max_frame_bytes,
+        });
     }
 
     let map_key = "http";
     if config_map.contains_key(map_key) {
         let config_node = &config_map[map_key];
         let listen_on = parse_listen_on(
+            map_key,
             config_node,
             DEFAULT_HTTP_SERVICE_LISTEN_ADDRESS,
             DEFAULT_HTTP_SERVICE_LISTEN_PORT,
-        );
-        let enabled = parse_enabled(config_node).unwrap_or(DEFAULT_HTTP_SERVICE_ENABLED);
-        config.http = Some(HttpConfig { listen_on, enabled });
+        )?;
+        let enabled = parse_enabled(map_key, config_node)?.unwrap_or(DEFAULT_HTTP_SERVICE_ENABLED);
+        let max_body_bytes = parse_max_body_bytes(map_key, config_node)?;
+        let access_log = parse_access_log(map_key, config_node)?;
+        let request_timeout = parse_request_timeout(map_key, config_node)?;
+        let bind_retries = parse_bind_retries(map_key, config_node)?;
+        let cors = parse_cors(map_key, config_node)?;
+        let access_token = parse_access_token(map_key, config_node)?;
+        config.http = Some(HttpConfig {
+            listen_on,
+            enabled,
+            max_body_bytes,
+            access_log,
+            request_timeout,
+            bind_retries,
+            cors,
+            access_token,
+        });
     }
 
     let map_key = "remote";
     if config_map.contains_key(map_key) {
         let config_node = &config_map[map_key];
-        let remote = parse_remote(config_node);
+        let remote = parse_remote(map_key, config_node)?;
         config.remote = Some(remote);
     }
 
     tracing::debug!("parsed config:\n{:#?}", config);
 
-    Arc::new(config)
+    Ok(Arc::new(config))
 }
 
 fn parse_listen_on(
+    section: &str,
     node: &HashMap<String, String>,
     default_listen_address: &str,
     default_listen_port: u16,
-) -> Vec<SocketAddr> {
+) -> Result<Vec<NetworkAddress>, String> {
     let node_key = "listen_addresses";
     let listen_addresses = if node.contains_key(node_key) {
         node[node_key]
@@ -140,23 +704,65 @@ fn parse_listen_on(
 
     let node_key = "listen_port";
     let port = if node.contains_key(node_key) {
-        node[node_key].parse().unwrap()
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?
     } else {
         default_listen_port
     };
 
     tracing::trace!("config: listen_port: {}", port);
 
-    let mut listen_on = Vec::<SocketAddr>::with_capacity(listen_addresses.len());
+    let mut listen_on = Vec::<NetworkAddress>::with_capacity(listen_addresses.len());
     for listen_addres in listen_addresses {
-        let ip_address: IpAddr = listen_addres.parse().unwrap();
-        let socket_addres = SocketAddr::new(ip_address, port);
-        listen_on.push(socket_addres);
+        let network_address = NetworkAddress::new(listen_addres, port)
+            .map_err(|err| invalid_value(section, "listen_addresses", listen_addres, err))?;
+        listen_on.push(network_address);
     }
 
     tracing::trace!("parsed: listen_on: {:?}", listen_on);
 
-    listen_on
+    Ok(listen_on)
+}
+
+/// Parses the storage section, the keys not given get their defaults
+fn parse_storage(section: &str, node: &HashMap<String, String>) -> Result<StorageConfig, String> {
+    let data_path = parse_storage_path(node);
+    let key_policy = parse_key_policy(section, node)?;
+    let lock_wait = parse_lock_wait(section, node)?;
+    let flush_every_writes = parse_flush_every_writes(section, node)?;
+    let audit = parse_audit(section, node)?;
+    let slow_lock_warn = parse_slow_lock_warn(section, node)?;
+    let temp_dir = parse_temp_dir(node);
+    let max_tags = parse_item_limit(section, node, "max_tags")?;
+    let max_metafields = parse_item_limit(section, node, "max_metafields")?;
+    let evict_expired_on_load = parse_evict_expired_on_load(section, node)?;
+    let read_only = parse_read_only(section, node)?;
+    let id_scheme = parse_id_scheme(section, node)?;
+    let initial_capacity = parse_initial_capacity(section, node)?;
+    let flush_interval = parse_flush_interval(section, node)?;
+    let durable = parse_durable(section, node)?;
+    let max_bytes = parse_max_bytes(section, node)?;
+    let ttl_rules = parse_ttl_rules(section, node)?;
+    Ok(StorageConfig {
+        data_path,
+        key_policy,
+        lock_wait,
+        flush_every_writes,
+        audit,
+        slow_lock_warn,
+        temp_dir,
+        max_tags,
+        max_metafields,
+        evict_expired_on_load,
+        read_only,
+        id_scheme,
+        initial_capacity,
+        flush_interval,
+        durable,
+        max_bytes,
+        ttl_rules,
+    })
 }
 
 fn parse_storage_path(node: &HashMap<String, String>) -> PathBuf {
@@ -170,7 +776,337 @@ fn parse_storage_path(node: &HashMap<String, String>) -> PathBuf {
     PathBuf::from(storage_path)
 }
 
-fn parse_remote(node: &HashMap<String, String>) -> RemoteConfig {
+fn parse_temp_dir(node: &HashMap<String, String>) -> Option<PathBuf> {
+    let node_key = "temp_dir";
+    node.get(node_key).map(PathBuf::from)
+}
+
+fn parse_lock_wait(section: &str, node: &HashMap<String, String>) -> Result<Duration, String> {
+    let node_key = "lock_wait_ms";
+    let lock_wait_ms = if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?
+    } else {
+        DEFAULT_STORAGE_LOCK_WAIT_MILLISECONDS
+    };
+    Ok(Duration::from_millis(lock_wait_ms))
+}
+
+fn parse_io_timeout(section: &str, node: &HashMap<String, String>) -> Result<Duration, String> {
+    let node_key = "io_timeout_ms";
+    let io_timeout_ms = if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?
+    } else {
+        DEFAULT_API_SERVICE_IO_TIMEOUT_MILLISECONDS
+    };
+    Ok(Duration::from_millis(io_timeout_ms))
+}
+
+fn parse_idle_timeout(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<Duration>, String> {
+    let node_key = "idle_timeout_ms";
+    if node.contains_key(node_key) {
+        let idle_timeout_ms = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        Ok(Some(Duration::from_millis(idle_timeout_ms)))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_request_timeout(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<Duration>, String> {
+    let node_key = "request_timeout_ms";
+    if node.contains_key(node_key) {
+        let request_timeout_ms = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        Ok(Some(Duration::from_millis(request_timeout_ms)))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_bind_retries(section: &str, node: &HashMap<String, String>) -> Result<u32, String> {
+    let node_key = "bind_retries";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
+    } else {
+        Ok(0)
+    }
+}
+
+fn parse_cors(section: &str, node: &HashMap<String, String>) -> Result<Option<CorsConfig>, String> {
+    let node_key = "cors_allow_origin";
+    let allow_origin = match node.get(node_key).map(|value| value.trim()) {
+        Some(allow_origin) if !allow_origin.is_empty() => allow_origin.to_string(),
+        Some(allow_origin) => {
+            let err = invalid_value(section, node_key, allow_origin, "empty origin");
+            return Err(err);
+        }
+        None => return Ok(None),
+    };
+
+    let node_key = "cors_allow_headers";
+    let allow_headers = node
+        .get(node_key)
+        .map_or(DEFAULT_HTTP_CORS_ALLOW_HEADERS, String::as_str)
+        .split(',')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok(Some(CorsConfig {
+        allow_origin,
+        allow_headers,
+    }))
+}
+
+fn parse_access_token(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<String>, String> {
+    let node_key = "access_token";
+    match node.get(node_key).map(|value| value.trim()) {
+        Some(access_token) if !access_token.is_empty() => Ok(Some(access_token.to_string())),
+        Some(access_token) => {
+            let err = invalid_value(section, node_key, access_token, "empty token");
+            Err(err)
+        }
+        None => Ok(None),
+    }
+}
+
+fn parse_keepalive(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<KeepaliveConfig>, String> {
+    let node_key = "keepalive";
+    if node.contains_key(node_key) {
+        let keepalive: bool = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        if !keepalive {
+            return Ok(None);
+        }
+    }
+
+    let mut keepalive = KeepaliveConfig::default();
+    let node_key = "keepalive_idle_ms";
+    if node.contains_key(node_key) {
+        let idle_ms = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        keepalive.idle = Duration::from_millis(idle_ms);
+    }
+    let node_key = "keepalive_interval_ms";
+    if node.contains_key(node_key) {
+        let interval_ms = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        keepalive.interval = Duration::from_millis(interval_ms);
+    }
+    Ok(Some(keepalive))
+}
+
+fn parse_max_conns_per_ip(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<usize>, String> {
+    let node_key = "max_conns_per_ip";
+    if node.contains_key(node_key) {
+        let max_conns_per_ip = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        Ok(Some(max_conns_per_ip))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_max_frame_bytes(section: &str, node: &HashMap<String, String>) -> Result<u64, String> {
+    let node_key = "max_frame_bytes";
+    if node.contains_key(node_key) {
+        let max_frame_bytes = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        if max_frame_bytes == 0 {
+            let err = invalid_value(section, node_key, &node[node_key], NOT_POSITIVE);
+            return Err(err);
+        }
+        Ok(max_frame_bytes)
+    } else {
+        Ok(DEFAULT_API_SERVICE_MAX_FRAME_BYTES)
+    }
+}
+
+fn parse_flush_every_writes(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<usize>, String> {
+    let node_key = "flush_every_writes";
+    if node.contains_key(node_key) {
+        let flush_every_writes = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        if flush_every_writes == 0 {
+            let err = invalid_value(section, node_key, &node[node_key], NOT_POSITIVE);
+            return Err(err);
+        }
+        Ok(Some(flush_every_writes))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_flush_interval(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<Duration>, String> {
+    let node_key = "flush_interval_ms";
+    if node.contains_key(node_key) {
+        let flush_interval_ms = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        if flush_interval_ms == 0 {
+            let err = invalid_value(section, node_key, &node[node_key], NOT_POSITIVE);
+            return Err(err);
+        }
+        Ok(Some(Duration::from_millis(flush_interval_ms)))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_durable(section: &str, node: &HashMap<String, String>) -> Result<bool, String> {
+    let node_key = "durable";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
+    } else {
+        Ok(false)
+    }
+}
+
+fn parse_max_bytes(section: &str, node: &HashMap<String, String>) -> Result<Option<usize>, String> {
+    let node_key = "max_bytes";
+    if node.contains_key(node_key) {
+        let max_bytes = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        if max_bytes == 0 {
+            let err = invalid_value(section, node_key, &node[node_key], NOT_POSITIVE);
+            return Err(err);
+        }
+        Ok(Some(max_bytes))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_item_limit(
+    section: &str,
+    node: &HashMap<String, String>,
+    node_key: &str,
+) -> Result<Option<usize>, String> {
+    if node.contains_key(node_key) {
+        let limit = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        Ok(Some(limit))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_initial_capacity(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<usize>, String> {
+    let node_key = "initial_capacity";
+    if node.contains_key(node_key) {
+        let initial_capacity = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        Ok(Some(initial_capacity))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Parses the comma separated `prefix=seconds` rules, e.g. `session:=1800, cache:=60`
+fn parse_ttl_rules(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Vec<(String, Duration)>, String> {
+    let node_key = "ttl_rules";
+    let mut ttl_rules = vec![];
+    if let Some(value) = node.get(node_key) {
+        for rule in value
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            // the prefix may contain `=`, the time to live follows the last one
+            let (prefix, ttl) = rule.rsplit_once('=').ok_or_else(|| {
+                invalid_value(section, node_key, rule, "expected `prefix=seconds`")
+            })?;
+            let ttl_secs: u64 = ttl
+                .trim()
+                .parse()
+                .map_err(|err| invalid_value(section, node_key, rule, err))?;
+            if ttl_secs == 0 {
+                return Err(invalid_value(section, node_key, rule, NOT_POSITIVE));
+            }
+            ttl_rules.push((prefix.trim().to_string(), Duration::from_secs(ttl_secs)));
+        }
+    }
+    Ok(ttl_rules)
+}
+
+fn parse_key_policy(section: &str, node: &HashMap<String, String>) -> Result<KeyPolicy, String> {
+    let mut key_policy = KeyPolicy::default();
+
+    let node_key = "max_key_len";
+    if node.contains_key(node_key) {
+        let max_key_len = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        key_policy.max_key_len = Some(max_key_len);
+    }
+
+    let node_key = "allowed_pattern";
+    if node.contains_key(node_key) {
+        // the pattern applies to the whole key
+        let allowed_pattern = Regex::new(&format!("^(?:{})$", node[node_key]))
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        key_policy.allowed_pattern = Some(allowed_pattern);
+    }
+
+    let node_key = "lowercase";
+    if node.contains_key(node_key) {
+        key_policy.lowercase = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+    }
+
+    tracing::trace!("parsed: key_policy: {:?}", key_policy);
+
+    Ok(key_policy)
+}
+
+fn parse_remote(section: &str, node: &HashMap<String, String>) -> Result<RemoteConfig, String> {
     let node_key = "nodes";
     let remote_nodes = if node.contains_key(node_key) {
         node[node_key]
@@ -183,26 +1119,122 @@ fn parse_remote(node: &HashMap<String, String>) -> RemoteConfig {
 
     tracing::trace!("config: remote nodes: {:?}", remote_nodes);
 
-    let mut nodes = Vec::<SocketAddr>::with_capacity(remote_nodes.len());
-    for node in remote_nodes {
-        let socket_addr: SocketAddr = node.parse().unwrap();
-        nodes.push(socket_addr);
+    let mut nodes = Vec::<RemoteNode>::with_capacity(remote_nodes.len());
+    for remote_node in remote_nodes {
+        let node: RemoteNode = remote_node
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, remote_node, err))?;
+        nodes.push(node);
     }
 
     tracing::trace!("parsed: remote nodes: {:?}", nodes);
 
-    RemoteConfig { nodes }
+    Ok(RemoteConfig { nodes })
 }
 
-fn parse_enabled(node: &HashMap<String, String>) -> Option<bool> {
+fn parse_enabled(section: &str, node: &HashMap<String, String>) -> Result<Option<bool>, String> {
     let node_key = "enabled";
     if node.contains_key(node_key) {
-        Some(node[node_key].parse().unwrap())
+        let enabled = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        Ok(Some(enabled))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_slow_lock_warn(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<Option<Duration>, String> {
+    let node_key = "slow_lock_warn_ms";
+    if node.contains_key(node_key) {
+        let slow_lock_warn_ms = node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))?;
+        Ok(Some(Duration::from_millis(slow_lock_warn_ms)))
+    } else {
+        Ok(None)
+    }
+}
+
+fn parse_audit(section: &str, node: &HashMap<String, String>) -> Result<bool, String> {
+    let node_key = "audit";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
+    } else {
+        Ok(false)
+    }
+}
+
+fn parse_evict_expired_on_load(
+    section: &str,
+    node: &HashMap<String, String>,
+) -> Result<bool, String> {
+    let node_key = "evict_expired_on_load";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
+    } else {
+        Ok(true)
+    }
+}
+
+fn parse_read_only(section: &str, node: &HashMap<String, String>) -> Result<bool, String> {
+    let node_key = "read_only";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
+    } else {
+        Ok(false)
+    }
+}
+
+fn parse_id_scheme(section: &str, node: &HashMap<String, String>) -> Result<IdScheme, String> {
+    let node_key = "id_scheme";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
     } else {
-        None
+        Ok(IdScheme::default())
     }
 }
 
+fn parse_access_log(section: &str, node: &HashMap<String, String>) -> Result<bool, String> {
+    let node_key = "access_log";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
+    } else {
+        Ok(false)
+    }
+}
+
+fn parse_max_body_bytes(section: &str, node: &HashMap<String, String>) -> Result<u64, String> {
+    let node_key = "max_body_bytes";
+    if node.contains_key(node_key) {
+        node[node_key]
+            .parse()
+            .map_err(|err| invalid_value(section, node_key, &node[node_key], err))
+    } else {
+        Ok(DEFAULT_HTTP_SERVICE_MAX_BODY_BYTES)
+    }
+}
+
+fn invalid_value(section: &str, key: &str, value: &str, err: impl Display) -> String {
+    format!(
+        "Invalid config value `{}` for `{}.{}`. Error Message: {}",
+        value, section, key, err
+    )
+}
+
 fn get_config_filename() -> &'static str {
     if cargo_profile::debug_mode() {
         if cargo_profile::is_profile_test() {
@@ -225,6 +1257,17 @@ mod test {
         assert_eq!(get_config_filename(), DEFAULT_CONFIG_FILENAME_TEST);
     }
 
+    #[test]
+    fn config_load_once_test() {
+        let config = load();
+        assert!(Arc::ptr_eq(&config, &load()));
+        assert!(Arc::ptr_eq(&config, &try_load().unwrap()));
+
+        // an explicitly given file is always read
+        let reloaded = try_load_from(get_config_filename()).unwrap();
+        assert!(!Arc::ptr_eq(&config, &reloaded));
+    }
+
     #[test]
     fn config_storage_test() {
         let config = load();
@@ -236,6 +1279,96 @@ mod test {
             .join("tmp")
             .join("anor");
         assert_eq!(storage.data_path, data_path);
+        assert_eq!(storage.lock_wait, Duration::from_millis(5000));
+    }
+
+    #[test]
+    fn config_storage_tuning_test() {
+        let node: HashMap<String, String> = [
+            ("data_path", "/tmp/anor"),
+            ("flush_every_writes", "100"),
+            ("flush_interval_ms", "30000"),
+            ("durable", "true"),
+            ("max_bytes", "1048576"),
+            ("initial_capacity", "1000"),
+            ("id_scheme", "uuid7"),
+            ("ttl_rules", "session:=1800, cache:=60"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        let storage = parse_storage("storage", &node).unwrap();
+        assert_eq!(storage.data_path, PathBuf::from("/tmp/anor"));
+        assert_eq!(storage.flush_every_writes, Some(100));
+        assert_eq!(storage.flush_interval, Some(Duration::from_secs(30)));
+        assert!(storage.durable);
+        assert_eq!(storage.max_bytes, Some(1048576));
+        assert_eq!(storage.initial_capacity, Some(1000));
+        assert_eq!(storage.id_scheme, IdScheme::Uuid7);
+        assert_eq!(
+            storage.ttl_rules,
+            vec![
+                ("session:".to_string(), Duration::from_secs(1800)),
+                ("cache:".to_string(), Duration::from_secs(60)),
+            ]
+        );
+
+        // the defaults apply to the keys not given
+        let storage = parse_storage("storage", &HashMap::new()).unwrap();
+        let defaults = StorageConfig::default();
+        assert_eq!(storage.data_path, defaults.data_path);
+        assert_eq!(storage.lock_wait, defaults.lock_wait);
+        assert_eq!(storage.flush_every_writes, None);
+        assert_eq!(storage.flush_interval, None);
+        assert!(!storage.durable);
+        assert_eq!(storage.max_bytes, None);
+        assert_eq!(storage.initial_capacity, None);
+        assert!(storage.evict_expired_on_load);
+        assert!(!storage.read_only);
+        assert_eq!(storage.id_scheme, IdScheme::Uuid4);
+        assert!(storage.ttl_rules.is_empty());
+
+        // zero is out of range of the positive knobs
+        for node_key in ["flush_every_writes", "flush_interval_ms", "max_bytes"] {
+            let mut node = HashMap::new();
+            node.insert(node_key.to_string(), "0".to_string());
+            let result = parse_storage("storage", &node);
+            assert!(result
+                .unwrap_err()
+                .contains(&format!("`storage.{}`", node_key)));
+        }
+        for ttl_rules in ["session:", "session:=soon", "session:=0"] {
+            let mut node = HashMap::new();
+            node.insert("ttl_rules".to_string(), ttl_rules.to_string());
+            let result = parse_storage("storage", &node);
+            assert!(result.unwrap_err().contains("`storage.ttl_rules`"));
+        }
+    }
+
+    #[test]
+    fn config_key_policy_test() {
+        let mut node = HashMap::new();
+        node.insert("max_key_len".to_string(), "16".to_string());
+        node.insert("allowed_pattern".to_string(), "[a-z_]+".to_string());
+        node.insert("lowercase".to_string(), "true".to_string());
+        let key_policy = parse_key_policy("storage", &node).unwrap();
+        assert_eq!(key_policy.max_key_len, Some(16));
+        assert!(key_policy.lowercase);
+
+        // the pattern is anchored to the whole key
+        let allowed_pattern = key_policy.allowed_pattern.unwrap();
+        assert!(allowed_pattern.is_match("my_key"));
+        assert!(!allowed_pattern.is_match("my key"));
+
+        let key_policy = parse_key_policy("storage", &HashMap::new()).unwrap();
+        assert!(key_policy.max_key_len.is_none());
+        assert!(key_policy.allowed_pattern.is_none());
+        assert!(!key_policy.lowercase);
+
+        let mut node = HashMap::new();
+        node.insert("allowed_pattern".to_string(), "[a-z".to_string());
+        let result = parse_key_policy("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.allowed_pattern`"));
     }
 
     #[test]
@@ -247,6 +1380,11 @@ mod test {
         assert_eq!(api.listen_on.len(), 1);
         assert_eq!(api.listen_on[0], "127.0.0.1:9191".parse().unwrap());
         assert!(api.enabled);
+        assert_eq!(api.keepalive, Some(KeepaliveConfig::default()));
+
+        let mut node = HashMap::new();
+        node.insert("keepalive".to_string(), "false".to_string());
+        assert_eq!(parse_keepalive("api", &node), Ok(None));
     }
 
     #[test]
@@ -258,6 +1396,32 @@ mod test {
         assert_eq!(http.listen_on.len(), 1);
         assert_eq!(http.listen_on[0], "127.0.0.1:8181".parse().unwrap());
         assert!(http.enabled);
+        assert_eq!(http.max_body_bytes, 1048576);
+
+        let mut node = HashMap::new();
+        assert_eq!(parse_cors("http", &node), Ok(None));
+        node.insert("cors_allow_origin".to_string(), "*".to_string());
+        let cors = parse_cors("http", &node).unwrap().unwrap();
+        assert_eq!(cors.allow_origin, "*");
+        assert_eq!(cors.allow_headers, vec!["Content-Type".to_string()]);
+        node.insert(
+            "cors_allow_headers".to_string(),
+            "Content-Type, X-Request-Id".to_string(),
+        );
+        let cors = parse_cors("http", &node).unwrap().unwrap();
+        assert_eq!(cors.allow_headers, vec!["Content-Type", "X-Request-Id"]);
+        node.insert("cors_allow_origin".to_string(), " ".to_string());
+        let result = parse_cors("http", &node);
+        assert!(result.unwrap_err().contains("`http.cors_allow_origin`"));
+
+        let mut node = HashMap::new();
+        assert_eq!(parse_access_token("http", &node), Ok(None));
+        node.insert("access_token".to_string(), " my-token ".to_string());
+        let access_token = parse_access_token("http", &node).unwrap();
+        assert_eq!(access_token.as_deref(), Some("my-token"));
+        node.insert("access_token".to_string(), " ".to_string());
+        let result = parse_access_token("http", &node);
+        assert!(result.unwrap_err().contains("`http.access_token`"));
     }
 
     #[test]
@@ -267,6 +1431,201 @@ mod test {
 
         let remote = config.remote.as_ref().unwrap();
         assert_eq!(remote.nodes.len(), 1);
-        assert_eq!(remote.nodes[0], "127.0.0.1:9191".parse().unwrap());
+        assert_eq!(remote.nodes[0].address, "127.0.0.1:9191".parse().unwrap());
+        assert_eq!(remote.nodes[0].priority, DEFAULT_REMOTE_NODE_PRIORITY);
+    }
+
+    #[test]
+    fn config_try_load_error_test() {
+        let result = try_load_from("anor-config.missing");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("anor-config.missing"));
+    }
+
+    #[test]
+    fn config_invalid_value_test() {
+        let mut node = HashMap::new();
+        node.insert("listen_port".to_string(), "port".to_string());
+        let result = parse_listen_on("api", &node, "127.0.0.1", 9191);
+        assert!(result.unwrap_err().contains("`api.listen_port`"));
+
+        let mut node = HashMap::new();
+        node.insert("enabled".to_string(), "yes".to_string());
+        let result = parse_enabled("http", &node);
+        assert!(result.unwrap_err().contains("`http.enabled`"));
+
+        let mut node = HashMap::new();
+        node.insert("max_body_bytes".to_string(), "-1".to_string());
+        let result = parse_max_body_bytes("http", &node);
+        assert!(result.unwrap_err().contains("`http.max_body_bytes`"));
+
+        let mut node = HashMap::new();
+        node.insert("lock_wait_ms".to_string(), "5s".to_string());
+        let result = parse_lock_wait("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.lock_wait_ms`"));
+
+        let mut node = HashMap::new();
+        node.insert("flush_every_writes".to_string(), "often".to_string());
+        let result = parse_flush_every_writes("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.flush_every_writes`"));
+
+        let mut node = HashMap::new();
+        node.insert("max_tags".to_string(), "many".to_string());
+        let result = parse_item_limit("storage", &node, "max_tags");
+        assert!(result.unwrap_err().contains("`storage.max_tags`"));
+
+        let mut node = HashMap::new();
+        node.insert("io_timeout_ms".to_string(), "30s".to_string());
+        let result = parse_io_timeout("api", &node);
+        assert!(result.unwrap_err().contains("`api.io_timeout_ms`"));
+
+        let mut node = HashMap::new();
+        node.insert("idle_timeout_ms".to_string(), "1m".to_string());
+        let result = parse_idle_timeout("api", &node);
+        assert!(result.unwrap_err().contains("`api.idle_timeout_ms`"));
+
+        let mut node = HashMap::new();
+        node.insert("keepalive_interval_ms".to_string(), "10s".to_string());
+        let result = parse_keepalive("api", &node);
+        assert!(result.unwrap_err().contains("`api.keepalive_interval_ms`"));
+
+        let mut node = HashMap::new();
+        node.insert("max_conns_per_ip".to_string(), "-1".to_string());
+        let result = parse_max_conns_per_ip("api", &node);
+        assert!(result.unwrap_err().contains("`api.max_conns_per_ip`"));
+
+        let mut node = HashMap::new();
+        node.insert("max_frame_bytes".to_string(), "0".to_string());
+        let result = parse_max_frame_bytes("api", &node);
+        assert!(result.unwrap_err().contains("`api.max_frame_bytes`"));
+
+        let mut node = HashMap::new();
+        node.insert("audit".to_string(), "on".to_string());
+        let result = parse_audit("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.audit`"));
+
+        let mut node = HashMap::new();
+        node.insert("evict_expired_on_load".to_string(), "yes".to_string());
+        let result = parse_evict_expired_on_load("storage", &node);
+        assert!(result
+            .unwrap_err()
+            .contains("`storage.evict_expired_on_load`"));
+
+        let mut node = HashMap::new();
+        node.insert("read_only".to_string(), "1".to_string());
+        let result = parse_read_only("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.read_only`"));
+
+        let mut node = HashMap::new();
+        node.insert("request_timeout_ms".to_string(), "5s".to_string());
+        let result = parse_request_timeout("http", &node);
+        assert!(result.unwrap_err().contains("`http.request_timeout_ms`"));
+
+        let mut node = HashMap::new();
+        node.insert("initial_capacity".to_string(), "1k".to_string());
+        let result = parse_initial_capacity("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.initial_capacity`"));
+
+        let mut node = HashMap::new();
+        node.insert("bind_retries".to_string(), "-1".to_string());
+        let result = parse_bind_retries("http", &node);
+        assert!(result.unwrap_err().contains("`http.bind_retries`"));
+
+        let mut node = HashMap::new();
+        node.insert("id_scheme".to_string(), "UUID7".to_string());
+        assert_eq!(parse_id_scheme("storage", &node), Ok(IdScheme::Uuid7));
+        node.insert("id_scheme".to_string(), "uuid1".to_string());
+        let result = parse_id_scheme("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.id_scheme`"));
+
+        let mut node = HashMap::new();
+        node.insert("slow_lock_warn_ms".to_string(), "100ms".to_string());
+        let result = parse_slow_lock_warn("storage", &node);
+        assert!(result.unwrap_err().contains("`storage.slow_lock_warn_ms`"));
+    }
+
+    #[test]
+    fn config_display_test() {
+        let config = load();
+        let summary = config.to_string();
+        assert!(summary.contains("api:\n  listen_on: 127.0.0.1:9191\n  enabled: true\n"));
+        assert!(summary.contains("remote:\n  nodes: 127.0.0.1:9191\n"));
+    }
+
+    #[test]
+    fn network_address_ipv6_test() {
+        let address: NetworkAddress = "[::1]:9191".parse().unwrap();
+        assert_eq!(address.host, "::1");
+        assert_eq!(address.port, 9191);
+        assert_eq!(address.to_string(), "[::1]:9191");
+
+        // unbracketed IPv6 with a port is ambiguous
+        assert!("::1:9191".parse::<NetworkAddress>().is_err());
+
+        // listen addresses are given without port, brackets are optional
+        let mut node = HashMap::new();
+        node.insert("listen_addresses".to_string(), "::1, [::1]".to_string());
+        node.insert("listen_port".to_string(), "9191".to_string());
+        let listen_on = parse_listen_on("api", &node, "127.0.0.1", 9191).unwrap();
+        assert_eq!(listen_on, vec![address.clone(), address]);
+    }
+
+    #[test]
+    fn network_address_hostname_test() {
+        let address: NetworkAddress = "db.internal:9191".parse().unwrap();
+        assert_eq!(address.host, "db.internal");
+        assert_eq!(address.port, 9191);
+
+        let mut node = HashMap::new();
+        node.insert(
+            "nodes".to_string(),
+            "db.internal:9191, 127.0.0.1:9192".to_string(),
+        );
+        let remote = parse_remote("remote", &node).unwrap();
+        assert_eq!(remote.nodes[0].address, address);
+        assert_eq!(remote.nodes[1].address, "127.0.0.1:9192".parse().unwrap());
+
+        let mut node = HashMap::new();
+        node.insert("nodes".to_string(), "db internal:9191".to_string());
+        let result = parse_remote("remote", &node);
+        assert!(result.unwrap_err().contains("`remote.nodes`"));
+    }
+
+    #[test]
+    fn remote_node_priority_test() {
+        let mut node = HashMap::new();
+        node.insert(
+            "nodes".to_string(),
+            "127.0.0.1:9191, db.internal:9192#10, [::1]:9193#5".to_string(),
+        );
+        let remote = parse_remote("remote", &node).unwrap();
+        assert_eq!(remote.nodes[0].priority, DEFAULT_REMOTE_NODE_PRIORITY);
+        assert_eq!(remote.nodes[1].priority, 10);
+        assert_eq!(remote.nodes[2].priority, 5);
+        assert_eq!(remote.nodes[2].to_string(), "[::1]:9193#5");
+
+        let ordered: Vec<_> = remote
+            .nodes_by_priority()
+            .iter()
+            .map(|node| node.address.port)
+            .collect();
+        assert_eq!(ordered, vec![9192, 9193, 9191]);
+
+        let mut node = HashMap::new();
+        node.insert("nodes".to_string(), "127.0.0.1:9191#high".to_string());
+        let result = parse_remote("remote", &node);
+        assert!(result.unwrap_err().contains("`remote.nodes`"));
+    }
+
+    #[test]
+    fn network_address_resolve_test() {
+        use std::net::{TcpListener, TcpStream};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let address = NetworkAddress::new("localhost", port).unwrap();
+        assert!(address.to_socket_addrs().unwrap().count() > 0);
+        assert!(TcpStream::connect(&address).is_ok());
     }
 }
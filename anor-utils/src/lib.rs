@@ -7,7 +7,12 @@
 pub mod cargo_profile;
 pub mod config;
 pub mod envsubst;
+pub mod metrics;
+pub mod net;
+pub mod shutdown;
 pub mod threadpool;
 
 pub use config::Config;
+pub use metrics::Metrics;
+pub use shutdown::ShutdownToken;
 pub use threadpool::ThreadPool;
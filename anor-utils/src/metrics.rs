@@ -0,0 +1,104 @@
+//! Service Metrics
+//! Atomic counters shared between the service handlers
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Request counters by method and status, served bytes and active connections
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: RwLock<HashMap<(String, String), AtomicU64>>,
+    bytes_sent: AtomicU64,
+    active_connections: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a handled request and the bytes sent in response
+    pub fn record_request(&self, method: &str, status: &str, bytes_sent: u64) {
+        self.bytes_sent.fetch_add(bytes_sent, Ordering::Relaxed);
+
+        let label = (method.to_string(), status.to_string());
+        if let Ok(requests) = self.requests.read() {
+            if let Some(counter) = requests.get(&label) {
+                counter.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        if let Ok(mut requests) = self.requests.write() {
+            requests
+                .entry(label)
+                .or_default()
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of requests with the method and status
+    pub fn requests(&self, method: &str, status: &str) -> u64 {
+        let label = (method.to_string(), status.to_string());
+        match self.requests.read() {
+            Ok(requests) => requests
+                .get(&label)
+                .map_or(0, |counter| counter.load(Ordering::Relaxed)),
+            Err(_) => 0,
+        }
+    }
+
+    /// Returns the total number of requests
+    pub fn requests_total(&self) -> u64 {
+        match self.requests.read() {
+            Ok(requests) => requests
+                .values()
+                .map(|counter| counter.load(Ordering::Relaxed))
+                .sum(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Returns the number of bytes sent in responses
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn connection_opened(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of currently open connections
+    pub fn active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metrics_test() {
+        let metrics = Metrics::new();
+        metrics.record_request("GET", "200", 10);
+        metrics.record_request("GET", "200", 5);
+        metrics.record_request("GET", "404", 0);
+
+        assert_eq!(metrics.requests("GET", "200"), 2);
+        assert_eq!(metrics.requests("GET", "404"), 1);
+        assert_eq!(metrics.requests("HEAD", "200"), 0);
+        assert_eq!(metrics.requests_total(), 3);
+        assert_eq!(metrics.bytes_sent(), 15);
+
+        metrics.connection_opened();
+        metrics.connection_opened();
+        metrics.connection_closed();
+        assert_eq!(metrics.active_connections(), 1);
+    }
+}
@@ -0,0 +1,78 @@
+//! Network Helpers
+//! Binding of the listen addresses of the services
+
+use crate::config::NetworkAddress;
+use std::io;
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+/// wait before the first bind retry, doubled on every further retry
+const BIND_RETRY_INITIAL_BACKOFF_MILLISECONDS: u64 = 100;
+
+/// upper bound of the wait between bind retries
+const BIND_RETRY_MAX_BACKOFF_MILLISECONDS: u64 = 2000;
+
+/// Binds the address, retrying up to `retries` times with backoff while binding fails,
+/// e.g. while the port is still held by the previous instance of a restarting server
+/// Returns the error of the last attempt once the retries are exhausted
+pub fn bind_with_retries(address: &NetworkAddress, retries: u32) -> io::Result<TcpListener> {
+    let mut backoff = Duration::from_millis(BIND_RETRY_INITIAL_BACKOFF_MILLISECONDS);
+    let max_backoff = Duration::from_millis(BIND_RETRY_MAX_BACKOFF_MILLISECONDS);
+    let mut attempt = 0;
+    loop {
+        match TcpListener::bind(address) {
+            Ok(listener) => return Ok(listener),
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "Could not bind {}: {}, retry {} of {} in {} ms",
+                    address,
+                    err,
+                    attempt,
+                    retries,
+                    backoff.as_millis()
+                );
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(max_backoff);
+            }
+            Err(err) => {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!(
+                        "Could not bind {} after {} retries: {}",
+                        address, retries, err
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn bind_with_retries_test() {
+        let holder = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = holder.local_addr().unwrap().port();
+        let address = NetworkAddress::new("127.0.0.1", port).unwrap();
+
+        let err = bind_with_retries(&address, 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(err.to_string().contains("after 0 retries"));
+
+        // the port is released while retrying
+        let started_on = Instant::now();
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(250));
+            drop(holder);
+        });
+        let listener = bind_with_retries(&address, 5).unwrap();
+        assert_eq!(listener.local_addr().unwrap().port(), port);
+        assert!(started_on.elapsed() >= Duration::from_millis(250));
+        release.join().unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+//! Shutdown Coordination
+//! A shutdown signal shared between the services
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+/// Shutdown signal observed by the accept loops of the services
+/// Synchronous loops poll `is_triggered`, asynchronous loops await `triggered`
+#[derive(Debug, Default)]
+pub struct ShutdownToken {
+    triggered: AtomicBool,
+    notify: Notify,
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signals the shutdown and wakes the tasks awaiting `triggered`
+    pub fn trigger(&self) {
+        self.triggered.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Returns whether the shutdown was signaled
+    pub fn is_triggered(&self) -> bool {
+        self.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the shutdown is signaled, immediately if it was signaled already
+    pub async fn triggered(&self) {
+        let mut notified = std::pin::pin!(self.notify.notified());
+        // register before checking the state, so a trigger in between is not missed
+        notified.as_mut().enable();
+        if self.is_triggered() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn shutdown_token_test() {
+        let token = Arc::new(ShutdownToken::new());
+        assert!(!token.is_triggered());
+
+        // sync-style accept loop, polls a non-blocking listener
+        let sync_token = token.clone();
+        let sync_loop = thread::spawn(move || {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.set_nonblocking(true).unwrap();
+            while !sync_token.is_triggered() {
+                _ = listener.accept();
+                thread::sleep(Duration::from_millis(10));
+            }
+            Instant::now()
+        });
+
+        // async-style accept loop, awaits the token next to the listener
+        let async_token = token.clone();
+        let async_loop = thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+                loop {
+                    tokio::select! {
+                        _ = listener.accept() => {}
+                        _ = async_token.triggered() => break,
+                    }
+                }
+                Instant::now()
+            })
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        let triggered_on = Instant::now();
+        token.trigger();
+
+        for handle in [sync_loop, async_loop] {
+            let exited_on = handle.join().unwrap();
+            assert!(exited_on.duration_since(triggered_on) < Duration::from_millis(500));
+        }
+
+        // a late waiter completes immediately
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(token.triggered());
+    }
+}
@@ -2,7 +2,10 @@
 //! The implementation is taken from the [book](https://doc.rust-lang.org/book/ch20-02-multithreaded.html)
 
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
 };
 
@@ -11,6 +14,14 @@ type Job = Box<dyn FnOnce() + Send + 'static>;
 pub struct ThreadPool {
     workers: Vec<Worker>,
     sender: Option<mpsc::Sender<Job>>,
+    counts: Arc<JobCounts>,
+}
+
+/// Numbers of the jobs waiting for a worker and being executed
+#[derive(Default)]
+struct JobCounts {
+    queued: AtomicUsize,
+    active: AtomicUsize,
 }
 
 struct Worker {
@@ -40,21 +51,44 @@ impl ThreadPool {
     ///
     /// The `new` function will panic if the size is zero.
     pub fn new(size: usize) -> ThreadPool {
+        Self::build(size, None)
+    }
+
+    /// Create a new ThreadPool with the given stack size of the worker threads.
+    ///
+    /// The size is the number of threads in the pool, the stack size is in bytes.
+    ///
+    /// # Panics
+    ///
+    /// The `with_stack_size` function will panic if the size or the stack size is zero.
+    pub fn with_stack_size(size: usize, stack_size: usize) -> ThreadPool {
+        assert!(stack_size > 0);
+        Self::build(size, Some(stack_size))
+    }
+
+    fn build(size: usize, stack_size: Option<usize>) -> ThreadPool {
         assert!(size > 0);
 
         let (sender, receiver) = mpsc::channel();
 
         let receiver = Arc::new(Mutex::new(receiver));
+        let counts = Arc::new(JobCounts::default());
 
         let mut workers = Vec::with_capacity(size);
 
         for id in 0..size {
-            workers.push(Worker::new(id, receiver.clone()));
+            workers.push(Worker::new(
+                id,
+                receiver.clone(),
+                counts.clone(),
+                stack_size,
+            ));
         }
 
         ThreadPool {
             workers,
             sender: Some(sender),
+            counts,
         }
     }
 
@@ -64,9 +98,20 @@ impl ThreadPool {
     {
         let job = Box::new(f);
 
+        self.counts.queued.fetch_add(1, Ordering::SeqCst);
         self.sender.as_ref().unwrap().send(job).unwrap();
     }
 
+    /// Returns the number of jobs being executed
+    pub fn active_count(&self) -> usize {
+        self.counts.active.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of jobs waiting for a free worker
+    pub fn queued_count(&self) -> usize {
+        self.counts.queued.load(Ordering::SeqCst)
+    }
+
     /// blocks the executor and waits for the completion of active jobs
     pub fn wait_for_completion(&self) {
         todo!()
@@ -74,14 +119,27 @@ impl ThreadPool {
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
+    fn new(
+        id: usize,
+        receiver: Arc<Mutex<mpsc::Receiver<Job>>>,
+        counts: Arc<JobCounts>,
+        stack_size: Option<usize>,
+    ) -> Worker {
+        let mut builder = thread::Builder::new().name(format!("worker-{id}"));
+        if let Some(stack_size) = stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+
+        let thread = builder.spawn(move || loop {
             let message = receiver.lock().unwrap().recv();
 
             match message {
                 Ok(job) => {
                     tracing::trace!("Worker {id} got a job; executing.");
+                    counts.active.fetch_add(1, Ordering::SeqCst);
+                    counts.queued.fetch_sub(1, Ordering::SeqCst);
                     job();
+                    counts.active.fetch_sub(1, Ordering::SeqCst);
                 }
                 Err(_) => {
                     tracing::trace!("Worker {id} disconnected; shutting down.");
@@ -89,6 +147,7 @@ impl Worker {
                 }
             }
         });
+        let thread = thread.expect("failed to spawn a worker thread");
 
         Worker {
             id,
@@ -133,4 +192,58 @@ pub mod test {
 
         assert_eq!(total.load(Ordering::SeqCst), 328350);
     }
+
+    #[test]
+    fn with_stack_size_test() {
+        // larger than the default stack size of spawned threads
+        const BUF_SIZE: usize = 8 * 1024 * 1024;
+
+        let (sender, receiver) = mpsc::channel();
+        let pool = ThreadPool::with_stack_size(2, 4 * BUF_SIZE);
+        pool.execute(move || {
+            let buf = std::hint::black_box([1u8; BUF_SIZE]);
+            sender
+                .send(buf.iter().map(|v| *v as usize).sum::<usize>())
+                .unwrap();
+        });
+
+        let sum = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(sum, BUF_SIZE);
+    }
+
+    #[test]
+    fn job_counts_test() {
+        use std::sync::RwLock;
+        use std::time::Instant;
+
+        let wait_until = |condition: &dyn Fn() -> bool| {
+            let started_on = Instant::now();
+            while !condition() {
+                assert!(started_on.elapsed() < Duration::from_secs(10));
+                thread::sleep(Duration::from_millis(5));
+            }
+        };
+
+        let pool = ThreadPool::new(2);
+        assert_eq!((pool.active_count(), pool.queued_count()), (0, 0));
+
+        // the jobs block until the gate is released
+        let gate = Arc::new(RwLock::new(()));
+        let closed = gate.write().unwrap();
+        for _ in 0..5 {
+            let gate = gate.clone();
+            pool.execute(move || drop(gate.read().unwrap()));
+        }
+        wait_until(&|| pool.active_count() == 2);
+        assert_eq!(pool.queued_count(), 3);
+
+        drop(closed);
+        wait_until(&|| pool.active_count() == 0 && pool.queued_count() == 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn with_stack_size_zero_test() {
+        _ = ThreadPool::with_stack_size(2, 0);
+    }
 }